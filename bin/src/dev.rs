@@ -0,0 +1,482 @@
+//! `--flash.dev`: block production driven in-process from a mock L1, without an op-node.
+//!
+//! The sequencer drives the engine as op-node does: a forkchoice update with payload attributes
+//! starting with the L1 info deposit of the current L1 origin, then the built payload is inserted
+//! and made canonical, which feeds it to the batcher. The L1 origin is mocked, with an L1 block
+//! every L1 block time. Blocks are final once produced, which the batcher prunes channels up to.
+
+use alloy_consensus::{Header, Transaction, Typed2718};
+use alloy_eips::eip2718::Encodable2718;
+use alloy_primitives::{Address, B64, B256, TxKind, U256, address, keccak256};
+use alloy_rpc_types_engine::{ForkchoiceState, PayloadAttributes};
+use flash_batcher::{
+    channel_builder::ChannelBuilder,
+    l1_info::{DEPOSIT_TX_TYPE, L1BlockInfo},
+};
+use op_alloy_consensus::TxDeposit;
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use reth_node_api::{
+    BeaconConsensusEngineHandle, BuiltPayload, EngineApiMessageVersion, PayloadKind, PayloadTypes,
+};
+use reth_optimism_chainspec::OpChainSpec;
+use reth_optimism_forks::OpHardforks;
+use reth_optimism_node::OpEngineTypes;
+use reth_optimism_primitives::OpTransactionSigned;
+use reth_payload_builder::PayloadBuilderHandle;
+use reth_primitives_traits::SealedHeader;
+use reth_storage_api::{BlockNumReader, HeaderProvider, TransactionsProvider};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, info};
+
+/// Sender of L1 info deposits.
+const L1_INFO_DEPOSITOR: Address = address!("0xDeaDDEaDDeAdDeAdDEAdDEaddeAddEAdDEAd0001");
+/// L1Block predeploy, receiving L1 info deposits.
+const L1_BLOCK_ADDRESS: Address = address!("0x4200000000000000000000000000000000000015");
+/// SequencerFeeVault predeploy, the fee recipient of L2 blocks.
+const SEQUENCER_FEE_VAULT: Address = address!("0x4200000000000000000000000000000000000011");
+/// Gas of L1 info deposits since Regolith.
+const L1_INFO_DEPOSIT_GAS: u64 = 1_000_000;
+/// Base fee of the mock L1 blocks.
+const MOCK_L1_BASE_FEE: u64 = 1_000_000_000;
+
+/// Command line arguments of the dev mode.
+#[derive(Debug, Clone, clap::Args)]
+#[command(next_help_heading = "Flash dev")]
+pub struct FlashDevArgs {
+    /// Produce blocks in-process from a mock L1, instead of following an op-node.
+    #[arg(id = "flash_dev", long = "flash.dev", conflicts_with = "op_node_rpc")]
+    pub enabled: bool,
+
+    /// Seconds between L2 blocks.
+    #[arg(
+        long = "flash.dev.block-time",
+        value_name = "SECONDS",
+        default_value_t = 2,
+        requires = "flash_dev"
+    )]
+    pub block_time: u64,
+
+    /// Seconds between mock L1 blocks, the L2 epochs.
+    #[arg(
+        long = "flash.dev.l1-block-time",
+        value_name = "SECONDS",
+        default_value_t = 12,
+        requires = "flash_dev"
+    )]
+    pub l1_block_time: u64,
+}
+
+impl FlashDevArgs {
+    pub fn block_time(&self) -> Duration {
+        Duration::from_secs(self.block_time)
+    }
+}
+
+/// Mock L1 block an L2 block is built on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MockL1Origin {
+    number: u64,
+    timestamp: u64,
+    /// Position of the L2 block in the epoch.
+    sequence_number: u64,
+}
+
+impl MockL1Origin {
+    fn hash(&self) -> B256 {
+        keccak256(self.number.to_be_bytes())
+    }
+
+    /// Origin of the L2 block following the one on this origin, at `timestamp`: the latest mock
+    /// L1 block by then, as the mock L1 produces one every `l1_block_time`.
+    fn next(&self, timestamp: u64, l1_block_time: u64) -> Self {
+        let elapsed = timestamp.saturating_sub(self.timestamp) / l1_block_time;
+        if elapsed > 0 {
+            Self {
+                number: self.number + elapsed,
+                timestamp: self.timestamp + elapsed * l1_block_time,
+                sequence_number: 0,
+            }
+        } else {
+            Self {
+                sequence_number: self.sequence_number + 1,
+                ..*self
+            }
+        }
+    }
+
+    fn l1_block_info(&self) -> L1BlockInfo {
+        L1BlockInfo {
+            number: self.number,
+            timestamp: self.timestamp,
+            hash: self.hash(),
+            sequence_number: self.sequence_number,
+            base_fee: U256::from(MOCK_L1_BASE_FEE),
+            blob_base_fee: Some(U256::from(1)),
+            batcher_hash: B256::ZERO,
+            base_fee_scalar: Some(0),
+            blob_base_fee_scalar: Some(0),
+        }
+    }
+}
+
+/// Source hash of the L1 info deposit of an L2 block, as op-node derives it.
+fn l1_info_source_hash(l1_hash: B256, sequence_number: u64) -> B256 {
+    let deposit_id = keccak256(
+        [
+            l1_hash.as_slice(),
+            B256::left_padding_from(&sequence_number.to_be_bytes()).as_slice(),
+        ]
+        .concat(),
+    );
+    // L1 info deposit source domain
+    let domain = B256::with_last_byte(1);
+    keccak256([domain.as_slice(), deposit_id.as_slice()].concat())
+}
+
+/// Payload attributes of the block at `timestamp` on `origin`, as op-node builds them, keeping
+/// the `gas_limit` of the parent.
+fn payload_attributes(
+    chain_spec: &OpChainSpec,
+    gas_limit: u64,
+    timestamp: u64,
+    origin: &MockL1Origin,
+) -> eyre::Result<OpPayloadAttributes> {
+    if !chain_spec.is_ecotone_active_at_timestamp(timestamp) {
+        return Err(eyre::eyre!(
+            "Dev mode requires Ecotone, inactive at {}",
+            timestamp
+        ));
+    }
+
+    let isthmus = chain_spec.is_isthmus_active_at_timestamp(timestamp);
+    let l1_info_deposit = TxDeposit {
+        source_hash: l1_info_source_hash(origin.hash(), origin.sequence_number),
+        from: L1_INFO_DEPOSITOR,
+        to: TxKind::Call(L1_BLOCK_ADDRESS),
+        mint: 0,
+        value: U256::ZERO,
+        gas_limit: L1_INFO_DEPOSIT_GAS,
+        is_system_transaction: false,
+        input: origin.l1_block_info().encode_calldata(isthmus).into(),
+    };
+
+    Ok(OpPayloadAttributes {
+        payload_attributes: PayloadAttributes {
+            timestamp,
+            prev_randao: origin.hash(),
+            suggested_fee_recipient: SEQUENCER_FEE_VAULT,
+            withdrawals: Some(Vec::new()),
+            parent_beacon_block_root: Some(B256::ZERO),
+        },
+        transactions: Some(vec![l1_info_deposit.encoded_2718().into()]),
+        no_tx_pool: Some(false),
+        gas_limit: Some(gas_limit),
+        // Zero parameters make the payload builder use the chain ones
+        eip_1559_params: chain_spec
+            .is_holocene_active_at_timestamp(timestamp)
+            .then_some(B64::ZERO),
+    })
+}
+
+/// Tells the batcher blocks up to `number` are final, so their channels can be pruned.
+fn set_finalized(channel_builder: &Mutex<ChannelBuilder>, number: u64) -> eyre::Result<()> {
+    channel_builder
+        .lock()
+        .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?
+        .set_local_finalized(number);
+    Ok(())
+}
+
+/// Produces an L2 block every block time, on top of the canonical head.
+pub struct DevSequencer {
+    chain_spec: Arc<OpChainSpec>,
+    to_engine: BeaconConsensusEngineHandle<OpEngineTypes>,
+    payload_builder: PayloadBuilderHandle<OpEngineTypes>,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    block_time: Duration,
+    l1_block_time: u64,
+    head: SealedHeader,
+    origin: MockL1Origin,
+}
+
+impl DevSequencer {
+    /// Starts from the canonical head, and its L1 origin if it has an L1 info deposit.
+    pub fn new<P>(
+        provider: &P,
+        chain_spec: Arc<OpChainSpec>,
+        to_engine: BeaconConsensusEngineHandle<OpEngineTypes>,
+        payload_builder: PayloadBuilderHandle<OpEngineTypes>,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        args: &FlashDevArgs,
+    ) -> eyre::Result<Self>
+    where
+        P: BlockNumReader
+            + HeaderProvider<Header = Header>
+            + TransactionsProvider<Transaction = OpTransactionSigned>,
+    {
+        if args.block_time == 0 || args.l1_block_time == 0 {
+            return Err(eyre::eyre!("Dev block times must be greater than 0"));
+        }
+
+        let number = provider.best_block_number()?;
+        let head = provider
+            .sealed_header(number)?
+            .ok_or_else(|| eyre::eyre!("Head block {} not found", number))?;
+
+        let l1_info = provider
+            .transactions_by_block(number.into())?
+            .and_then(|transactions| transactions.into_iter().next())
+            .filter(|tx| tx.ty() == DEPOSIT_TX_TYPE)
+            .and_then(|tx| L1BlockInfo::decode_calldata(tx.input()).ok());
+        let origin = match l1_info {
+            Some(l1_info) => MockL1Origin {
+                number: l1_info.number,
+                timestamp: l1_info.timestamp,
+                sequence_number: l1_info.sequence_number,
+            },
+            None => MockL1Origin {
+                number: 0,
+                timestamp: head.timestamp,
+                sequence_number: 0,
+            },
+        };
+
+        set_finalized(&channel_builder, head.number)?;
+
+        Ok(Self {
+            chain_spec,
+            to_engine,
+            payload_builder,
+            channel_builder,
+            block_time: args.block_time(),
+            l1_block_time: args.l1_block_time,
+            head,
+            origin,
+        })
+    }
+
+    /// Builds a block on the head, inserts it and makes it canonical.
+    async fn produce_block(&mut self) -> eyre::Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        // Blocks follow the wall clock rather than catching up from an old genesis
+        let timestamp = (self.head.timestamp + self.block_time.as_secs()).max(now);
+        let origin = self.origin.next(timestamp, self.l1_block_time);
+        let attributes =
+            payload_attributes(&self.chain_spec, self.head.gas_limit, timestamp, &origin)?;
+
+        let updated = self
+            .to_engine
+            .fork_choice_updated(
+                forkchoice_state(self.head.hash()),
+                Some(attributes),
+                EngineApiMessageVersion::default(),
+            )
+            .await?;
+        let payload_id = updated.payload_id.ok_or_else(|| {
+            eyre::eyre!(
+                "Forkchoice update started no payload: {:?}",
+                updated.payload_status
+            )
+        })?;
+
+        let payload = self
+            .payload_builder
+            .resolve_kind(payload_id, PayloadKind::WaitForPending)
+            .await
+            .ok_or_else(|| eyre::eyre!("Payload {} not found", payload_id))??;
+        let block = payload.block().clone();
+
+        let status = self
+            .to_engine
+            .new_payload(OpEngineTypes::block_to_payload(block.clone()))
+            .await?;
+        if !status.is_valid() {
+            return Err(eyre::eyre!(
+                "Built block {} is invalid: {:?}",
+                block.number,
+                status
+            ));
+        }
+
+        // Once canonical, the block reaches the batcher
+        self.to_engine
+            .fork_choice_updated(
+                forkchoice_state(block.hash()),
+                None,
+                EngineApiMessageVersion::default(),
+            )
+            .await?;
+
+        debug!(
+            "Produced block {} {} on L1 origin {} with {} transactions",
+            block.number,
+            block.hash(),
+            origin.number,
+            block.body().transactions.len()
+        );
+        set_finalized(&self.channel_builder, block.number)?;
+        self.head = block.sealed_header().clone();
+        self.origin = origin;
+
+        Ok(())
+    }
+
+    /// Produces a block on every tick of the block time, forever.
+    pub async fn run(mut self) {
+        info!(
+            "Producing blocks every {:?} from block {}, on mock L1 block {}",
+            self.block_time, self.head.number, self.origin.number
+        );
+        let mut interval = tokio::time::interval(self.block_time);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.produce_block().await {
+                error!("Failed to produce block: {}", e);
+            }
+        }
+    }
+}
+
+/// Forkchoice with `hash` as head. Without derivation, dev blocks are final once produced.
+fn forkchoice_state(hash: B256) -> ForkchoiceState {
+    ForkchoiceState {
+        head_block_hash: hash,
+        safe_block_hash: hash,
+        finalized_block_hash: hash,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_eips::eip2718::Decodable2718;
+    use alloy_primitives::{Bytes, b256};
+    use flash_chainspec::{
+        FlashChainArgs,
+        presets::{FLASH_DEV, preset},
+    };
+    use op_alloy_consensus::OpTxEnvelope;
+    use reth_optimism_chainspec::OP_MAINNET;
+
+    const ORIGIN: MockL1Origin = MockL1Origin {
+        number: 10,
+        timestamp: 1_000,
+        sequence_number: 2,
+    };
+
+    /// The L1 info deposit, the only transaction of the attributes.
+    fn l1_info_deposit(attributes: &OpPayloadAttributes) -> TxDeposit {
+        let transactions = attributes.transactions.as_ref().unwrap();
+        assert_eq!(transactions.len(), 1);
+
+        match OpTxEnvelope::decode_2718(&mut transactions[0].as_ref()).unwrap() {
+            OpTxEnvelope::Deposit(deposit) => deposit.into_inner(),
+            tx => panic!("Expected the L1 info deposit, got {tx:?}"),
+        }
+    }
+
+    #[test]
+    fn l1_info_source_hash_matches_op_node() {
+        // TestL1InfoDepositSource of op-node
+        assert_eq!(
+            l1_info_source_hash(
+                b256!("0xc00e5d67c2755389aded7d8b151cbd5bcdf7ed275ad5e028b664880fc7581c77"),
+                4
+            ),
+            b256!("0x0586c503340591999b8b38bc9834bb16aec7d5bc00eb5587ab139c9ddab81977")
+        );
+    }
+
+    #[test]
+    fn origin_follows_the_mock_l1() {
+        // Until the next L1 block, blocks stay in the epoch
+        assert_eq!(
+            ORIGIN.next(1_011, 12),
+            MockL1Origin {
+                sequence_number: 3,
+                ..ORIGIN
+            }
+        );
+        assert_eq!(
+            ORIGIN.next(990, 12),
+            MockL1Origin {
+                sequence_number: 3,
+                ..ORIGIN
+            }
+        );
+
+        // The next epoch starts over
+        assert_eq!(
+            ORIGIN.next(1_012, 12),
+            MockL1Origin {
+                number: 11,
+                timestamp: 1_012,
+                sequence_number: 0,
+            }
+        );
+        // After a pause, on the latest L1 block
+        assert_eq!(
+            ORIGIN.next(1_050, 12),
+            MockL1Origin {
+                number: 14,
+                timestamp: 1_048,
+                sequence_number: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn attributes_start_with_the_l1_info_deposit() {
+        let chain_spec = preset(FLASH_DEV)
+            .unwrap()
+            .chainspec(&FlashChainArgs::default())
+            .unwrap();
+        let attributes = payload_attributes(&chain_spec, 30_000_000, 1_010, &ORIGIN).unwrap();
+
+        assert_eq!(attributes.payload_attributes.timestamp, 1_010);
+        assert_eq!(attributes.payload_attributes.prev_randao, ORIGIN.hash());
+        assert_eq!(
+            attributes.payload_attributes.suggested_fee_recipient,
+            SEQUENCER_FEE_VAULT
+        );
+        assert_eq!(attributes.gas_limit, Some(30_000_000));
+        assert_eq!(attributes.no_tx_pool, Some(false));
+        // Holocene is active, zero parameters select the chain ones
+        assert_eq!(attributes.eip_1559_params, Some(B64::ZERO));
+
+        let deposit = l1_info_deposit(&attributes);
+        assert_eq!(deposit.source_hash, l1_info_source_hash(ORIGIN.hash(), 2));
+        assert_eq!(deposit.from, L1_INFO_DEPOSITOR);
+        assert_eq!(deposit.to, TxKind::Call(L1_BLOCK_ADDRESS));
+        assert_eq!(deposit.gas_limit, L1_INFO_DEPOSIT_GAS);
+        assert!(!deposit.is_system_transaction);
+        // Isthmus is active too
+        assert_eq!(
+            deposit.input,
+            Bytes::from(ORIGIN.l1_block_info().encode_calldata(true))
+        );
+        assert_eq!(
+            L1BlockInfo::decode_calldata(&deposit.input).unwrap(),
+            ORIGIN.l1_block_info()
+        );
+    }
+
+    #[test]
+    fn attributes_follow_the_active_forks() {
+        // OP Mainnet activated Ecotone at 1710374401, Holocene and Isthmus later
+        assert!(payload_attributes(&OP_MAINNET, 30_000_000, 1_710_374_400, &ORIGIN).is_err());
+
+        let attributes =
+            payload_attributes(&OP_MAINNET, 30_000_000, 1_710_374_401, &ORIGIN).unwrap();
+        assert_eq!(attributes.eip_1559_params, None);
+        assert_eq!(
+            l1_info_deposit(&attributes).input,
+            Bytes::from(ORIGIN.l1_block_info().encode_calldata(false))
+        );
+    }
+}
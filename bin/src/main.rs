@@ -1,55 +1,338 @@
+mod dev;
+
 use std::sync::{Arc, Mutex};
 
-use clap::Parser;
-use flash_batcher::{BatcherExEx, channel_builder::ChannelBuilder, db::DB};
-use flash_chainspec::FlashChainSpecParser;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use flash_batcher::{
+    BatcherExEx,
+    alt_da::{AltDaClient, AltDaSubmitter},
+    args::BatcherArgs,
+    channel_builder::{CHANNEL_TIMER_INTERVAL, ChannelBuilder, ChannelTimer},
+    cli::BatcherCommand,
+    db::{DB, DEFAULT_DB_PATH, DbLock},
+    l1::{BatchInbox, L1Client},
+    l1_cost::L1FeePoller,
+    op_node::{OpNodeClient, SyncStatusPoller},
+    pruner::Pruner,
+    rpc::{
+        BatcherAccountingApiServer, BatcherAccountingRpc, BatcherAdminApiServer, BatcherAdminRpc,
+        BatcherApiServer, BatcherRpc, BatcherVerifierApiServer, BatcherVerifierRpc,
+    },
+    signer::MockSignerCommand,
+    submitter::BatchSubmitter,
+    system_config::SystemConfigWatcher,
+    throttle::Throttler,
+};
+use flash_chainspec::{FlashChainSpecParser, genesis::GenesisCommand};
+use flash_da_server::DaServerCommand;
 use reth_optimism_cli::Cli;
 use reth_optimism_node::{OpNode, args::RollupArgs};
+use reth_optimism_payload_builder::config::OpDAConfig;
+use reth_rpc_server_types::RethRpcModule;
+use reth_tracing::{RethTracer, Tracer};
 use tracing::{error, info};
 
+use crate::dev::{DevSequencer, FlashDevArgs};
+
+/// Node arguments, extending the op-reth rollup arguments with the batcher ones.
+#[derive(Debug, Clone, clap::Args)]
+struct FlashArgs {
+    #[command(flatten)]
+    rollup: RollupArgs,
+
+    #[command(flatten)]
+    batcher: BatcherArgs,
+
+    #[command(flatten)]
+    dev: FlashDevArgs,
+}
+
+/// The reth commands, running the node with the flash chain arguments.
+type FlashNodeCli = Cli<FlashChainSpecParser, FlashArgs>;
+
+/// Flash chain commands, run instead of the reth ones.
+#[derive(Debug, Parser)]
+#[command(name = "flash_chain")]
+struct FlashCli {
+    #[command(subcommand)]
+    command: FlashCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum FlashCommands {
+    /// Inspect and operate the batcher database
+    Batcher(BatcherCommand<FlashChainSpecParser>),
+    /// Run a file-backed alt-DA server
+    DaServer(DaServerCommand),
+    /// Generate the genesis and rollup config of a new chain
+    Genesis(GenesisCommand),
+    /// Serve `eth_signTransaction` from local keys, mocking a remote signer
+    MockSigner(MockSignerCommand),
+}
+
+impl FlashCommands {
+    fn execute(self) -> eyre::Result<()> {
+        match self {
+            FlashCommands::Batcher(command) => command.execute::<OpNode>(),
+            FlashCommands::DaServer(command) => {
+                let _guard = RethTracer::new().init()?;
+                command.execute()
+            }
+            FlashCommands::Genesis(command) => command.execute(),
+            FlashCommands::MockSigner(command) => {
+                let _guard = RethTracer::new().init()?;
+                command.execute()
+            }
+        }
+    }
+}
+
+/// Locks and opens the batcher database of the node, migrating it to the current schema.
+///
+/// The lock is held until the node exits, so `batcher rebuild` can't run along it.
+fn open_db() -> eyre::Result<(DbLock, DB)> {
+    let lock = DbLock::acquire(DEFAULT_DB_PATH)
+        .map_err(|e| eyre::eyre!("Failed to lock database {}: {}", DEFAULT_DB_PATH, e))?;
+    let db = DB::new(DEFAULT_DB_PATH)
+        .map_err(|e| eyre::eyre!("Failed to create database {}: {}", DEFAULT_DB_PATH, e))?;
+    db.initialize_database()
+        .map_err(|e| eyre::eyre!("Failed to initialize database schema: {}", e))?;
+    Ok((lock, db))
+}
+
 fn main() {
     reth_cli_util::sigsegv_handler::install();
 
-    let db = match DB::new("batcher.db") {
-        Ok(db) => Arc::new(Mutex::new(db)),
-        Err(e) => {
-            error!("Failed to create database: {}", e);
+    // The flash chain genesis and hardforks must be known before `--chain` is parsed
+    let args = match flash_chainspec::take_flash_args(std::env::args()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
             std::process::exit(1);
         }
     };
 
-    if let Err(e) = db.lock().unwrap().initialize_database() {
-        error!("Failed to initialize database schema: {}", e);
-        std::process::exit(1);
+    // Flash chain commands don't run a node, dispatch them before the reth ones
+    if args
+        .get(1)
+        .is_some_and(|command| FlashCommands::has_subcommand(command))
+    {
+        if let Err(err) = FlashCli::parse_from(args).command.execute() {
+            eprintln!("Error: {err:?}");
+            std::process::exit(1);
+        }
+        return;
     }
 
-    // Validate batch size
-    const BATCH_SIZE: u64 = 10;
-    if BATCH_SIZE == 0 {
-        error!("Batch size must be greater than 0");
-        std::process::exit(1);
-    }
+    // The flash chain commands are listed along the reth ones in the help
+    let matches =
+        FlashCommands::augment_subcommands(FlashNodeCli::command()).get_matches_from(args);
 
-    let channel_builder = ChannelBuilder::new(db, BATCH_SIZE);
-    info!(
-        "Initialized channel builder with batch size: {}",
-        BATCH_SIZE
-    );
+    // Only the node runs the batcher, the other reth commands leave its database alone
+    let (_db_lock, db) = match matches.subcommand_name() {
+        Some("node") => match open_db() {
+            Ok((lock, db)) => (Some(lock), Some(Arc::new(Mutex::new(db)))),
+            Err(err) => {
+                eprintln!("Error: {err:?}");
+                std::process::exit(1);
+            }
+        },
+        _ => (None, None),
+    };
 
-    if let Err(err) =
-        Cli::<FlashChainSpecParser, RollupArgs>::parse().run(async move |builder, rollup_args| {
+    if let Err(err) = FlashNodeCli::from_arg_matches(&matches)
+        .unwrap_or_else(|err| err.exit())
+        .run(async move |builder, args| {
             info!(target: "reth::cli", "Launching node with flash batcher");
+            let db = db.ok_or_else(|| eyre::eyre!("The batcher database wasn't opened"))?;
 
-            let node = OpNode::new(rollup_args);
+            // Shared with `miner_setMaxDASize`, and lowered by the batcher when throttling
+            let da_config = OpDAConfig::default();
+            let node = OpNode::new(args.rollup).with_da_config(da_config.clone());
+
+            // Pruning waits for the finalized head, reported by op-node or set by dev mode
+            if args.batcher.retention_policy().is_some()
+                && args.batcher.op_node_rpc.is_none()
+                && !args.dev.enabled
+            {
+                eyre::bail!(
+                    "--batcher.retention-age and --batcher.retention-depth require --batcher.op-node-rpc or --flash.dev"
+                );
+            }
+
+            let flush_policy = args.batcher.flush_policy();
+            let mut channel_builder = ChannelBuilder::new(db.clone(), flush_policy);
+            channel_builder.set_codec(args.batcher.codec);
+            channel_builder.set_da_type(args.batcher.da_type);
+            if let Some(budget) = args.batcher.l1_budget() {
+                channel_builder.set_l1_budget(budget);
+            }
+            let alt_da = args.batcher.alt_da_url.as_deref().map(|url| {
+                channel_builder.set_alt_da(true);
+                AltDaClient::new(url, args.batcher.alt_da_commitment)
+            });
+            if let Some(keys) = args.batcher.keys.load().await? {
+                info!("Loaded batcher keys {:?}", keys.addresses());
+                channel_builder.set_keys(Arc::new(keys));
+            } else if alt_da.is_some() {
+                eyre::bail!("--batcher.alt-da-url requires a batcher key to post commitments");
+            }
+            // `--batcher.alt-da-url` requires both
+            let inbox = match (args.batcher.l1_rpc.as_deref(), args.batcher.batch_inbox) {
+                (Some(url), Some(address)) => Some(BatchInbox::new(L1Client::new(url)?, address)),
+                _ => None,
+            };
+            let channel_builder = Arc::new(Mutex::new(channel_builder));
+            info!(
+                "Initialized channel builder with flush policy: {:?}",
+                flush_policy
+            );
+
+            let op_node = args
+                .batcher
+                .op_node_rpc
+                .as_deref()
+                .map(OpNodeClient::new)
+                .transpose()?;
+            let exex_op_node = op_node.clone();
+            let poller_channel_builder = channel_builder.clone();
+            let throttler_channel_builder = channel_builder.clone();
+            let submitter_channel_builder = channel_builder.clone();
+            let watcher_channel_builder = channel_builder.clone();
+            let fee_channel_builder = channel_builder.clone();
+            let pruner_channel_builder = channel_builder.clone();
+            let timer_channel_builder = channel_builder.clone();
+            let dev_channel_builder = channel_builder.clone();
+
+            let batcher_rpc = BatcherRpc::new(channel_builder.clone());
+            let batcher_admin_rpc = BatcherAdminRpc::new(channel_builder.clone());
+            let verifier_channel_builder = channel_builder.clone();
+            let verifier_alt_da = alt_da.clone();
+            let accounting_db = db.clone();
 
             let handle = builder
                 .node(node)
                 .install_exex("flash-batcher", |ctx| async move {
-                    BatcherExEx::new(ctx, channel_builder).await
+                    BatcherExEx::new(ctx, channel_builder, exex_op_node).await
+                })
+                .extend_rpc_modules(move |ctx| {
+                    ctx.modules.merge_configured(batcher_rpc.into_rpc())?;
+                    ctx.modules.merge_configured(
+                        BatcherVerifierRpc::new(
+                            ctx.provider().clone(),
+                            verifier_channel_builder,
+                            verifier_alt_da,
+                        )
+                        .into_rpc(),
+                    )?;
+                    ctx.modules.merge_configured(
+                        BatcherAccountingRpc::new(ctx.provider().clone(), accounting_db).into_rpc(),
+                    )?;
+                    ctx.modules.merge_if_module_configured(
+                        RethRpcModule::Admin,
+                        batcher_admin_rpc.into_rpc(),
+                    )?;
+                    info!("Batcher RPC namespace registered");
+                    Ok(())
                 })
                 .launch_with_debug_capabilities()
                 .await?;
 
+            // Closes channels past their max duration when no block comes in
+            let timer = ChannelTimer::new(timer_channel_builder, CHANNEL_TIMER_INTERVAL);
+            handle.node.task_executor.spawn(timer.run());
+
+            if let Some(policy) = args.batcher.retention_policy() {
+                info!("Starting batcher pruner with policy {:?}", policy);
+                let pruner = Pruner::new(
+                    pruner_channel_builder,
+                    policy,
+                    args.batcher.prune_interval(),
+                );
+                handle.node.task_executor.spawn(pruner.run());
+            }
+
+            if let Some(op_node) = op_node {
+                info!("Following op-node sync status");
+                let poller = SyncStatusPoller::new(
+                    op_node,
+                    poller_channel_builder,
+                    args.batcher.sync_interval(),
+                    args.batcher.max_safe_lag,
+                );
+                handle.node.task_executor.spawn(poller.run());
+            }
+
+            if let (Some(client), Some(inbox)) = (alt_da, inbox) {
+                info!(
+                    "Putting channels to alt-DA server {:?}, posting commitments to {}",
+                    args.batcher.alt_da_url,
+                    inbox.address()
+                );
+                let submitter = AltDaSubmitter::new(
+                    client,
+                    inbox,
+                    submitter_channel_builder,
+                    args.batcher.max_in_flight,
+                )
+                .with_fee_per_byte(args.batcher.alt_da_fee_per_byte);
+                handle.node.task_executor.spawn(submitter.run());
+            } else {
+                let submitter = BatchSubmitter::new(submitter_channel_builder);
+                handle.node.task_executor.spawn(submitter.run());
+            }
+
+            if let Some(url) = args.batcher.l1_rpc.as_deref() {
+                info!(
+                    "Following L1 fees, posting channels as {}",
+                    args.batcher.da_type
+                );
+                let poller = L1FeePoller::new(
+                    L1Client::new(url)?,
+                    fee_channel_builder,
+                    args.batcher.l1_fee_interval(),
+                );
+                handle.node.task_executor.spawn(poller.run());
+            }
+
+            if let (Some(url), Some(system_config)) =
+                (args.batcher.l1_rpc.as_deref(), args.batcher.system_config)
+            {
+                info!("Following SystemConfig {}", system_config);
+                let watcher = SystemConfigWatcher::new(
+                    L1Client::new(url)?,
+                    system_config,
+                    watcher_channel_builder,
+                    args.batcher.system_config_interval(),
+                    args.batcher.system_config_confirmations,
+                )
+                .await?;
+                handle.node.task_executor.spawn(watcher.run());
+            }
+
+            if let Some(config) = args.batcher.throttle_config() {
+                info!("Starting sequencer throttling with {:?}", config);
+                let throttler = Throttler::new(
+                    da_config,
+                    throttler_channel_builder,
+                    config,
+                    args.batcher.throttle_interval(),
+                );
+                handle.node.task_executor.spawn(throttler.run());
+            }
+
+            if args.dev.enabled {
+                let sequencer = DevSequencer::new(
+                    &handle.node.provider,
+                    handle.node.chain_spec(),
+                    handle.node.add_ons_handle.beacon_engine_handle.clone(),
+                    handle.node.payload_builder_handle.clone(),
+                    dev_channel_builder,
+                    &args.dev,
+                )?;
+                handle.node.task_executor.spawn(sequencer.run());
+            }
+
             info!("Flash chain node started successfully");
             handle.node_exit_future.await
         })
@@ -0,0 +1,296 @@
+//! Revenue and cost accounting of the batched blocks.
+//!
+//! The revenue of a block is the L1 data fee its transactions paid, computed as op-reth does
+//! for receipts. The cost of a channel is its estimated L1 data fee, recorded when it was
+//! submitted, and its alt-DA fee, spread evenly over its blocks, so ranges cutting through a
+//! channel only carry its share. Channels without a recorded L1 fee are left out of the days and
+//! totals, as their blocks would otherwise look free to post.
+
+use crate::{
+    db::{BatchStatus, DB},
+    l1_info::DEPOSIT_TX_TYPE,
+};
+use alloy_consensus::BlockHeader;
+use alloy_eips::{Typed2718, eip2718::Encodable2718};
+use reth::providers::{BlockReader, ChainSpecProvider};
+use reth_optimism_evm::{RethL1BlockInfo, extract_l1_info_from_tx};
+use reth_optimism_forks::OpHardforks;
+use reth_primitives_traits::{Block, BlockBody};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tracing::debug;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Revenue and costs of the blocks of a channel within the report range. Amounts are in wei.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelAccount {
+    pub id: String,
+    pub status: BatchStatus,
+    /// Number of blocks of the channel in the range.
+    pub blocks: usize,
+    /// L1 data fees paid by the transactions of those blocks.
+    pub revenue: u128,
+    /// Share of the estimated L1 data fee of the channel, `None` if not recorded.
+    pub l1_cost: Option<u128>,
+    /// Share of the recorded alt-DA fee of the channel, `None` if not recorded.
+    pub da_cost: Option<u128>,
+    /// `None` if the L1 fee of the channel isn't recorded.
+    pub margin: Option<i128>,
+}
+
+/// Revenue and costs of the blocks of a UTC day, except those of unrecorded channels. Amounts
+/// are in wei.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayAccount {
+    /// `YYYY-MM-DD`, from the block timestamps.
+    pub day: String,
+    pub blocks: usize,
+    /// Blocks of the day in channels without a recorded L1 fee, left out of the day.
+    pub unrecorded_blocks: usize,
+    pub transactions: usize,
+    pub revenue: u128,
+    pub l1_cost: u128,
+    pub da_cost: u128,
+    pub margin: i128,
+}
+
+/// Revenue and costs of a block range, except the blocks of unrecorded channels. Amounts are
+/// in wei.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountingReport {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub revenue: u128,
+    pub l1_cost: u128,
+    pub da_cost: u128,
+    pub margin: i128,
+    pub channels: Vec<ChannelAccount>,
+    pub days: Vec<DayAccount>,
+    /// Channels of the range without a recorded L1 fee, such as pending ones, left out of the
+    /// days and totals.
+    pub unrecorded_channels: Vec<String>,
+    /// Blocks of the range not part of any channel, left out of the report.
+    pub unbatched_blocks: usize,
+}
+
+/// Computes the revenue of blocks from a provider, and their costs from the batcher database.
+pub struct Accountant<P> {
+    provider: P,
+    db: Arc<Mutex<DB>>,
+}
+
+impl<P> Accountant<P>
+where
+    P: BlockReader + ChainSpecProvider<ChainSpec: OpHardforks>,
+{
+    pub fn new(provider: P, db: Arc<Mutex<DB>>) -> Self {
+        Self { provider, db }
+    }
+
+    /// Returns the highest block number contained in a valid channel.
+    pub fn latest_batched_block(&self) -> eyre::Result<Option<u64>> {
+        self.db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .latest_batched_block()
+            .map_err(|e| eyre::eyre!("Failed to fetch latest batched block: {}", e))
+    }
+
+    /// Reports the revenue and costs of the channels covering blocks `from..=to`.
+    pub fn report(&self, from: u64, to: u64) -> eyre::Result<AccountingReport> {
+        debug!("Accounting for blocks {} to {}", from, to);
+
+        let channels = self
+            .db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .get_batches_in_range(from, to)
+            .map_err(|e| eyre::eyre!("Failed to fetch channels: {}", e))?;
+
+        let mut report = AccountingReport {
+            from_block: from,
+            to_block: to,
+            ..Default::default()
+        };
+        let mut days: BTreeMap<u64, DayAccount> = BTreeMap::new();
+        let mut batched_blocks = 0;
+
+        for channel in channels {
+            let l1_fee = channel.estimated_l1_cost.map(|cost| cost.estimated_fee);
+            let da_fee = channel.da_fee;
+            if l1_fee.is_none() {
+                report.unrecorded_channels.push(channel.id.clone());
+            }
+
+            let mut account = ChannelAccount {
+                id: channel.id,
+                status: channel.status,
+                blocks: 0,
+                revenue: 0,
+                l1_cost: l1_fee.map(|_| 0),
+                da_cost: da_fee.map(|_| 0),
+                margin: None,
+            };
+
+            let block_count = channel.block_numbers.len() as u128;
+            for (index, block_number) in channel.block_numbers.into_iter().enumerate() {
+                if !(from..=to).contains(&block_number) {
+                    continue;
+                }
+
+                let (timestamp, transactions, revenue) = self.block_revenue(block_number)?;
+                let l1_cost = l1_fee.map_or(0, |fee| share(fee, index as u128, block_count));
+                let da_cost = da_fee.map_or(0, |fee| share(fee, index as u128, block_count));
+
+                account.blocks += 1;
+                account.revenue += revenue;
+                if let Some(total) = &mut account.l1_cost {
+                    *total += l1_cost;
+                }
+                if let Some(total) = &mut account.da_cost {
+                    *total += da_cost;
+                }
+
+                let day = days
+                    .entry(timestamp / SECONDS_PER_DAY)
+                    .or_insert_with(|| DayAccount {
+                        day: utc_day(timestamp),
+                        ..Default::default()
+                    });
+                if l1_fee.is_none() {
+                    day.unrecorded_blocks += 1;
+                    continue;
+                }
+                day.blocks += 1;
+                day.transactions += transactions;
+                day.revenue += revenue;
+                day.l1_cost += l1_cost;
+                day.da_cost += da_cost;
+            }
+
+            batched_blocks += account.blocks;
+            if let Some(l1_cost) = account.l1_cost {
+                let da_cost = account.da_cost.unwrap_or_default();
+                account.margin = Some(margin(account.revenue, l1_cost + da_cost));
+                report.revenue += account.revenue;
+                report.l1_cost += l1_cost;
+                report.da_cost += da_cost;
+            }
+            report.channels.push(account);
+        }
+
+        report.margin = margin(report.revenue, report.l1_cost + report.da_cost);
+        report.days = days
+            .into_values()
+            .map(|mut day| {
+                day.margin = margin(day.revenue, day.l1_cost + day.da_cost);
+                day
+            })
+            .collect();
+        report.unbatched_blocks = ((to - from + 1) as usize).saturating_sub(batched_blocks);
+
+        Ok(report)
+    }
+
+    /// Returns the timestamp, the number of non-deposit transactions and the L1 data fees they
+    /// paid of a block.
+    fn block_revenue(&self, block_number: u64) -> eyre::Result<(u64, usize, u128)> {
+        let block = self
+            .provider
+            .block_by_number(block_number)?
+            .ok_or_else(|| eyre::eyre!("Block {} not found", block_number))?;
+        let timestamp = block.header().timestamp();
+        let transactions = block.body().transactions();
+
+        // The L1 info deposit is always the first transaction of an L2 block
+        let l1_info_tx = transactions
+            .first()
+            .ok_or_else(|| eyre::eyre!("Block {} has no L1 info deposit", block_number))?;
+        let mut l1_info = extract_l1_info_from_tx(l1_info_tx).map_err(|e| {
+            eyre::eyre!("Failed to decode L1 info of block {}: {}", block_number, e)
+        })?;
+
+        let chain_spec = self.provider.chain_spec();
+        let mut count = 0;
+        let mut revenue = 0u128;
+        for tx in transactions.iter().filter(|tx| tx.ty() != DEPOSIT_TX_TYPE) {
+            let fee = l1_info
+                .l1_tx_data_fee(&*chain_spec, timestamp, &tx.encoded_2718(), false)
+                .map_err(|e| {
+                    eyre::eyre!("Failed to compute L1 fee in block {}: {}", block_number, e)
+                })?;
+            count += 1;
+            revenue += fee.saturating_to::<u128>();
+        }
+
+        Ok((timestamp, count, revenue))
+    }
+}
+
+/// Share of `total` of the item at `index` out of `count`, the shares adding up to `total`.
+fn share(total: u128, index: u128, count: u128) -> u128 {
+    total * (index + 1) / count - total * index / count
+}
+
+fn margin(revenue: u128, cost: u128) -> i128 {
+    revenue as i128 - cost as i128
+}
+
+/// Formats the UTC day of a unix timestamp as `YYYY-MM-DD`.
+fn utc_day(timestamp: u64) -> String {
+    // Civil date from days since the epoch, from Howard Hinnant's date algorithms
+    let days = (timestamp / SECONDS_PER_DAY) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_the_epoch() {
+        assert_eq!(utc_day(0), "1970-01-01");
+        assert_eq!(utc_day(SECONDS_PER_DAY - 1), "1970-01-01");
+        assert_eq!(utc_day(SECONDS_PER_DAY), "1970-01-02");
+    }
+
+    #[test]
+    fn formats_leap_days() {
+        assert_eq!(utc_day(1_709_164_800), "2024-02-29");
+        assert_eq!(utc_day(1_709_164_800 + SECONDS_PER_DAY), "2024-03-01");
+        // 2023 isn't a leap year
+        assert_eq!(utc_day(1_677_628_800 - 1), "2023-02-28");
+        assert_eq!(utc_day(1_677_628_800), "2023-03-01");
+    }
+
+    #[test]
+    fn formats_century_boundaries() {
+        assert_eq!(utc_day(946_684_800 - 1), "1999-12-31");
+        assert_eq!(utc_day(946_684_800), "2000-01-01");
+        // 2000 is a leap year as a multiple of 400, 2100 isn't
+        assert_eq!(utc_day(951_782_400), "2000-02-29");
+        assert_eq!(utc_day(4_107_456_000), "2100-02-28");
+        assert_eq!(utc_day(4_107_456_000 + SECONDS_PER_DAY), "2100-03-01");
+    }
+}
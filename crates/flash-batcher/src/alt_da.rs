@@ -0,0 +1,496 @@
+//! Alt-DA submission: channel data is put to a DA server over the OP alt-DA HTTP API, and only
+//! its commitment is posted to the L1 batch inbox.
+
+use crate::{
+    channel_builder::ChannelBuilder, db::BatchStatus, events::ChannelEvent, l1::BatchInbox,
+    l1_cost::DaType,
+};
+use clap::ValueEnum;
+use flash_da_server::commitment::Commitment;
+use futures::{StreamExt, stream::FuturesOrdered};
+use http_body_util::{BodyExt, Full};
+use hyper::{Method, Request, StatusCode, body::Bytes};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, info, warn};
+
+/// Interval between checks for pending channels.
+const SUBMISSION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which commitments channels are put under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CommitmentMode {
+    /// The batcher computes the keccak256 commitment, `PUT /put/<commitment>`.
+    Keccak,
+    /// The DA server returns the commitment, `PUT /put`.
+    Generic,
+}
+
+/// HTTP client of an alt-DA server.
+#[derive(Debug, Clone)]
+pub struct AltDaClient {
+    url: String,
+    mode: CommitmentMode,
+    client: Client<HttpConnector, Full<Bytes>>,
+}
+
+impl AltDaClient {
+    pub fn new(url: &str, mode: CommitmentMode) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            mode,
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    pub fn mode(&self) -> CommitmentMode {
+        self.mode
+    }
+
+    /// Puts `data` to the DA server, returning its commitment.
+    pub async fn put(&self, data: &[u8]) -> eyre::Result<Commitment> {
+        let (uri, commitment) = match self.mode {
+            CommitmentMode::Keccak => {
+                let commitment = Commitment::keccak(data);
+                (format!("{}/put/{}", self.url, commitment), Some(commitment))
+            }
+            CommitmentMode::Generic => (format!("{}/put", self.url), None),
+        };
+
+        let (status, body) = self
+            .request(Method::PUT, &uri, Bytes::copy_from_slice(data))
+            .await?;
+        if status != StatusCode::OK {
+            return Err(eyre::eyre!(
+                "DA server returned {} on put: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+
+        match commitment {
+            Some(commitment) => Ok(commitment),
+            None => Commitment::decode(&body),
+        }
+    }
+
+    /// Gets the data stored under `commitment`, `None` if the DA server doesn't have it.
+    pub async fn get(&self, commitment: &Commitment) -> eyre::Result<Option<Vec<u8>>> {
+        let uri = format!("{}/get/{}", self.url, commitment);
+        let (status, body) = self.request(Method::GET, &uri, Bytes::new()).await?;
+
+        match status {
+            StatusCode::OK => Ok(Some(body.to_vec())),
+            StatusCode::NOT_FOUND => Ok(None),
+            status => Err(eyre::eyre!(
+                "DA server returned {} on get: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            )),
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Bytes,
+    ) -> eyre::Result<(StatusCode, Bytes)> {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/octet-stream")
+            .body(Full::new(body))?;
+
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| eyre::eyre!("Request to {} failed: {}", uri, e))?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| eyre::eyre!("Failed to read response from {}: {}", uri, e))?
+            .to_bytes();
+
+        Ok((status, body))
+    }
+}
+
+/// Puts pending channels to the DA server, and posts their commitments to the batch inbox, in
+/// order.
+///
+/// Up to `max_in_flight` channels are put concurrently, and puts go on while a commitment is
+/// posted, but commitments are posted in channel order, one L1 transaction at a time: a channel
+/// is only marked posted once its transaction is included, after every channel before it, so
+/// derivation never sees them out of order.
+pub struct AltDaSubmitter {
+    client: AltDaClient,
+    inbox: BatchInbox,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    max_in_flight: usize,
+    /// Price of the DA server per byte of channel data, in wei, recorded as the DA fee of
+    /// channels.
+    fee_per_byte: Option<u64>,
+}
+
+impl AltDaSubmitter {
+    pub fn new(
+        client: AltDaClient,
+        inbox: BatchInbox,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        max_in_flight: usize,
+    ) -> Self {
+        Self {
+            client,
+            inbox,
+            channel_builder,
+            max_in_flight: max_in_flight.max(1),
+            fee_per_byte: None,
+        }
+    }
+
+    pub fn with_fee_per_byte(mut self, fee_per_byte: Option<u64>) -> Self {
+        self.fee_per_byte = fee_per_byte;
+        self
+    }
+
+    /// Puts and posts the pending channels, stopping at the first failure so channels stay
+    /// ordered.
+    ///
+    /// Channels still being put or posted when this returns, on a failed put or L1 transaction
+    /// or on any other error, are marked pending again, to be retried on the next round together
+    /// with the ones after them.
+    pub async fn submit_pending(&self) -> eyre::Result<()> {
+        let mut in_flight_ids = VecDeque::new();
+        let result = self.put_pending(&mut in_flight_ids).await;
+
+        if !in_flight_ids.is_empty() {
+            let channel_builder = self
+                .channel_builder
+                .lock()
+                .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+            channel_builder
+                .metrics()
+                .channel
+                .channels_in_flight
+                .decrement(in_flight_ids.len() as f64);
+
+            let db = channel_builder.db();
+            let db = db
+                .lock()
+                .map_err(|_| eyre::eyre!("Database lock poisoned"))?;
+            for id in &in_flight_ids {
+                if let Err(e) = db.requeue_submitting_batch(id) {
+                    // Left being submitted, requeued when the submitter restarts
+                    error!("Failed to requeue batch {}: {}", id, e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Puts the pending channels, keeping the ids of the channels being put in `in_flight_ids`
+    /// until their commitment is posted.
+    async fn put_pending(&self, in_flight_ids: &mut VecDeque<String>) -> eyre::Result<()> {
+        let (db, events, metrics, l1_fees, l1_budget, keys) = {
+            let channel_builder = self
+                .channel_builder
+                .lock()
+                .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+            if !channel_builder.is_running() {
+                return Ok(());
+            }
+            let Some(keys) = channel_builder.keys().cloned() else {
+                warn!("No batcher key configured, holding pending batches");
+                return Ok(());
+            };
+            if let Some(batcher) = channel_builder.unauthorized_batcher() {
+                warn!(
+                    "No key configured for the authorized batcher {}, holding pending batches",
+                    batcher
+                );
+                return Ok(());
+            }
+
+            (
+                channel_builder.db(),
+                channel_builder.events().clone(),
+                channel_builder.metrics().channel.clone(),
+                channel_builder.l1_fees().copied(),
+                channel_builder.l1_budget().copied(),
+                keys,
+            )
+        };
+
+        let mut batches = db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .get_pending_batches()
+            .map_err(|e| eyre::eyre!("Failed to get pending batches: {}", e))?
+            .into_iter();
+
+        // Puts resolve in the order they were started
+        let mut puts = FuturesOrdered::new();
+        // Channels put, waiting for the commitments of the channels before them to be posted
+        let mut put = VecDeque::new();
+        // The commitment being posted, puts go on meanwhile
+        let mut posting = None;
+
+        // Set once a channel is held over the L1 budget, the ones after it aren't put either
+        let mut holding = false;
+        // Set once a put failed, the channels put before it are still posted
+        let mut put_failed = false;
+
+        loop {
+            while !holding && !put_failed && puts.len() < self.max_in_flight {
+                // `admin_stopBatcher` waits for the puts in flight, no new one is started
+                if !self.is_running()? {
+                    break;
+                }
+                let Some(batch) = batches.next() else {
+                    break;
+                };
+
+                if let Some(budget) = &l1_budget {
+                    // The commitment is posted, generic ones are about the size of keccak ones
+                    let size = Commitment::keccak(&batch.data).tx_data().len() as u64;
+                    let cost = l1_fees.map(|fees| fees.estimate(size, DaType::Calldata));
+                    let reason = {
+                        let db = db
+                            .lock()
+                            .map_err(|_| eyre::eyre!("Database lock poisoned"))?;
+                        budget.hold_reason(&db, &batch, cost.as_ref(), size)?
+                    };
+                    if let Some(reason) = reason {
+                        warn!("Holding batch {} over the L1 budget: {}", batch.id, reason);
+                        holding = true;
+                        break;
+                    }
+                }
+
+                // Puts already in flight may have let a reorg invalidate the batch
+                let submitting = db
+                    .lock()
+                    .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+                    .mark_batch_submitting(&batch.id)
+                    .map_err(|e| eyre::eyre!("Failed to update batch {}: {}", batch.id, e))?;
+                if !submitting {
+                    debug!("Batch {} is no longer pending, not putting it", batch.id);
+                    continue;
+                }
+                in_flight_ids.push_back(batch.id.clone());
+                events.emit(ChannelEvent::FrameSubmitted {
+                    channel_id: batch.id.clone(),
+                    frame_number: 0,
+                });
+                metrics.channels_in_flight.increment(1.0);
+
+                puts.push_back(async move {
+                    let started_at = Instant::now();
+                    let result = self.client.put(&batch.data).await;
+                    (batch, started_at.elapsed(), result)
+                });
+            }
+
+            // Posts the commitment of the next channel put, once the previous one is included
+            while posting.is_none() {
+                let Some((batch, commitment)) = put.pop_front() else {
+                    break;
+                };
+
+                // Commitments of batches a reorg invalidated while they were put aren't posted
+                let status = db
+                    .lock()
+                    .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+                    .get_batch(&batch.id)
+                    .map_err(|e| eyre::eyre!("Failed to get batch {}: {}", batch.id, e))?
+                    .map(|batch| batch.status);
+                if status != Some(BatchStatus::Submitting) {
+                    in_flight_ids.pop_front();
+                    metrics.channels_in_flight.decrement(1.0);
+                    debug!("Batch {} was invalidated while being put", batch.id);
+                    continue;
+                }
+
+                let keys = &keys;
+                posting = Some(Box::pin(async move {
+                    let result = self.inbox.post(keys, commitment.tx_data()).await;
+                    (batch, commitment, result)
+                }));
+            }
+
+            if puts.is_empty() && posting.is_none() {
+                break;
+            }
+
+            tokio::select! {
+                Some((batch, put_latency, result)) = puts.next(), if !puts.is_empty() => {
+                    match result {
+                        Ok(commitment) => {
+                            metrics.submission_latency.record(put_latency.as_secs_f64());
+                            put.push_back((batch, commitment));
+                        }
+                        Err(e) => {
+                            error!("Failed to put batch {} to the DA server: {}", batch.id, e);
+                            metrics.submission_failures.increment(1);
+                            events.emit(ChannelEvent::FrameFailed {
+                                channel_id: batch.id.clone(),
+                                frame_number: 0,
+                                error: e.to_string(),
+                            });
+
+                            // The failed channel and the ones put after it are requeued by the
+                            // caller, once the commitments of the ones before it are posted
+                            put_failed = true;
+                            puts = FuturesOrdered::new();
+                        }
+                    }
+                }
+                (batch, commitment, result) = async { posting.as_mut().expect("posting").await },
+                    if posting.is_some() =>
+                {
+                    posting = None;
+                    let receipt = match result {
+                        Ok(receipt) => receipt,
+                        Err(e) => {
+                            error!(
+                                "Failed to post commitment of batch {} to L1: {}",
+                                batch.id, e
+                            );
+                            metrics.submission_failures.increment(1);
+                            events.emit(ChannelEvent::FrameFailed {
+                                channel_id: batch.id.clone(),
+                                frame_number: 0,
+                                error: e.to_string(),
+                            });
+
+                            // Requeued by the caller, with the channels put after it
+                            return Ok(());
+                        }
+                    };
+
+                    let posted = db
+                        .lock()
+                        .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+                        .mark_batch_posted(
+                            &batch.id,
+                            &commitment.to_string(),
+                            &receipt.transaction_hash.to_string(),
+                        )
+                        .map_err(|e| eyre::eyre!("Failed to update batch {}: {}", batch.id, e))?;
+                    in_flight_ids.pop_front();
+                    metrics.channels_in_flight.decrement(1.0);
+                    if !posted {
+                        warn!(
+                            "Batch {} was invalidated after its commitment was posted in L1 transaction {}",
+                            batch.id, receipt.transaction_hash
+                        );
+                        continue;
+                    }
+
+                    if let Some(fee_per_byte) = self.fee_per_byte {
+                        let fee = batch.data.len() as u128 * u128::from(fee_per_byte);
+                        if let Err(e) = db
+                            .lock()
+                            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+                            .record_da_fee(&batch.id, fee)
+                        {
+                            error!("Failed to record DA fee of batch {}: {}", batch.id, e);
+                        }
+                    }
+
+                    // Only the commitment is posted to L1
+                    if let Some(fees) = l1_fees {
+                        let cost =
+                            fees.estimate(commitment.tx_data().len() as u64, DaType::Calldata);
+                        if let Err(e) = db
+                            .lock()
+                            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+                            .record_estimated_l1_cost(&batch.id, &cost)
+                        {
+                            error!("Failed to record L1 fee of batch {}: {}", batch.id, e);
+                        }
+                    }
+
+                    metrics.bytes_submitted.increment(batch.data.len() as u64);
+                    if let Some(first_block_timestamp) = batch.first_block_timestamp {
+                        let now = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs();
+                        metrics
+                            .block_to_inclusion_latency
+                            .record(now.saturating_sub(first_block_timestamp) as f64);
+                    }
+
+                    info!(
+                        "Put batch {} to the DA server, posted its commitment in L1 transaction {}",
+                        batch.id, receipt.transaction_hash
+                    );
+                    events.emit(ChannelEvent::FrameIncluded {
+                        channel_id: batch.id,
+                        frame_number: 0,
+                        da_height: receipt.block_number,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_running(&self) -> eyre::Result<bool> {
+        Ok(self
+            .channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?
+            .is_running())
+    }
+
+    fn requeue_submitting(&self) -> eyre::Result<usize> {
+        let db = self
+            .channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?
+            .db();
+        let requeued = db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .requeue_submitting()
+            .map_err(|e| eyre::eyre!("Failed to requeue batches: {}", e))?;
+        Ok(requeued)
+    }
+
+    /// Submits pending channels every second, forever.
+    ///
+    /// Channels left being put or posted by a previous run are requeued first, so they are put
+    /// again before the ones after them.
+    pub async fn run(self) {
+        match self.requeue_submitting() {
+            Ok(0) => {}
+            Ok(requeued) => info!("Requeued {} batches left being put", requeued),
+            Err(e) => error!("Failed to requeue batches left being put: {}", e),
+        }
+
+        let mut interval = tokio::time::interval(SUBMISSION_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.submit_pending().await {
+                error!("Failed to submit batches to the DA server: {}", e);
+            }
+        }
+    }
+}
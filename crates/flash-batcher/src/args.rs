@@ -0,0 +1,277 @@
+use crate::{
+    alt_da::CommitmentMode,
+    channel_builder::FlushPolicy,
+    codec::Codec,
+    l1_cost::{DaType, L1Budget},
+    pruner::RetentionPolicy,
+    signer::KeySources,
+    throttle::ThrottleConfig,
+};
+use alloy_primitives::Address;
+use clap::Args;
+use std::time::Duration;
+
+/// Command line arguments for the flash batcher.
+#[derive(Debug, Clone, Args)]
+#[command(next_help_heading = "Batcher")]
+pub struct BatcherArgs {
+    /// Number of blocks after which a channel is closed.
+    #[arg(
+        long = "batcher.batch-size",
+        value_name = "BLOCKS",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub batch_size: u64,
+
+    /// Seconds after which a channel is closed, even if it has fewer blocks than the batch size.
+    #[arg(long = "batcher.max-duration", value_name = "SECONDS")]
+    pub max_duration: Option<u64>,
+
+    /// Codec the batch of each block is serialized with.
+    #[arg(long = "batcher.codec", value_enum, default_value_t = Codec::Json)]
+    pub codec: Codec,
+
+    /// Drop payloads of finalized channels submitted more than this many seconds ago.
+    /// Requires `--batcher.op-node-rpc`, or `--flash.dev`, to know the finalized head.
+    #[arg(long = "batcher.retention-age", value_name = "SECONDS")]
+    pub retention_age: Option<u64>,
+
+    /// Drop payloads of finalized channels more than this many blocks behind the latest
+    /// batched block. Requires `--batcher.op-node-rpc`, or `--flash.dev`.
+    #[arg(
+        long = "batcher.retention-depth",
+        value_name = "BLOCKS",
+        conflicts_with = "retention_age"
+    )]
+    pub retention_depth: Option<u64>,
+
+    /// Seconds between pruning runs.
+    #[arg(
+        long = "batcher.prune-interval",
+        value_name = "SECONDS",
+        default_value_t = 3600
+    )]
+    pub prune_interval: u64,
+
+    /// op-node RPC URL. When set, blocks derivation already made safe are not batched.
+    #[arg(long = "batcher.op-node-rpc", value_name = "URL")]
+    pub op_node_rpc: Option<String>,
+
+    /// Seconds between op-node sync status polls.
+    #[arg(
+        long = "batcher.sync-interval",
+        value_name = "SECONDS",
+        default_value_t = 6
+    )]
+    pub sync_interval: u64,
+
+    /// Number of blocks the op-node safe head can lag behind its unsafe head before derivation
+    /// is reported as behind.
+    #[arg(
+        long = "batcher.max-safe-lag",
+        value_name = "BLOCKS",
+        default_value_t = 600
+    )]
+    pub max_safe_lag: u64,
+
+    /// Unsubmitted bytes above which the sequencer DA limits are throttled. Throttling is
+    /// disabled when unset.
+    #[arg(long = "batcher.throttle-threshold", value_name = "BYTES")]
+    pub throttle_threshold: Option<u64>,
+
+    /// Maximum DA size of a transaction while throttled.
+    #[arg(
+        long = "batcher.throttle-tx-size",
+        value_name = "BYTES",
+        default_value_t = 5_000
+    )]
+    pub throttle_tx_size: u64,
+
+    /// Maximum DA size of a block while throttled.
+    #[arg(
+        long = "batcher.throttle-block-size",
+        value_name = "BYTES",
+        default_value_t = 21_000
+    )]
+    pub throttle_block_size: u64,
+
+    /// Seconds between throttling updates.
+    #[arg(
+        long = "batcher.throttle-interval",
+        value_name = "SECONDS",
+        default_value_t = 2
+    )]
+    pub throttle_interval: u64,
+
+    /// Alt-DA server URL. When set, channels are put to the DA server, and only their
+    /// commitment is posted to the batch inbox.
+    #[arg(
+        long = "batcher.alt-da-url",
+        value_name = "URL",
+        requires = "batch_inbox"
+    )]
+    pub alt_da_url: Option<String>,
+
+    /// Batch inbox address, `batch_inbox_address` in `rollup.json`, alt-DA commitments are
+    /// posted to.
+    #[arg(
+        long = "batcher.batch-inbox",
+        value_name = "ADDRESS",
+        requires = "l1_rpc"
+    )]
+    pub batch_inbox: Option<Address>,
+
+    /// Commitments channels are put under on the alt-DA server.
+    #[arg(
+        long = "batcher.alt-da-commitment",
+        value_enum,
+        default_value_t = CommitmentMode::Keccak
+    )]
+    pub alt_da_commitment: CommitmentMode,
+
+    /// Maximum number of channels put to the alt-DA server concurrently. Commitments are still
+    /// recorded in channel order. Without an alt-DA server, channels are submitted one at a
+    /// time, so the flag is rejected.
+    #[arg(
+        long = "batcher.max-in-flight",
+        value_name = "CHANNELS",
+        default_value_t = 1,
+        requires = "alt_da_url"
+    )]
+    pub max_in_flight: usize,
+
+    /// Price of the alt-DA server per byte of channel data, in wei, recorded as the DA fee of
+    /// the channels put to it.
+    #[arg(
+        long = "batcher.alt-da-fee-per-byte",
+        value_name = "WEI",
+        requires = "alt_da_url"
+    )]
+    pub alt_da_fee_per_byte: Option<u64>,
+
+    #[command(flatten)]
+    pub keys: KeySources,
+
+    /// L1 RPC URL, reading the SystemConfig and the L1 fees.
+    #[arg(long = "batcher.l1-rpc", value_name = "URL")]
+    pub l1_rpc: Option<String>,
+
+    /// SystemConfig address, `l1_system_config_address` in `rollup.json`. When set, its
+    /// `ConfigUpdate` events are followed, and the batcher key is the one it authorizes.
+    #[arg(
+        long = "batcher.system-config",
+        value_name = "ADDRESS",
+        requires = "l1_rpc"
+    )]
+    pub system_config: Option<Address>,
+
+    /// Seconds between polls of the SystemConfig `ConfigUpdate` events.
+    #[arg(
+        long = "batcher.system-config-interval",
+        value_name = "SECONDS",
+        default_value_t = 12
+    )]
+    pub system_config_interval: u64,
+
+    /// L1 blocks a `ConfigUpdate` event must be buried under before it's applied, so reorged
+    /// updates are never followed.
+    #[arg(
+        long = "batcher.system-config-confirmations",
+        value_name = "BLOCKS",
+        default_value_t = 10
+    )]
+    pub system_config_confirmations: u64,
+
+    /// How channels are posted to L1.
+    #[arg(long = "batcher.da-type", value_enum, default_value_t = DaType::Calldata)]
+    pub da_type: DaType,
+
+    /// Seconds between polls of the L1 base fee and blob base fee.
+    #[arg(
+        long = "batcher.l1-fee-interval",
+        value_name = "SECONDS",
+        default_value_t = 12
+    )]
+    pub l1_fee_interval: u64,
+
+    /// Maximum L1 data fees of the channels submitted over the last hour, in wei.
+    #[arg(
+        long = "batcher.max-l1-fee-per-hour",
+        value_name = "WEI",
+        requires = "l1_rpc"
+    )]
+    pub max_l1_fee_per_hour: Option<u128>,
+
+    /// Maximum L1 data fee per byte of channel data, in wei.
+    #[arg(
+        long = "batcher.max-l1-fee-per-byte",
+        value_name = "WEI",
+        requires = "l1_rpc"
+    )]
+    pub max_l1_fee_per_byte: Option<u128>,
+
+    /// Seconds after which a channel held over the L1 budget is submitted anyway, so its blocks
+    /// are posted within the sequencing window.
+    #[arg(
+        long = "batcher.max-hold",
+        value_name = "SECONDS",
+        default_value_t = 3600
+    )]
+    pub max_hold: u64,
+}
+
+impl BatcherArgs {
+    pub fn flush_policy(&self) -> FlushPolicy {
+        FlushPolicy::new(self.batch_size, self.max_duration)
+    }
+
+    /// Returns the configured retention policy, if pruning is enabled.
+    pub fn retention_policy(&self) -> Option<RetentionPolicy> {
+        match (self.retention_age, self.retention_depth) {
+            (Some(age), _) => Some(RetentionPolicy::Age(Duration::from_secs(age))),
+            (None, Some(depth)) => Some(RetentionPolicy::Depth(depth)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn prune_interval(&self) -> Duration {
+        Duration::from_secs(self.prune_interval.max(1))
+    }
+
+    pub fn sync_interval(&self) -> Duration {
+        Duration::from_secs(self.sync_interval.max(1))
+    }
+
+    /// Returns the throttling limits, if throttling is enabled.
+    pub fn throttle_config(&self) -> Option<ThrottleConfig> {
+        self.throttle_threshold.map(|threshold| ThrottleConfig {
+            threshold,
+            tx_size: self.throttle_tx_size,
+            block_size: self.throttle_block_size,
+        })
+    }
+
+    pub fn throttle_interval(&self) -> Duration {
+        Duration::from_secs(self.throttle_interval.max(1))
+    }
+
+    pub fn system_config_interval(&self) -> Duration {
+        Duration::from_secs(self.system_config_interval.max(1))
+    }
+
+    pub fn l1_fee_interval(&self) -> Duration {
+        Duration::from_secs(self.l1_fee_interval.max(1))
+    }
+
+    /// Returns the L1 budget, if any limit is set.
+    pub fn l1_budget(&self) -> Option<L1Budget> {
+        (self.max_l1_fee_per_hour.is_some() || self.max_l1_fee_per_byte.is_some()).then(|| {
+            L1Budget {
+                max_fee_per_hour: self.max_l1_fee_per_hour,
+                max_fee_per_byte: self.max_l1_fee_per_byte,
+                max_hold: Duration::from_secs(self.max_hold),
+            }
+        })
+    }
+}
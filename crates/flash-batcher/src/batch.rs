@@ -0,0 +1,107 @@
+use crate::l1_info::{DEPOSIT_TX_TYPE, L1BlockInfo};
+use alloy_consensus::{BlockHeader, Transaction};
+use alloy_eips::{Typed2718, eip2718::Encodable2718};
+use alloy_primitives::{B256, Bytes};
+use alloy_rlp::{Decodable, Encodable, Header};
+use reth_primitives::SealedBlock;
+use reth_primitives_traits::{Block, BlockBody};
+use serde::{Deserialize, Serialize};
+
+/// The data derivation needs to rebuild an L2 block, following op-stack singular batches.
+///
+/// Deposits are not included, derivation reconstructs them from L1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SingularBatch {
+    pub parent_hash: B256,
+    /// Number of the L1 origin of the block.
+    pub epoch_num: u64,
+    /// Hash of the L1 origin of the block.
+    pub epoch_hash: B256,
+    pub timestamp: u64,
+    /// EIP-2718 encoded non-deposit transactions.
+    pub transactions: Vec<Bytes>,
+}
+
+impl SingularBatch {
+    /// Builds the batch of an L2 block, taking its epoch from the L1 info deposit.
+    pub fn from_block<B: Block>(block: &SealedBlock<B>) -> anyhow::Result<Self> {
+        let transactions = block.body().transactions();
+
+        // The L1 info deposit is always the first transaction of an L2 block
+        let l1_info_tx = transactions
+            .first()
+            .filter(|tx| tx.ty() == DEPOSIT_TX_TYPE)
+            .ok_or_else(|| anyhow::anyhow!("Block {} has no L1 info deposit", block.number()))?;
+        let l1_info = L1BlockInfo::decode_calldata(l1_info_tx.input())?;
+
+        Ok(Self {
+            parent_hash: block.parent_hash(),
+            epoch_num: l1_info.number,
+            epoch_hash: l1_info.hash,
+            timestamp: block.timestamp(),
+            transactions: transactions
+                .iter()
+                .filter(|tx| tx.ty() != DEPOSIT_TX_TYPE)
+                .map(|tx| tx.encoded_2718().into())
+                .collect(),
+        })
+    }
+
+    fn rlp_payload_length(&self) -> usize {
+        self.parent_hash.length()
+            + self.epoch_num.length()
+            + self.epoch_hash.length()
+            + self.timestamp.length()
+            + self.transactions.length()
+    }
+}
+
+/// RLP list of the batch fields, in op-stack order.
+impl Encodable for SingularBatch {
+    fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
+        Header {
+            list: true,
+            payload_length: self.rlp_payload_length(),
+        }
+        .encode(out);
+        self.parent_hash.encode(out);
+        self.epoch_num.encode(out);
+        self.epoch_hash.encode(out);
+        self.timestamp.encode(out);
+        self.transactions.encode(out);
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.rlp_payload_length();
+        payload_length + alloy_rlp::length_of_length(payload_length)
+    }
+}
+
+impl Decodable for SingularBatch {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let header = Header::decode(buf)?;
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString);
+        }
+
+        let remaining = buf.len();
+        let batch = Self {
+            parent_hash: Decodable::decode(buf)?,
+            epoch_num: Decodable::decode(buf)?,
+            epoch_hash: Decodable::decode(buf)?,
+            timestamp: Decodable::decode(buf)?,
+            transactions: Decodable::decode(buf)?,
+        };
+
+        let consumed = remaining - buf.len();
+        if consumed != header.payload_length {
+            return Err(alloy_rlp::Error::ListLengthMismatch {
+                expected: header.payload_length,
+                got: consumed,
+            });
+        }
+
+        Ok(batch)
+    }
+}
@@ -1,39 +1,274 @@
-use crate::db::{BlockData, DB};
+use crate::{
+    codec::Codec,
+    db::{BlockData, DB},
+    events::{ChannelEvent, ChannelEvents},
+    l1_cost::{self, DaType, L1Budget, L1Fees},
+    metrics::BatcherMetrics,
+    op_node::SyncStatus,
+    signer::KeyRing,
+    system_config::SystemConfig,
+};
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
     sync::{Arc, Mutex},
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-pub struct ChannelBuilder {
-    db: Arc<Mutex<DB>>,
-    pending_blocks: VecDeque<BlockData>,
-    batch_size: u64,
+/// Interval between checks of the open channel's max duration.
+pub const CHANNEL_TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Summary of the channel currently being filled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenChannel {
+    pub block_count: usize,
+    pub first_block: u64,
+    pub last_block: u64,
+    /// Size of the pending block data, in bytes.
+    pub size: usize,
 }
 
-impl ChannelBuilder {
-    pub fn new(db: Arc<Mutex<DB>>, batch_size: u64) -> Self {
+/// When the open channel gets closed into a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlushPolicy {
+    /// Number of blocks after which the channel is closed.
+    pub batch_size: u64,
+    /// Seconds after the first pending block was added after which the channel is closed.
+    pub max_duration: Option<u64>,
+}
+
+impl FlushPolicy {
+    pub fn new(batch_size: u64, max_duration: Option<u64>) -> Self {
         if batch_size == 0 {
             warn!("Batch size is 0, defaulting to 1");
         }
 
-        debug!("Creating ChannelBuilder with batch size: {}", batch_size);
+        Self {
+            batch_size: batch_size.max(1), // Ensure minimum batch size of 1
+            max_duration,
+        }
+    }
+}
+
+/// Fills the open channel, a single one at a time, with the blocks of the chain.
+///
+/// Closed channels are persisted as pending batches, put and posted by the submitters while the
+/// next channel fills.
+pub struct ChannelBuilder {
+    db: Arc<Mutex<DB>>,
+    pending_blocks: VecDeque<BlockData>,
+    policy: FlushPolicy,
+    /// When the first pending block was added.
+    opened_at: Option<Instant>,
+    /// Whether closed channels are submitted. While stopped, channels are still closed and
+    /// persisted as pending batches.
+    running: bool,
+    /// Id of the open channel, assigned when its first block is added.
+    channel_id: Option<String>,
+    events: ChannelEvents,
+    metrics: BatcherMetrics,
+    /// Latest op-node sync status, if an op-node is configured.
+    sync_status: Option<SyncStatus>,
+    /// Latest block the node finalized itself, without an op-node deriving the chain.
+    local_finalized: Option<u64>,
+    /// Whether pending batches are put to an alt-DA server by the alt-DA submitter.
+    alt_da: bool,
+    /// Codec the pending blocks are serialized with.
+    codec: Codec,
+    /// Batcher keys, if configured.
+    keys: Option<Arc<KeyRing>>,
+    /// Latest L1 SystemConfig, if followed.
+    system_config: Option<SystemConfig>,
+    /// How channels are posted to L1.
+    da_type: DaType,
+    /// Latest L1 fees, if an L1 RPC is configured.
+    l1_fees: Option<L1Fees>,
+    /// Limits on the L1 fees of submitted channels, if any.
+    l1_budget: Option<L1Budget>,
+}
+
+impl ChannelBuilder {
+    pub fn new(db: Arc<Mutex<DB>>, policy: FlushPolicy) -> Self {
+        debug!("Creating ChannelBuilder with flush policy: {:?}", policy);
 
         Self {
             db,
             pending_blocks: VecDeque::new(),
-            batch_size: batch_size.max(1), // Ensure minimum batch size of 1
+            policy: FlushPolicy::new(policy.batch_size, policy.max_duration),
+            opened_at: None,
+            running: true,
+            channel_id: None,
+            events: ChannelEvents::new(),
+            metrics: BatcherMetrics::new(),
+            sync_status: None,
+            local_finalized: None,
+            alt_da: false,
+            codec: Codec::default(),
+            keys: None,
+            system_config: None,
+            da_type: DaType::default(),
+            l1_fees: None,
+            l1_budget: None,
         }
     }
 
+    pub fn events(&self) -> &ChannelEvents {
+        &self.events
+    }
+
+    pub fn metrics(&self) -> &BatcherMetrics {
+        &self.metrics
+    }
+
     pub fn db(&self) -> Arc<Mutex<DB>> {
         self.db.clone()
     }
 
     pub fn batch_size(&self) -> u64 {
-        self.batch_size
+        self.policy.batch_size
+    }
+
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.policy
+    }
+
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        let policy = FlushPolicy::new(policy.batch_size, policy.max_duration);
+        info!(
+            "Updating flush policy from {:?} to {:?}",
+            self.policy, policy
+        );
+        self.policy = policy;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn set_running(&mut self, running: bool) {
+        info!(
+            "Batcher submission {}",
+            if running { "started" } else { "stopped" }
+        );
+        self.running = running;
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Sets the codec blocks are serialized with. Only set it while the queue is empty, pending
+    /// blocks are not serialized again.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    pub fn is_alt_da(&self) -> bool {
+        self.alt_da
+    }
+
+    pub fn set_alt_da(&mut self, alt_da: bool) {
+        self.alt_da = alt_da;
+    }
+
+    pub fn keys(&self) -> Option<&Arc<KeyRing>> {
+        self.keys.as_ref()
+    }
+
+    pub fn set_keys(&mut self, keys: Arc<KeyRing>) {
+        self.keys = Some(keys);
+    }
+
+    /// Returns the batcher address L1 authorizes if none of the configured keys is its key.
+    ///
+    /// Batches signed by a key L1 no longer authorizes would be dropped by derivation, every
+    /// submitter holds pending batches until the authorized key is configured.
+    pub fn unauthorized_batcher(&self) -> Option<Address> {
+        self.keys
+            .as_ref()
+            .filter(|keys| keys.active().is_none())
+            .map(|keys| keys.authorized())
+    }
+
+    pub fn system_config(&self) -> Option<&SystemConfig> {
+        self.system_config.as_ref()
+    }
+
+    pub fn set_system_config(&mut self, system_config: SystemConfig) {
+        self.system_config = Some(system_config);
+    }
+
+    pub fn da_type(&self) -> DaType {
+        self.da_type
+    }
+
+    pub fn set_da_type(&mut self, da_type: DaType) {
+        self.da_type = da_type;
+    }
+
+    pub fn l1_fees(&self) -> Option<&L1Fees> {
+        self.l1_fees.as_ref()
+    }
+
+    pub fn set_l1_fees(&mut self, l1_fees: L1Fees) {
+        self.l1_fees = Some(l1_fees);
+    }
+
+    pub fn l1_budget(&self) -> Option<&L1Budget> {
+        self.l1_budget.as_ref()
+    }
+
+    pub fn set_l1_budget(&mut self, l1_budget: L1Budget) {
+        self.l1_budget = Some(l1_budget);
+    }
+
+    pub fn sync_status(&self) -> Option<&SyncStatus> {
+        self.sync_status.as_ref()
+    }
+
+    pub fn set_sync_status(&mut self, sync_status: SyncStatus) {
+        self.sync_status = Some(sync_status);
+    }
+
+    /// Sets the latest block the node finalized itself, as in dev mode where blocks are final
+    /// once produced.
+    pub fn set_local_finalized(&mut self, number: u64) {
+        self.local_finalized = Some(number);
+    }
+
+    /// Latest finalized block: the one op-node reports, or else the one the node finalized
+    /// itself.
+    pub fn finalized_head(&self) -> Option<u64> {
+        self.sync_status
+            .as_ref()
+            .map(|status| status.finalized_l2.number)
+            .or(self.local_finalized)
+    }
+
+    /// Latest block derivation reached, blocks up to it don't need to be batched.
+    pub fn safe_head(&self) -> Option<u64> {
+        self.sync_status
+            .as_ref()
+            .map(|status| status.safe_l2.number)
+    }
+
+    /// Whether the open channel reached the batch size or the max duration.
+    pub fn should_close(&self) -> bool {
+        if self.pending_blocks.len() >= self.policy.batch_size as usize {
+            return true;
+        }
+
+        match (self.opened_at, self.policy.max_duration) {
+            (Some(opened_at), Some(max_duration)) => {
+                opened_at.elapsed() >= Duration::from_secs(max_duration)
+            }
+            _ => false,
+        }
     }
 
     pub fn pending_blocks(&self) -> &VecDeque<BlockData> {
@@ -42,20 +277,119 @@ impl ChannelBuilder {
 
     pub fn add_block(&mut self, block: BlockData) {
         debug!("Adding block {} to pending queue", block.block_number);
+        if self.pending_blocks.is_empty() {
+            self.open(block.block_number);
+        }
         self.pending_blocks.push_back(block);
+        self.record_open_channel();
+    }
+
+    /// Puts blocks back at the front of the pending queue, e.g. after their batch was invalidated.
+    ///
+    /// The blocks must be ordered, and precede any pending block.
+    pub fn requeue_blocks(&mut self, blocks: Vec<BlockData>) {
+        let Some(first) = blocks.first() else {
+            return;
+        };
+
+        debug!(
+            "Requeueing {} blocks starting at block {}",
+            blocks.len(),
+            first.block_number
+        );
+        if self.pending_blocks.is_empty() {
+            self.open(first.block_number);
+        }
+        for block in blocks.into_iter().rev() {
+            self.pending_blocks.push_front(block);
+        }
+        self.record_open_channel();
+    }
+
+    /// Drops pending blocks numbered `block_number` or higher, returning how many were dropped.
+    pub fn truncate_from(&mut self, block_number: u64) -> usize {
+        let keep = self
+            .pending_blocks
+            .iter()
+            .take_while(|b| b.block_number < block_number)
+            .count();
+        let dropped = self.pending_blocks.len() - keep;
+
+        self.pending_blocks.truncate(keep);
+        if self.pending_blocks.is_empty() {
+            self.opened_at = None;
+            self.channel_id = None;
+        }
+        self.record_open_channel();
+
+        debug!(
+            "Dropped {} pending blocks from block {}",
+            dropped, block_number
+        );
+        dropped
     }
 
     pub fn clear_queue(&mut self) {
         let count = self.pending_blocks.len();
         self.pending_blocks.clear();
+        self.opened_at = None;
+        self.channel_id = None;
+        self.record_open_channel();
         debug!("Cleared {} blocks from pending queue", count);
     }
 
+    fn record_open_channel(&self) {
+        self.metrics
+            .channel
+            .pending_blocks
+            .set(self.pending_blocks.len() as f64);
+        self.metrics.channel.open_channel_size.set(
+            self.pending_blocks
+                .iter()
+                .map(|b| b.block_data.len())
+                .sum::<usize>() as f64,
+        );
+    }
+
+    fn open(&mut self, first_block: u64) {
+        let channel_id = Uuid::new_v4().to_string();
+        debug!("Opening channel {} at block {}", channel_id, first_block);
+
+        self.opened_at = Some(Instant::now());
+        self.channel_id = Some(channel_id.clone());
+        self.events.emit(ChannelEvent::ChannelOpened {
+            channel_id,
+            first_block,
+        });
+    }
+
+    /// Summarizes the channel currently being filled, if any blocks are pending.
+    pub fn open_channel(&self) -> Option<OpenChannel> {
+        let first = self.pending_blocks.front()?;
+        let last = self.pending_blocks.back()?;
+
+        Some(OpenChannel {
+            block_count: self.pending_blocks.len(),
+            first_block: first.block_number,
+            last_block: last.block_number,
+            size: self.pending_blocks.iter().map(|b| b.block_data.len()).sum(),
+        })
+    }
+
+    /// Closes the open channel: inserts the pending blocks as a batch and clears the queue.
+    ///
+    /// Returns the id of the created batch, or `None` if no blocks were pending.
+    pub fn close_channel(&mut self) -> anyhow::Result<Option<String>> {
+        let batch_id = self.insert_batch()?;
+        self.clear_queue();
+        Ok(batch_id)
+    }
+
     // Creates a batch from the pending blocks and inserts it into the database
-    pub fn insert_batch(&mut self) -> anyhow::Result<()> {
+    pub fn insert_batch(&mut self) -> anyhow::Result<Option<String>> {
         if self.pending_blocks.is_empty() {
             warn!("Attempted to create batch with no pending blocks");
-            return Ok(());
+            return Ok(None);
         }
 
         let db = self
@@ -71,10 +405,14 @@ impl ChannelBuilder {
             .collect();
 
         let concatenated_data = batch_data.concat();
+        let compressed_size = l1_cost::compressed_size(&concatenated_data);
         let batch_data_json = serde_json::to_string(&concatenated_data)
             .map_err(|e| anyhow::anyhow!("Failed to serialize batch data: {}", e))?;
 
-        let batch_id = Uuid::new_v4().to_string();
+        let batch_id = self
+            .channel_id
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map_err(|e| anyhow::anyhow!("System time error: {}", e))?
@@ -88,14 +426,19 @@ impl ChannelBuilder {
         // Create batch record
         db.conn()
             .execute(
-                "INSERT INTO batches (id, block_numbers, data, created_at, status) 
-                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                "INSERT INTO batches (id, block_numbers, data, created_at, status, start_block, end_block, first_block_timestamp, codec, compressed_size) 
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
                 (
                     &batch_id,
                     &block_numbers_json,
                     &batch_data_json,
                     current_time,
                     "Pending",
+                    block_numbers.iter().min(),
+                    block_numbers.iter().max(),
+                    self.pending_blocks.front().map(|b| b.timestamp),
+                    self.codec.to_string(),
+                    compressed_size,
                 ),
             )
             .map_err(|e| anyhow::anyhow!("Failed to insert batch into database: {}", e))?;
@@ -117,6 +460,63 @@ impl ChannelBuilder {
             batch_data_json.len()
         );
 
-        Ok(())
+        self.metrics.channel.channels_created.increment(1);
+        self.metrics
+            .channel
+            .compression_ratio
+            .record(concatenated_data.len() as f64 / compressed_size.max(1) as f64);
+
+        self.events.emit(ChannelEvent::ChannelClosed {
+            channel_id: batch_id.clone(),
+            block_numbers,
+            size: concatenated_data.len(),
+        });
+
+        Ok(Some(batch_id))
+    }
+}
+
+/// Closes the open channel once it's open for the max duration of the flush policy.
+///
+/// The ExEx checks the flush policy when a block comes in, so without this a channel stays
+/// open past its max duration until the next block.
+pub struct ChannelTimer {
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    interval: Duration,
+}
+
+impl ChannelTimer {
+    pub fn new(channel_builder: Arc<Mutex<ChannelBuilder>>, interval: Duration) -> Self {
+        Self {
+            channel_builder,
+            interval,
+        }
+    }
+
+    /// Closes the open channel if it's expired, returning the id of the created batch.
+    pub fn close_expired(&self) -> anyhow::Result<Option<String>> {
+        let mut channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Channel builder lock poisoned"))?;
+        if !channel_builder.should_close() {
+            return Ok(None);
+        }
+
+        debug!("Open channel reached the flush policy, creating batch...");
+        channel_builder.close_channel()
+    }
+
+    /// Checks the open channel on every tick of the interval, forever.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.close_expired() {
+                error!("Failed to close expired channel: {}", e);
+            }
+        }
     }
 }
@@ -0,0 +1,475 @@
+//! `flash_chain batcher` subcommands, inspecting and operating the batcher database.
+
+use crate::{
+    accounting::Accountant,
+    alt_da::{AltDaClient, CommitmentMode},
+    channel_builder::{ChannelBuilder, FlushPolicy},
+    codec::Codec,
+    db::{BatchInfo, BatchStatus, DB, DEFAULT_DB_PATH, DbLock},
+    op_node::OpNodeClient,
+    rebuild::{AltDaLayer, DaLayer, Rebuilder},
+    verifier::Verifier,
+};
+use alloy_primitives::hex;
+use clap::{Args, Subcommand};
+use reth::providers::BlockNumReader;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, EnvironmentArgs};
+use reth_node_api::BlockTy;
+use reth_optimism_forks::OpHardforks;
+use reth_primitives::SealedBlock;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::{
+    fs::File,
+    io::Write,
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Inspect and operate the batcher database.
+#[derive(Debug, Args)]
+pub struct BatcherCommand<C: ChainSpecParser> {
+    /// Path of the batcher database.
+    #[arg(long, value_name = "PATH", default_value = DEFAULT_DB_PATH, global = true)]
+    pub db: PathBuf,
+
+    #[command(subcommand)]
+    pub command: BatcherSubcommand<C>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BatcherSubcommand<C: ChainSpecParser> {
+    /// List channels in creation order.
+    List {
+        /// Only list channels with this status.
+        #[arg(long)]
+        status: Option<BatchStatus>,
+
+        #[arg(long, default_value_t = 0)]
+        offset: u64,
+
+        #[arg(long, default_value_t = 100)]
+        limit: u64,
+    },
+    /// Show the metadata of a channel.
+    Show { id: String },
+    /// Pretty-print the batches of a channel.
+    Decode { id: String },
+    /// Mark a submitted or failed channel as pending again, so it gets resubmitted.
+    Requeue { id: String },
+    /// Export the channels covering a block range as JSON lines.
+    Export {
+        /// Block range, as `<FROM>..<TO>` (inclusive).
+        #[arg(long, value_parser = parse_range)]
+        range: RangeInclusive<u64>,
+
+        /// File to write to, defaults to stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Show aggregate statistics of the database.
+    Stats,
+    /// Rewrite the database, enabling incremental vacuum so pruning frees disk space. Run it
+    /// with the node stopped, it takes as long as copying the database.
+    Vacuum,
+    /// Regenerate the channels of a block range from the node's chain history.
+    Rebuild(Box<RebuildCommand<C>>),
+    /// Report the L1 data fee revenue of a block range against the costs of its channels.
+    Report(Box<ReportCommand<C>>),
+    /// Check the channels of a block range decode back into the node's blocks.
+    Verify(Box<VerifyCommand<C>>),
+}
+
+impl<C: ChainSpecParser> BatcherSubcommand<C> {
+    /// Whether the command writes to the database, which is then opened for writing and
+    /// migrated. Other commands open it read-only.
+    fn writes(&self) -> bool {
+        matches!(self, Self::Requeue { .. } | Self::Vacuum | Self::Rebuild(_))
+    }
+
+    /// Whether the command can't run along the node, and takes the database lock it holds. A
+    /// rebuild would create channels along the ones of the node, and a vacuum would stall it.
+    fn locks(&self) -> bool {
+        matches!(self, Self::Vacuum | Self::Rebuild(_))
+    }
+}
+
+/// Regenerates the channels of the blocks missing from the database.
+#[derive(Debug, Args)]
+pub struct RebuildCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    env: EnvironmentArgs<C>,
+
+    /// Block range, as `<FROM>..<TO>` (inclusive). Defaults to all blocks after genesis.
+    #[arg(long, value_parser = parse_range)]
+    range: Option<RangeInclusive<u64>>,
+
+    /// Number of blocks after which a channel is closed.
+    #[arg(long, value_name = "BLOCKS", default_value_t = 10)]
+    batch_size: u64,
+
+    /// Seconds of block time after which a channel is closed.
+    #[arg(long, value_name = "SECONDS")]
+    max_duration: Option<u64>,
+
+    /// Codec the batches of the created channels are serialized with.
+    #[arg(long, value_enum, default_value_t = Codec::Json)]
+    codec: Codec,
+
+    /// Check the channels of the range against the alt-DA server: created channels it has are
+    /// marked submitted, submitted channels it doesn't have are requeued.
+    #[arg(long, requires = "alt_da_url")]
+    reconcile: bool,
+
+    /// URL of the alt-DA server channels were put to.
+    #[arg(long, value_name = "URL")]
+    alt_da_url: Option<String>,
+
+    /// Which commitments channels were put under.
+    #[arg(long, value_enum, default_value_t = CommitmentMode::Keccak)]
+    alt_da_commitment: CommitmentMode,
+}
+
+/// Reports the revenue and costs of the channels of a block range, per channel and per day.
+#[derive(Debug, Args)]
+pub struct ReportCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    env: EnvironmentArgs<C>,
+
+    /// Block range, as `<FROM>..<TO>` (inclusive). Defaults to all batched blocks.
+    #[arg(long, value_parser = parse_range)]
+    range: Option<RangeInclusive<u64>>,
+}
+
+/// Verifies the channels of a block range against the node's blocks, as `batcher_verify` does.
+#[derive(Debug, Args)]
+pub struct VerifyCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    env: EnvironmentArgs<C>,
+
+    /// Block range, as `<FROM>..<TO>` (inclusive). Defaults to all batched blocks.
+    #[arg(long, value_parser = parse_range)]
+    range: Option<RangeInclusive<u64>>,
+
+    /// URL of the alt-DA server channels were put to, read back from it as derivation would.
+    #[arg(long, value_name = "URL")]
+    alt_da_url: Option<String>,
+
+    /// Which commitments channels were put under.
+    #[arg(long, value_enum, default_value_t = CommitmentMode::Keccak)]
+    alt_da_commitment: CommitmentMode,
+
+    /// URL of the op-node RPC. Blocks outside any channel are only reported missing past its
+    /// safe head, the batcher doesn't batch blocks already safe.
+    #[arg(long, value_name = "URL")]
+    op_node_rpc: Option<String>,
+}
+
+impl<C: ChainSpecParser> BatcherCommand<C> {
+    pub fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec>,
+        C::ChainSpec: OpHardforks,
+        SealedBlock<BlockTy<N>>: DeserializeOwned,
+    {
+        let db_path = self.db.to_string_lossy();
+        let _lock = self
+            .command
+            .locks()
+            .then(|| DbLock::acquire(&db_path))
+            .transpose()
+            .map_err(|e| eyre::eyre!("Failed to lock database {}: {}", db_path, e))?;
+        let db = match self.command {
+            // Only a rebuild creates the database, other commands would read an empty one
+            BatcherSubcommand::Rebuild(_) => DB::new(&db_path),
+            BatcherSubcommand::Requeue { .. } | BatcherSubcommand::Vacuum => {
+                DB::open_existing(&db_path)
+            }
+            _ => DB::open_read_only(&db_path),
+        }
+        .map_err(|e| eyre::eyre!("Failed to open database {}: {}", db_path, e))?;
+
+        if self.command.writes() {
+            db.initialize_database()
+                .map_err(|e| eyre::eyre!("Failed to initialize database: {}", e))?;
+        } else {
+            // Migrating takes a write lock, and may rewrite the whole database
+            let missing = db.missing_columns()?;
+            if !missing.is_empty() {
+                return Err(eyre::eyre!(
+                    "Database {} predates this version, it lacks columns {}. Start the node once to migrate it",
+                    db_path,
+                    missing.join(", ")
+                ));
+            }
+        }
+
+        match self.command {
+            BatcherSubcommand::List {
+                status,
+                offset,
+                limit,
+            } => {
+                let batches = db.list_batches(status, offset, limit)?;
+
+                println!(
+                    "{:<36}  {:<11}  {:>21}  {:>10}  {:>10}",
+                    "ID", "STATUS", "BLOCKS", "SIZE", "CREATED"
+                );
+                for batch in batches {
+                    println!(
+                        "{:<36}  {:<11}  {:>21}  {:>10}  {:>10}",
+                        batch.id,
+                        batch.status.to_string(),
+                        block_range(&batch),
+                        if batch.pruned_at.is_some() {
+                            "pruned".to_string()
+                        } else {
+                            batch.data.len().to_string()
+                        },
+                        batch.created_at
+                    );
+                }
+            }
+            BatcherSubcommand::Show { id } => {
+                let batch = get_batch(&db, &id)?;
+                println!("{}", serde_json::to_string_pretty(&metadata(&batch))?);
+            }
+            BatcherSubcommand::Decode { id } => {
+                let batch = get_batch(&db, &id)?;
+                if batch.pruned_at.is_some() {
+                    return Err(eyre::eyre!("Channel {} was pruned", id));
+                }
+
+                let batches = batch
+                    .codec
+                    .decode_channel_of::<BlockTy<N>>(&batch.data)
+                    .map_err(|e| eyre::eyre!("Failed to decode channel {}: {}", id, e))?;
+                for (block_number, decoded) in batch.block_numbers.iter().zip(batches) {
+                    let decoded = if batch.codec.has_batch_fields() {
+                        json!(decoded)
+                    } else {
+                        json!({ "transactions": decoded.transactions })
+                    };
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({
+                            "blockNumber": block_number,
+                            "batch": decoded,
+                        }))?
+                    );
+                }
+            }
+            BatcherSubcommand::Requeue { id } => {
+                let batch = get_batch(&db, &id)?;
+                // Requeueing it would get it put and posted twice
+                if batch.status == BatchStatus::Submitting {
+                    return Err(eyre::eyre!(
+                        "Channel {} is being submitted by the node, which requeues it if the \
+                         submission fails",
+                        id
+                    ));
+                }
+                if !db.requeue_batch(&id)? {
+                    return Err(eyre::eyre!(
+                        "Channel {} can't be requeued (status {}, pruned: {})",
+                        id,
+                        batch.status,
+                        batch.pruned_at.is_some()
+                    ));
+                }
+                println!("Requeued channel {}", id);
+            }
+            BatcherSubcommand::Export { range, output } => {
+                let mut writer: Box<dyn Write> = match output {
+                    Some(path) => Box::new(File::create(path)?),
+                    None => Box::new(std::io::stdout()),
+                };
+
+                for batch in db.get_batches_in_range(*range.start(), *range.end())? {
+                    let mut line = metadata(&batch);
+                    line["data"] = match batch.pruned_at {
+                        Some(_) => serde_json::Value::Null,
+                        None => hex::encode_prefixed(&batch.data).into(),
+                    };
+                    writeln!(writer, "{}", serde_json::to_string(&line)?)?;
+                }
+            }
+            BatcherSubcommand::Stats => {
+                let stats = db.get_stats()?;
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            }
+            BatcherSubcommand::Vacuum => {
+                db.vacuum()?;
+                println!("Vacuumed database {}", db_path);
+            }
+            BatcherSubcommand::Rebuild(command) => command.execute::<N>(db)?,
+            BatcherSubcommand::Report(command) => command.execute::<N>(db)?,
+            BatcherSubcommand::Verify(command) => command.execute::<N>(db)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: ChainSpecParser> RebuildCommand<C> {
+    fn execute<N>(self, db: DB) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec>,
+    {
+        let env = self.env.init::<N>(AccessRights::RO)?;
+        let provider = env.provider_factory;
+
+        let range = match self.range {
+            Some(range) => range,
+            // Genesis has no batch
+            None => 1..=provider.best_block_number()?,
+        };
+
+        let policy = FlushPolicy::new(self.batch_size, self.max_duration);
+        let rebuilder = Rebuilder::new(provider, Arc::new(Mutex::new(db)), policy, self.codec);
+        let da = match self.alt_da_url.as_deref() {
+            Some(url) if self.reconcile => Some(AltDaLayer::new(AltDaClient::new(
+                url,
+                self.alt_da_commitment,
+            ))?),
+            _ => None,
+        };
+
+        let report = rebuilder.rebuild(
+            *range.start(),
+            *range.end(),
+            da.as_ref().map(|da| da as &dyn DaLayer),
+        )?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        Ok(())
+    }
+}
+
+impl<C: ChainSpecParser> ReportCommand<C>
+where
+    C::ChainSpec: OpHardforks,
+{
+    fn execute<N>(self, db: DB) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec>,
+    {
+        let env = self.env.init::<N>(AccessRights::RO)?;
+        let accountant = Accountant::new(env.provider_factory, Arc::new(Mutex::new(db)));
+
+        let range = match self.range {
+            Some(range) => range,
+            // Genesis has no batch
+            None => 1..=accountant.latest_batched_block()?.unwrap_or(1),
+        };
+
+        let report = accountant.report(*range.start(), *range.end())?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        Ok(())
+    }
+}
+
+impl<C: ChainSpecParser> VerifyCommand<C> {
+    fn execute<N>(self, db: DB) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec>,
+        SealedBlock<BlockTy<N>>: DeserializeOwned,
+    {
+        let env = self.env.init::<N>(AccessRights::RO)?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let report = runtime.block_on(async {
+            // Only read for the op-node safe head, no block is pending outside the node
+            let mut channel_builder =
+                ChannelBuilder::new(Arc::new(Mutex::new(db)), FlushPolicy::new(1, None));
+            if let Some(url) = self.op_node_rpc.as_deref() {
+                channel_builder.set_sync_status(OpNodeClient::new(url)?.sync_status().await?);
+            }
+            let alt_da = self
+                .alt_da_url
+                .as_deref()
+                .map(|url| AltDaClient::new(url, self.alt_da_commitment));
+            let verifier = Verifier::new(
+                env.provider_factory,
+                Arc::new(Mutex::new(channel_builder)),
+                alt_da,
+            );
+
+            let range = match self.range {
+                Some(range) => range,
+                // Genesis has no batch
+                None => 1..=verifier.latest_batched_block()?.unwrap_or(1),
+            };
+            verifier.verify_range(*range.start(), *range.end()).await
+        })?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        if !report.is_ok() {
+            return Err(eyre::eyre!(
+                "Found {} mismatches verifying blocks {} to {}",
+                report.mismatches.len(),
+                report.from_block,
+                report.to_block
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn get_batch(db: &DB, id: &str) -> eyre::Result<BatchInfo> {
+    db.get_batch(id)?
+        .ok_or_else(|| eyre::eyre!("Channel {} not found", id))
+}
+
+fn block_range(batch: &BatchInfo) -> String {
+    match (batch.block_numbers.first(), batch.block_numbers.last()) {
+        (Some(first), Some(last)) => format!("{first}-{last}"),
+        _ => "-".to_string(),
+    }
+}
+
+fn metadata(batch: &BatchInfo) -> serde_json::Value {
+    json!({
+        "id": batch.id,
+        "status": batch.status,
+        "blockNumbers": batch.block_numbers,
+        "size": batch.data.len(),
+        "createdAt": batch.created_at,
+        "submittedAt": batch.submitted_at,
+        "celestiaHeight": batch.celestia_height,
+        "retryCount": batch.retry_count,
+        "prunedAt": batch.pruned_at,
+        "firstBlockTimestamp": batch.first_block_timestamp,
+        "daCommitment": batch.da_commitment,
+        "l1TxHash": batch.l1_tx_hash,
+        "codec": batch.codec,
+        "compressedSize": batch.compressed_size,
+        "estimatedL1Cost": batch.estimated_l1_cost,
+    })
+}
+
+fn parse_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let (from, to) = s
+        .split_once("..")
+        .ok_or_else(|| format!("invalid range '{s}', expected <FROM>..<TO>"))?;
+    let from: u64 = from
+        .parse()
+        .map_err(|e| format!("invalid range start '{from}': {e}"))?;
+    let to: u64 = to
+        .trim_start_matches('=')
+        .parse()
+        .map_err(|e| format!("invalid range end '{to}': {e}"))?;
+
+    if from > to {
+        return Err(format!("invalid range '{s}', start is after end"));
+    }
+
+    Ok(from..=to)
+}
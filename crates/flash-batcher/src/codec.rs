@@ -0,0 +1,189 @@
+use crate::batch::SingularBatch;
+use alloy_primitives::Bytes;
+use alloy_rlp::{Decodable, Encodable};
+use clap::ValueEnum;
+use reth_primitives::SealedBlock;
+use reth_primitives_traits::Block;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Cursor, str::FromStr};
+
+/// Version byte of singular batches in op-stack channels.
+const SINGULAR_BATCH_VERSION: u8 = 0x00;
+
+/// How the batch of each block is serialized in a channel payload.
+///
+/// A channel payload is the concatenation of its serialized batches, or blocks with
+/// [`Codec::BlockRlp`]. The codec is recorded with each batch, so readers know how to decode it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Codec {
+    /// JSON batches.
+    #[default]
+    Json,
+    /// bincode batches.
+    Bincode,
+    /// op-stack singular batches: the `0x00` version byte followed by the RLP list of the parent
+    /// hash, epoch number, epoch hash, timestamp and transactions, as in channels.
+    SingularBatch,
+    /// RLP list of the batch transactions only, without the parent hash, epoch and timestamp.
+    RawTransactions,
+    /// The network RLP encoding of the whole block, header and deposits included.
+    BlockRlp,
+}
+
+impl Codec {
+    pub const ALL: [Codec; 5] = [
+        Codec::Json,
+        Codec::Bincode,
+        Codec::SingularBatch,
+        Codec::RawTransactions,
+        Codec::BlockRlp,
+    ];
+
+    /// Whether decoded batches carry the parent hash, epoch and timestamp, or only transactions.
+    pub fn has_batch_fields(&self) -> bool {
+        !matches!(self, Codec::RawTransactions)
+    }
+
+    /// Whether channels hold whole blocks rather than batches, so they are only encoded with
+    /// [`Codec::encode_block`] and decoded with [`Codec::decode_channel_of`].
+    pub fn stores_blocks(&self) -> bool {
+        matches!(self, Codec::BlockRlp)
+    }
+
+    /// Encodes the data a block adds to a channel.
+    pub fn encode_block<B: Block>(&self, block: &SealedBlock<B>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Codec::BlockRlp => Ok(alloy_rlp::encode(block.clone_block())),
+            _ => self.encode(&SingularBatch::from_block(block)?),
+        }
+    }
+
+    /// Decodes a channel payload of blocks of type `B` into their batches.
+    ///
+    /// Channels of the batch codecs are decoded as with [`Codec::decode_channel`].
+    pub fn decode_channel_of<B: Block>(&self, data: &[u8]) -> anyhow::Result<Vec<SingularBatch>> {
+        if !self.stores_blocks() {
+            return self.decode_channel(data);
+        }
+
+        let mut buf = data;
+        let mut batches = Vec::new();
+        while !buf.is_empty() {
+            let block = B::decode(&mut buf)
+                .map_err(|e| anyhow::anyhow!("Failed to decode channel: {}", e))?;
+            batches.push(SingularBatch::from_block(&SealedBlock::seal_slow(block))?);
+        }
+        Ok(batches)
+    }
+
+    /// Encodes a batch, with the codecs that store batches.
+    pub fn encode(&self, batch: &SingularBatch) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Codec::Json => serde_json::to_vec(batch)
+                .map_err(|e| anyhow::anyhow!("Failed to serialize batch: {}", e)),
+            Codec::Bincode => bincode::serialize(batch)
+                .map_err(|e| anyhow::anyhow!("Failed to serialize batch: {}", e)),
+            Codec::SingularBatch => {
+                let mut out = vec![SINGULAR_BATCH_VERSION];
+                batch.encode(&mut out);
+                Ok(out)
+            }
+            Codec::RawTransactions => Ok(alloy_rlp::encode(&batch.transactions)),
+            Codec::BlockRlp => Err(anyhow::anyhow!(
+                "The {} codec encodes blocks, not batches",
+                self
+            )),
+        }
+    }
+
+    /// Decodes a channel payload into its batches.
+    ///
+    /// With [`Codec::RawTransactions`], only the transactions of the batches are set. Channels of
+    /// [`Codec::BlockRlp`] need the block type, see [`Codec::decode_channel_of`].
+    pub fn decode_channel(&self, data: &[u8]) -> anyhow::Result<Vec<SingularBatch>> {
+        match self {
+            Codec::Json => serde_json::Deserializer::from_slice(data)
+                .into_iter::<SingularBatch>()
+                .collect::<Result<_, _>>()
+                .map_err(|e| anyhow::anyhow!("Failed to decode channel: {}", e)),
+            Codec::Bincode => {
+                let mut cursor = Cursor::new(data);
+                let mut batches = Vec::new();
+                while (cursor.position() as usize) < data.len() {
+                    batches.push(
+                        bincode::deserialize_from(&mut cursor)
+                            .map_err(|e| anyhow::anyhow!("Failed to decode channel: {}", e))?,
+                    );
+                }
+                Ok(batches)
+            }
+            Codec::SingularBatch => {
+                let mut buf = data;
+                let mut batches = Vec::new();
+                while let Some((version, rest)) = buf.split_first() {
+                    if *version != SINGULAR_BATCH_VERSION {
+                        return Err(anyhow::anyhow!(
+                            "Failed to decode channel: unknown batch version 0x{:02x}",
+                            version
+                        ));
+                    }
+                    buf = rest;
+                    batches.push(
+                        SingularBatch::decode(&mut buf)
+                            .map_err(|e| anyhow::anyhow!("Failed to decode channel: {}", e))?,
+                    );
+                }
+                Ok(batches)
+            }
+            Codec::RawTransactions => {
+                let mut buf = data;
+                let mut batches = Vec::new();
+                while !buf.is_empty() {
+                    let transactions = Vec::<Bytes>::decode(&mut buf)
+                        .map_err(|e| anyhow::anyhow!("Failed to decode channel: {}", e))?;
+                    batches.push(SingularBatch {
+                        parent_hash: Default::default(),
+                        epoch_num: 0,
+                        epoch_hash: Default::default(),
+                        timestamp: 0,
+                        transactions,
+                    });
+                }
+                Ok(batches)
+            }
+            Codec::BlockRlp => Err(anyhow::anyhow!(
+                "Failed to decode channel: the {} codec needs the block type",
+                self
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Codec::Json => "json",
+            Codec::Bincode => "bincode",
+            Codec::SingularBatch => "singular-batch",
+            Codec::RawTransactions => "raw-transactions",
+            Codec::BlockRlp => "block-rlp",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Former name of the singular batch codec, still found in channels of older batchers
+        if s == "rlp" {
+            return Ok(Codec::SingularBatch);
+        }
+        Codec::ALL
+            .into_iter()
+            .find(|codec| codec.to_string() == s)
+            .ok_or_else(|| format!("unknown codec '{s}'"))
+    }
+}
@@ -1,10 +1,70 @@
-use std::fmt::Display;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{File, TryLockError},
+    io,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Result, Row};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use tracing::{debug, error, info, warn};
 
+use crate::{
+    codec::Codec,
+    l1_cost::{DaType, L1Cost},
+};
+
+/// Default location of the batcher database, relative to the working directory.
+pub const DEFAULT_DB_PATH: &str = "batcher.db";
+
+/// Columns the `batches` table was first created with.
+const BATCHES_COLUMNS: [&str; 8] = [
+    "id",
+    "block_numbers",
+    "data",
+    "created_at",
+    "submitted_at",
+    "celestia_height",
+    "retry_count",
+    "status",
+];
+
+/// Columns added to the `batches` table after it was first created, with their definitions.
+///
+/// Databases created before pruning was supported lack the block range columns, and batches
+/// were JSON serialized before the codec was recorded. Wei amounts are decimal `TEXT`, as
+/// SQLite integers can't hold amounts above `i64::MAX`.
+const MIGRATED_COLUMNS: [(&str, &str); 13] = [
+    ("start_block", "INTEGER"),
+    ("end_block", "INTEGER"),
+    ("pruned_at", "INTEGER"),
+    ("first_block_timestamp", "INTEGER"),
+    ("da_commitment", "TEXT"),
+    ("codec", "TEXT NOT NULL DEFAULT 'json'"),
+    ("l1_da_type", "TEXT"),
+    ("compressed_size", "INTEGER"),
+    ("estimated_l1_fee", "TEXT"),
+    ("estimated_l1_base_fee", "TEXT"),
+    ("estimated_l1_blob_base_fee", "TEXT"),
+    ("alt_da_fee", "TEXT"),
+    ("l1_tx_hash", "TEXT"),
+];
+
+/// Fee columns of earlier versions, which stored wei amounts as integers, with the columns they
+/// are moved to.
+const LEGACY_FEE_COLUMNS: [(&str, &str); 4] = [
+    ("l1_fee", "estimated_l1_fee"),
+    ("l1_base_fee", "estimated_l1_base_fee"),
+    ("l1_blob_base_fee", "estimated_l1_blob_base_fee"),
+    ("da_fee", "alt_da_fee"),
+];
+
+/// `PRAGMA auto_vacuum` value of incremental vacuum.
+const INCREMENTAL_AUTO_VACUUM: i64 = 2;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BlockData {
     pub block_number: u64,
@@ -24,14 +84,56 @@ pub struct BatchInfo {
     pub celestia_height: Option<u64>,
     pub retry_count: u32,
     pub status: BatchStatus,
+    pub pruned_at: Option<i64>,
+    /// Timestamp of the first block in the batch.
+    pub first_block_timestamp: Option<u64>,
+    /// Hex encoded alt-DA commitment, once put to the DA server.
+    pub da_commitment: Option<String>,
+    /// Hash of the L1 transaction posting the alt-DA commitment to the batch inbox.
+    pub l1_tx_hash: Option<String>,
+    /// Codec the batches in `data` are serialized with.
+    pub codec: Codec,
+    /// Estimated L1 data fee of the batch, once submitted with the L1 fees known.
+    pub estimated_l1_cost: Option<L1Cost>,
+    /// Fee of the alt-DA server for the batch, in wei, once put with a DA fee configured.
+    pub da_fee: Option<u128>,
+    /// Size of `data` once zlib compressed, as op-batcher compresses channels. `None` for
+    /// batches created before it was recorded.
+    pub compressed_size: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchStats {
+    pub total: u64,
+    pub by_status: BTreeMap<String, u32>,
+    /// Size of the stored batch payloads, in bytes.
+    pub stored_bytes: u64,
+    pub pruned: u64,
+    pub first_block: Option<u64>,
+    pub last_block: Option<u64>,
+    /// Creation time of the oldest pending batch.
+    pub oldest_pending_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum BatchStatus {
     Pending,
     Submitting,
     Submitted,
     Failed,
+    /// Contains blocks that were reorged out.
+    Invalidated,
+}
+
+impl BatchStatus {
+    pub const ALL: [BatchStatus; 5] = [
+        BatchStatus::Pending,
+        BatchStatus::Submitting,
+        BatchStatus::Submitted,
+        BatchStatus::Failed,
+        BatchStatus::Invalidated,
+    ];
 }
 
 impl Display for BatchStatus {
@@ -41,6 +143,22 @@ impl Display for BatchStatus {
             BatchStatus::Submitting => write!(f, "Submitting"),
             BatchStatus::Submitted => write!(f, "Submitted"),
             BatchStatus::Failed => write!(f, "Failed"),
+            BatchStatus::Invalidated => write!(f, "Invalidated"),
+        }
+    }
+}
+
+impl FromStr for BatchStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(BatchStatus::Pending),
+            "Submitting" => Ok(BatchStatus::Submitting),
+            "Submitted" => Ok(BatchStatus::Submitted),
+            "Failed" => Ok(BatchStatus::Failed),
+            "Invalidated" => Ok(BatchStatus::Invalidated),
+            unknown => Err(format!("unknown batch status '{unknown}'")),
         }
     }
 }
@@ -49,6 +167,40 @@ pub struct DB {
     conn: Connection,
 }
 
+/// Exclusive lock on a batcher database, held by the node while it runs and by commands that
+/// can't run along it. Released when dropped.
+///
+/// SQLite locks only last as long as a transaction, so the lock is taken on a `<db>.lock` file
+/// next to the database, which the OS releases if the process dies.
+#[derive(Debug)]
+pub struct DbLock {
+    _file: File,
+}
+
+impl DbLock {
+    /// Takes the lock of the database at `file_path`, failing if another process holds it.
+    pub fn acquire(file_path: &str) -> io::Result<Self> {
+        let path = format!("{file_path}.lock");
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {
+                debug!("Locked database {}", file_path);
+                Ok(Self { _file: file })
+            }
+            Err(TryLockError::WouldBlock) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("{file_path} is locked by another process, is the node running?"),
+            )),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
 impl DB {
     pub fn new(file_path: &str) -> Result<Self> {
         let conn = Connection::open(file_path).map_err(|e| {
@@ -61,6 +213,35 @@ impl DB {
         Ok(Self { conn })
     }
 
+    /// Opens an existing database, failing instead of creating it when missing.
+    pub fn open_existing(file_path: &str) -> Result<Self> {
+        let flags = OpenFlags::default() - OpenFlags::SQLITE_OPEN_CREATE;
+        let conn = Connection::open_with_flags(file_path, flags).map_err(|e| {
+            error!("Failed to open database at {}: {}", file_path, e);
+            e
+        })?;
+
+        info!("Database connection established: {}", file_path);
+
+        Ok(Self { conn })
+    }
+
+    /// Opens an existing database read-only, without migrating it. Check its schema with
+    /// [`DB::missing_columns`] before reading it.
+    pub fn open_read_only(file_path: &str) -> Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(file_path, flags).map_err(|e| {
+            error!("Failed to open database at {}: {}", file_path, e);
+            e
+        })?;
+
+        info!("Database connection established read-only: {}", file_path);
+
+        Ok(Self { conn })
+    }
+
     pub fn conn(&self) -> &Connection {
         &self.conn
     }
@@ -68,6 +249,21 @@ impl DB {
     pub fn initialize_database(&self) -> Result<()> {
         debug!("Initializing database schema...");
 
+        // Pruned pages are freed in small steps by the pruner, rather than by a `VACUUM`
+        // rewriting the whole database. This only applies right away to new databases, others
+        // have to be rewritten once with `batcher vacuum`, which would stall startup.
+        self.conn
+            .execute_batch("PRAGMA auto_vacuum = INCREMENTAL")
+            .map_err(|e| {
+                error!("Failed to enable incremental vacuum: {}", e);
+                e
+            })?;
+        if !self.incremental_auto_vacuum()? {
+            warn!(
+                "Database doesn't use incremental vacuum, pruned pages aren't freed: stop the node and run `flash_chain batcher vacuum` to enable it"
+            );
+        }
+
         self.conn
             .execute(
                 "CREATE TABLE IF NOT EXISTS batches (
@@ -78,7 +274,11 @@ impl DB {
                 submitted_at INTEGER,
                 celestia_height INTEGER,
                 retry_count INTEGER DEFAULT 0,
-                status TEXT NOT NULL DEFAULT 'Pending'
+                status TEXT NOT NULL DEFAULT 'Pending',
+                start_block INTEGER,
+                end_block INTEGER,
+                pruned_at INTEGER,
+                first_block_timestamp INTEGER
             )",
                 [],
             )
@@ -87,77 +287,110 @@ impl DB {
                 e
             })?;
 
+        for (column, definition) in MIGRATED_COLUMNS {
+            self.add_column_if_missing("batches", column, definition)?;
+        }
+        for (legacy, column) in LEGACY_FEE_COLUMNS {
+            self.move_legacy_fee_column(legacy, column)?;
+        }
+
+        self.conn
+            .execute(
+                "UPDATE batches SET
+                    start_block = (SELECT MIN(value) FROM json_each(batches.block_numbers)),
+                    end_block = (SELECT MAX(value) FROM json_each(batches.block_numbers))
+                 WHERE start_block IS NULL OR end_block IS NULL",
+                [],
+            )
+            .map_err(|e| {
+                error!("Failed to backfill batch block ranges: {}", e);
+                e
+            })?;
+
         info!("Database schema initialized successfully");
         Ok(())
     }
 
+    /// Returns the columns of the current schema the database lacks, every one of them when it
+    /// has no `batches` table. Empty when the database is up to date.
+    pub fn missing_columns(&self) -> Result<Vec<&'static str>> {
+        let mut missing = Vec::new();
+        for column in BATCHES_COLUMNS
+            .into_iter()
+            .chain(MIGRATED_COLUMNS.map(|(column, _)| column))
+        {
+            if !self.has_column("batches", column)? {
+                missing.push(column);
+            }
+        }
+
+        Ok(missing)
+    }
+
+    fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+        self.conn
+            .query_row(
+                &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{table}') WHERE name = ?"),
+                [column],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                error!("Failed to inspect columns of table {}: {}", table, e);
+                e
+            })
+    }
+
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        if !self.has_column(table, column)? {
+            debug!("Adding column {} to table {}", column, table);
+            self.conn
+                .execute(
+                    &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                    [],
+                )
+                .map_err(|e| {
+                    error!("Failed to add column {} to table {}: {}", column, table, e);
+                    e
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the wei amounts of an integer `legacy` column to the text `column`, and drops it.
+    fn move_legacy_fee_column(&self, legacy: &str, column: &str) -> Result<()> {
+        if !self.has_column("batches", legacy)? {
+            return Ok(());
+        }
+
+        debug!("Moving column {} to {}", legacy, column);
+        self.conn
+            .execute_batch(&format!(
+                "UPDATE batches SET {column} = CAST({legacy} AS TEXT) WHERE {legacy} IS NOT NULL;
+                 ALTER TABLE batches DROP COLUMN {legacy}"
+            ))
+            .map_err(|e| {
+                error!("Failed to move column {} to {}: {}", legacy, column, e);
+                e
+            })
+    }
+
+    /// Returns the pending batches in block order, the order they must be submitted in.
     pub fn get_pending_batches(&self) -> Result<Vec<BatchInfo>> {
         debug!("Fetching pending batches from database...");
 
         let mut stmt = self
             .conn
-            .prepare("SELECT * FROM batches WHERE status = 'Pending' ORDER BY created_at ASC")
+            .prepare("SELECT * FROM batches WHERE status = 'Pending' ORDER BY start_block ASC, created_at ASC")
             .map_err(|e| {
                 error!("Failed to prepare pending batches query: {}", e);
                 e
             })?;
 
-        let batches = stmt
-            .query_map([], |row| {
-                let block_numbers_str: String = row.get(1)?;
-                let status_str: String = row.get(7)?;
-                let data_str: String = row.get(2)?;
-
-                let block_numbers = match serde_json::from_str(&block_numbers_str) {
-                    Ok(nums) => nums,
-                    Err(e) => {
-                        error!(
-                            "Failed to deserialize block numbers for batch {}: {}",
-                            row.get::<_, String>(0).unwrap_or_default(),
-                            e
-                        );
-                        Vec::new()
-                    }
-                };
-
-                let data = match serde_json::from_str(&data_str) {
-                    Ok(d) => d,
-                    Err(e) => {
-                        error!(
-                            "Failed to deserialize batch data for batch {}: {}",
-                            row.get::<_, String>(0).unwrap_or_default(),
-                            e
-                        );
-                        Vec::new()
-                    }
-                };
-
-                let status = match status_str.as_str() {
-                    "Pending" => BatchStatus::Pending,
-                    "Submitting" => BatchStatus::Submitting,
-                    "Submitted" => BatchStatus::Submitted,
-                    "Failed" => BatchStatus::Failed,
-                    unknown => {
-                        warn!("Unknown batch status '{}', defaulting to Pending", unknown);
-                        BatchStatus::Pending
-                    }
-                };
-
-                Ok(BatchInfo {
-                    id: row.get(0)?,
-                    block_numbers,
-                    data,
-                    created_at: row.get(3)?,
-                    submitted_at: row.get(4)?,
-                    celestia_height: row.get(5)?,
-                    retry_count: row.get(6)?,
-                    status,
-                })
-            })
-            .map_err(|e| {
-                error!("Failed to execute pending batches query: {}", e);
-                e
-            })?;
+        let batches = stmt.query_map([], batch_from_row).map_err(|e| {
+            error!("Failed to execute pending batches query: {}", e);
+            e
+        })?;
 
         let result: Result<Vec<BatchInfo>> = batches.collect();
 
@@ -169,14 +402,113 @@ impl DB {
         result
     }
 
+    pub fn get_batch(&self, batch_id: &str) -> Result<Option<BatchInfo>> {
+        debug!("Fetching batch {}", batch_id);
+
+        self.conn
+            .query_row(
+                "SELECT * FROM batches WHERE id = ?",
+                [batch_id],
+                batch_from_row,
+            )
+            .optional()
+            .map_err(|e| {
+                error!("Failed to fetch batch {}: {}", batch_id, e);
+                e
+            })
+    }
+
+    /// Returns the batch containing the given block, if any.
+    pub fn get_batch_for_block(&self, block_number: u64) -> Result<Option<BatchInfo>> {
+        debug!("Fetching batch for block {}", block_number);
+
+        self.conn
+            .query_row(
+                "SELECT * FROM batches WHERE ?1 BETWEEN start_block AND end_block
+                 ORDER BY created_at DESC LIMIT 1",
+                [block_number],
+                batch_from_row,
+            )
+            .optional()
+            .map_err(|e| {
+                error!("Failed to fetch batch for block {}: {}", block_number, e);
+                e
+            })
+    }
+
+    /// Returns the valid batches containing blocks in `from..=to`, ordered by first block.
+    pub fn get_batches_in_range(&self, from: u64, to: u64) -> Result<Vec<BatchInfo>> {
+        debug!("Fetching batches for blocks {} to {}", from, to);
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT * FROM batches
+                 WHERE status != 'Invalidated' AND end_block >= ?1 AND start_block <= ?2
+                 ORDER BY start_block ASC, created_at ASC",
+            )
+            .map_err(|e| {
+                error!("Failed to prepare batches in range query: {}", e);
+                e
+            })?;
+
+        let batches = stmt.query_map((from, to), batch_from_row).map_err(|e| {
+            error!(
+                "Failed to fetch batches for blocks {} to {}: {}",
+                from, to, e
+            );
+            e
+        })?;
+
+        batches.collect()
+    }
+
+    /// Lists batches ordered by creation time, optionally filtered by status.
+    pub fn list_batches(
+        &self,
+        status: Option<BatchStatus>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<BatchInfo>> {
+        debug!(
+            "Listing batches with status {:?}, offset {}, limit {}",
+            status, offset, limit
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT * FROM batches WHERE ?1 IS NULL OR status = ?1
+                 ORDER BY created_at ASC, start_block ASC LIMIT ?2 OFFSET ?3",
+            )
+            .map_err(|e| {
+                error!("Failed to prepare list batches query: {}", e);
+                e
+            })?;
+
+        let batches = stmt
+            .query_map(
+                (status.map(|s| s.to_string()), limit, offset),
+                batch_from_row,
+            )
+            .map_err(|e| {
+                error!("Failed to execute list batches query: {}", e);
+                e
+            })?;
+
+        batches.collect()
+    }
+
     pub fn update_batch_status(&self, batch_id: &str, status: BatchStatus) -> Result<()> {
         debug!("Updating batch {} status to {}", batch_id, status);
 
         let rows_affected = self
             .conn
             .execute(
-                "UPDATE batches SET status = ? WHERE id = ?",
-                (status.to_string(), batch_id),
+                "UPDATE batches SET status = ?1,
+                    submitted_at = CASE WHEN ?1 = 'Submitted' THEN ?3 ELSE submitted_at END
+                 WHERE id = ?2",
+                (status.to_string(), batch_id, unix_now()),
             )
             .map_err(|e| {
                 error!("Failed to update batch status for {}: {}", batch_id, e);
@@ -192,6 +524,283 @@ impl DB {
         Ok(())
     }
 
+    /// Marks a pending batch as being submitted.
+    ///
+    /// Returns whether the batch was updated, which it isn't if it got invalidated since it was
+    /// read.
+    pub fn mark_batch_submitting(&self, batch_id: &str) -> Result<bool> {
+        debug!("Marking batch {} as being submitted", batch_id);
+
+        let rows_affected = self
+            .conn
+            .execute(
+                "UPDATE batches SET status = 'Submitting' WHERE id = ?1 AND status = 'Pending'",
+                [batch_id],
+            )
+            .map_err(|e| {
+                error!(
+                    "Failed to mark batch {} as being submitted: {}",
+                    batch_id, e
+                );
+                e
+            })?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Marks a batch being submitted as submitted, recording its alt-DA commitment and the L1
+    /// transaction posting it.
+    ///
+    /// Returns whether the batch was updated, which it isn't if it got invalidated meanwhile.
+    pub fn mark_batch_posted(
+        &self,
+        batch_id: &str,
+        da_commitment: &str,
+        l1_tx_hash: &str,
+    ) -> Result<bool> {
+        debug!(
+            "Marking batch {} posted under {} in L1 transaction {}",
+            batch_id, da_commitment, l1_tx_hash
+        );
+
+        let rows_affected = self
+            .conn
+            .execute(
+                "UPDATE batches SET status = 'Submitted', submitted_at = ?1, da_commitment = ?2,
+                    l1_tx_hash = ?3
+                 WHERE id = ?4 AND status = 'Submitting'",
+                (unix_now(), da_commitment, l1_tx_hash, batch_id),
+            )
+            .map_err(|e| {
+                error!("Failed to mark batch {} posted: {}", batch_id, e);
+                e
+            })?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Marks a pending batch found on the DA layer as submitted under `da_commitment`, so it
+    /// doesn't get put again.
+    pub fn mark_pending_batch_posted(&self, batch_id: &str, da_commitment: &str) -> Result<bool> {
+        debug!(
+            "Marking pending batch {} posted under {}",
+            batch_id, da_commitment
+        );
+
+        let rows_affected = self
+            .conn
+            .execute(
+                "UPDATE batches SET status = 'Submitted', submitted_at = ?1, da_commitment = ?2
+                 WHERE id = ?3 AND status = 'Pending'",
+                (unix_now(), da_commitment, batch_id),
+            )
+            .map_err(|e| {
+                error!("Failed to mark batch {} posted: {}", batch_id, e);
+                e
+            })?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Records the estimated L1 data fee of a submitted batch. Fees of resubmissions add up,
+    /// the L1 fees are the ones of the last submission.
+    pub fn record_estimated_l1_cost(&self, batch_id: &str, cost: &L1Cost) -> Result<()> {
+        debug!(
+            "Recording estimated L1 fee of {} wei for batch {}",
+            cost.estimated_fee, batch_id
+        );
+
+        let recorded = self.wei_column(batch_id, "estimated_l1_fee")?;
+        self.conn
+            .execute(
+                "UPDATE batches SET l1_da_type = ?1, estimated_l1_fee = ?2,
+                    estimated_l1_base_fee = ?3, estimated_l1_blob_base_fee = ?4
+                 WHERE id = ?5",
+                (
+                    cost.da_type.to_string(),
+                    add_wei(recorded, cost.estimated_fee)?.to_string(),
+                    cost.base_fee.to_string(),
+                    cost.blob_base_fee.to_string(),
+                    batch_id,
+                ),
+            )
+            .map_err(|e| {
+                error!("Failed to record L1 fee of batch {}: {}", batch_id, e);
+                e
+            })?;
+
+        Ok(())
+    }
+
+    /// Records the alt-DA server fee of a posted batch. Fees of resubmissions add up.
+    pub fn record_da_fee(&self, batch_id: &str, fee: u128) -> Result<()> {
+        debug!("Recording DA fee of {} wei for batch {}", fee, batch_id);
+
+        let recorded = self.wei_column(batch_id, "alt_da_fee")?;
+        self.conn
+            .execute(
+                "UPDATE batches SET alt_da_fee = ?1 WHERE id = ?2",
+                (add_wei(recorded, fee)?.to_string(), batch_id),
+            )
+            .map_err(|e| {
+                error!("Failed to record DA fee of batch {}: {}", batch_id, e);
+                e
+            })?;
+
+        Ok(())
+    }
+
+    /// Returns the wei amount of `column` recorded for a batch, `None` if there is none.
+    fn wei_column(&self, batch_id: &str, column: &str) -> Result<Option<u128>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {column} FROM batches WHERE id = ?"),
+                [batch_id],
+                |row| parse_wei(row, column),
+            )
+            .optional()
+            .map(Option::flatten)
+            .map_err(|e| {
+                error!("Failed to read {} of batch {}: {}", column, batch_id, e);
+                e
+            })
+    }
+
+    /// Returns the estimated L1 data fees of the batches submitted since `since`, in wei.
+    ///
+    /// Summed here rather than by SQLite, which can't add amounts stored as text without
+    /// losing precision.
+    pub fn l1_fees_since(&self, since: i64) -> Result<u128> {
+        let sum = || -> Result<u128> {
+            let mut stmt = self.conn.prepare(
+                "SELECT estimated_l1_fee FROM batches
+                 WHERE submitted_at >= ? AND estimated_l1_fee IS NOT NULL",
+            )?;
+            let fees = stmt.query_map([since], |row| parse_wei(row, "estimated_l1_fee"))?;
+
+            let mut total = 0u128;
+            for fee in fees {
+                total = add_wei(Some(total), fee?.unwrap_or_default())?;
+            }
+            Ok(total)
+        };
+
+        sum().map_err(|e| {
+            error!("Failed to sum L1 fees since {}: {}", since, e);
+            e
+        })
+    }
+
+    /// Marks a submitted or failed batch as pending again, so it gets resubmitted.
+    ///
+    /// Pruned and invalidated batches can't be requeued. Returns whether the batch was requeued.
+    pub fn requeue_batch(&self, batch_id: &str) -> Result<bool> {
+        debug!("Requeueing batch {}", batch_id);
+
+        let rows_affected = self
+            .conn
+            .execute(
+                "UPDATE batches SET status = 'Pending', submitted_at = NULL,
+                    retry_count = retry_count + 1
+                 WHERE id = ?1 AND pruned_at IS NULL AND status IN ('Submitted', 'Failed')",
+                [batch_id],
+            )
+            .map_err(|e| {
+                error!("Failed to requeue batch {}: {}", batch_id, e);
+                e
+            })?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Marks a batch being submitted as pending again, after its submission failed.
+    ///
+    /// Returns whether the batch was requeued, which it isn't if it got invalidated meanwhile.
+    pub fn requeue_submitting_batch(&self, batch_id: &str) -> Result<bool> {
+        debug!("Requeueing batch {} being submitted", batch_id);
+
+        let rows_affected = self
+            .conn
+            .execute(
+                "UPDATE batches SET status = 'Pending', retry_count = retry_count + 1
+                 WHERE id = ?1 AND status = 'Submitting'",
+                [batch_id],
+            )
+            .map_err(|e| {
+                error!("Failed to requeue batch {}: {}", batch_id, e);
+                e
+            })?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Marks the batches left being submitted as pending again, e.g. after a crash mid-put, so
+    /// they get resubmitted in order. Returns the number of requeued batches.
+    pub fn requeue_submitting(&self) -> Result<usize> {
+        debug!("Requeueing batches left being submitted");
+
+        self.conn
+            .execute(
+                "UPDATE batches SET status = 'Pending', retry_count = retry_count + 1
+                 WHERE status = 'Submitting'",
+                [],
+            )
+            .map_err(|e| {
+                error!("Failed to requeue batches being submitted: {}", e);
+                e
+            })
+    }
+
+    /// Returns aggregate statistics over all batches.
+    pub fn get_stats(&self) -> Result<BatchStats> {
+        let mut stats = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(json_array_length(data)), 0), COUNT(pruned_at),
+                    MIN(start_block), MAX(end_block), MIN(CASE WHEN status = 'Pending' THEN created_at END)
+                 FROM batches",
+                [],
+                |row| {
+                    Ok(BatchStats {
+                        total: row.get(0)?,
+                        stored_bytes: row.get(1)?,
+                        pruned: row.get(2)?,
+                        first_block: row.get(3)?,
+                        last_block: row.get(4)?,
+                        oldest_pending_at: row.get(5)?,
+                        by_status: Default::default(),
+                    })
+                },
+            )
+            .map_err(|e| {
+                error!("Failed to compute batch stats: {}", e);
+                e
+            })?;
+
+        for status in BatchStatus::ALL {
+            stats
+                .by_status
+                .insert(status.to_string(), self.get_batch_count_by_status(status)?);
+        }
+
+        Ok(stats)
+    }
+
+    /// Returns the payload size of the batches not submitted yet, in bytes.
+    pub fn unsubmitted_bytes(&self) -> Result<u64> {
+        self.conn
+            .query_row(
+                "SELECT COALESCE(SUM(json_array_length(data)), 0) FROM batches
+                 WHERE status IN ('Pending', 'Submitting') AND pruned_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                error!("Failed to compute unsubmitted bytes: {}", e);
+                e
+            })
+    }
+
     pub fn get_batch_count_by_status(&self, status: BatchStatus) -> Result<u32> {
         let count: u32 = self
             .conn
@@ -208,4 +817,515 @@ impl DB {
         debug!("Found {} batches with status {}", count, status);
         Ok(count)
     }
+
+    /// Marks every batch containing `block_number` or a later block as invalidated.
+    ///
+    /// Returns the invalidated batches.
+    pub fn invalidate_batches_from(&self, block_number: u64) -> Result<Vec<BatchInfo>> {
+        debug!("Invalidating batches from block {}", block_number);
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "UPDATE batches SET status = 'Invalidated'
+                 WHERE end_block >= ?1 AND status != 'Invalidated'
+                 RETURNING *",
+            )
+            .map_err(|e| {
+                error!("Failed to prepare invalidate batches query: {}", e);
+                e
+            })?;
+
+        let batches = stmt
+            .query_map([block_number], batch_from_row)
+            .map_err(|e| {
+                error!(
+                    "Failed to invalidate batches from block {}: {}",
+                    block_number, e
+                );
+                e
+            })?;
+
+        batches.collect()
+    }
+
+    /// Returns the highest block number contained in any batch.
+    pub fn latest_batched_block(&self) -> Result<Option<u64>> {
+        self.conn
+            .query_row(
+                "SELECT MAX(end_block) FROM batches WHERE status != 'Invalidated'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                error!("Failed to query latest batched block: {}", e);
+                e
+            })
+    }
+
+    /// Drops the payload of up to `limit` submitted batches that were submitted before `cutoff`
+    /// (unix seconds) and end at or below `finalized_block`.
+    ///
+    /// Ids, block ranges and DA references are kept. Returns the number of pruned batches.
+    pub fn prune_submitted_before(
+        &self,
+        cutoff: i64,
+        finalized_block: u64,
+        limit: usize,
+    ) -> Result<usize> {
+        debug!(
+            "Pruning payloads of batches submitted before {}, up to block {}",
+            cutoff, finalized_block
+        );
+
+        self.conn
+            .execute(
+                "UPDATE batches SET data = '[]', pruned_at = ?3
+                 WHERE id IN (
+                    SELECT id FROM batches
+                    WHERE status = 'Submitted' AND pruned_at IS NULL
+                      AND COALESCE(submitted_at, created_at) < ?1 AND end_block <= ?2
+                    LIMIT ?4
+                 )",
+                (cutoff, finalized_block, unix_now(), limit as i64),
+            )
+            .map_err(|e| {
+                error!("Failed to prune batches submitted before {}: {}", cutoff, e);
+                e
+            })
+    }
+
+    /// Drops the payload of up to `limit` submitted batches whose last block is at or below
+    /// `block_number`.
+    ///
+    /// Ids, block ranges and DA references are kept. Returns the number of pruned batches.
+    pub fn prune_submitted_up_to_block(&self, block_number: u64, limit: usize) -> Result<usize> {
+        debug!("Pruning payloads of batches up to block {}", block_number);
+
+        self.conn
+            .execute(
+                "UPDATE batches SET data = '[]', pruned_at = ?2
+                 WHERE id IN (
+                    SELECT id FROM batches
+                    WHERE status = 'Submitted' AND pruned_at IS NULL AND end_block <= ?1
+                    LIMIT ?3
+                 )",
+                (block_number, unix_now(), limit as i64),
+            )
+            .map_err(|e| {
+                error!(
+                    "Failed to prune batches up to block {}: {}",
+                    block_number, e
+                );
+                e
+            })
+    }
+
+    /// Whether pages freed by pruning can be released with [`DB::incremental_vacuum`].
+    pub fn incremental_auto_vacuum(&self) -> Result<bool> {
+        let auto_vacuum: i64 = self
+            .conn
+            .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+        Ok(auto_vacuum == INCREMENTAL_AUTO_VACUUM)
+    }
+
+    /// Rewrites the whole database with a `VACUUM`, enabling incremental vacuum and releasing
+    /// every free page. Takes as long as copying the database, during which it can't be written.
+    pub fn vacuum(&self) -> Result<()> {
+        info!("Rewriting the database, enabling incremental vacuum");
+        let started = std::time::Instant::now();
+
+        self.conn
+            .execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM")
+            .map_err(|e| {
+                error!("Failed to vacuum database: {}", e);
+                e
+            })?;
+
+        info!("Rewrote the database in {:?}", started.elapsed());
+        Ok(())
+    }
+
+    /// Returns up to `pages` free pages to the file system, with SQLite's incremental vacuum.
+    ///
+    /// Returns the number of pages freed, zero once no page is free.
+    pub fn incremental_vacuum(&self, pages: u32) -> Result<u32> {
+        let free_pages = || -> Result<u32> {
+            self.conn
+                .query_row("PRAGMA freelist_count", [], |row| row.get(0))
+        };
+
+        let before = free_pages()?;
+        if before == 0 {
+            return Ok(0);
+        }
+
+        self.conn
+            .execute_batch(&format!("PRAGMA incremental_vacuum({pages})"))
+            .map_err(|e| {
+                error!("Failed to vacuum database: {}", e);
+                e
+            })?;
+
+        Ok(before.saturating_sub(free_pages()?))
+    }
+}
+
+fn batch_from_row(row: &Row<'_>) -> Result<BatchInfo> {
+    let block_numbers_str: String = row.get("block_numbers")?;
+    let status_str: String = row.get("status")?;
+    let data_str: String = row.get("data")?;
+
+    let block_numbers = match serde_json::from_str(&block_numbers_str) {
+        Ok(nums) => nums,
+        Err(e) => {
+            error!(
+                "Failed to deserialize block numbers for batch {}: {}",
+                row.get::<_, String>("id").unwrap_or_default(),
+                e
+            );
+            Vec::new()
+        }
+    };
+
+    let data = match serde_json::from_str(&data_str) {
+        Ok(d) => d,
+        Err(e) => {
+            error!(
+                "Failed to deserialize batch data for batch {}: {}",
+                row.get::<_, String>("id").unwrap_or_default(),
+                e
+            );
+            Vec::new()
+        }
+    };
+
+    let status = status_str.parse().unwrap_or_else(|_| {
+        warn!(
+            "Unknown batch status '{}', defaulting to Pending",
+            status_str
+        );
+        BatchStatus::Pending
+    });
+
+    let codec_str: String = row.get("codec")?;
+    let codec = codec_str.parse().unwrap_or_else(|_| {
+        warn!("Unknown batch codec '{}', defaulting to json", codec_str);
+        Codec::Json
+    });
+
+    let l1_da_type: Option<String> = row.get("l1_da_type")?;
+    let estimated_l1_cost = match (l1_da_type, parse_wei(row, "estimated_l1_fee")?) {
+        (Some(da_type), Some(fee)) => Some(L1Cost {
+            da_type: da_type.parse().unwrap_or_else(|_| {
+                warn!(
+                    "Unknown batch DA type '{}', defaulting to calldata",
+                    da_type
+                );
+                DaType::Calldata
+            }),
+            estimated_fee: fee,
+            base_fee: parse_wei(row, "estimated_l1_base_fee")?.unwrap_or_default(),
+            blob_base_fee: parse_wei(row, "estimated_l1_blob_base_fee")?.unwrap_or_default(),
+        }),
+        _ => None,
+    };
+
+    Ok(BatchInfo {
+        id: row.get("id")?,
+        block_numbers,
+        data,
+        created_at: row.get("created_at")?,
+        submitted_at: row.get("submitted_at")?,
+        celestia_height: row.get("celestia_height")?,
+        retry_count: row.get("retry_count")?,
+        status,
+        pruned_at: row.get("pruned_at")?,
+        first_block_timestamp: row.get("first_block_timestamp")?,
+        da_commitment: row.get("da_commitment")?,
+        l1_tx_hash: row.get("l1_tx_hash")?,
+        codec,
+        estimated_l1_cost,
+        da_fee: parse_wei(row, "alt_da_fee")?,
+        compressed_size: row.get("compressed_size")?,
+    })
+}
+
+/// Parses the wei amount stored as decimal text in `column` of `row`.
+fn parse_wei(row: &Row<'_>, column: &str) -> Result<Option<u128>> {
+    let index = row.as_ref().column_index(column)?;
+    let Some(wei) = row.get::<_, Option<String>>(index)? else {
+        return Ok(None);
+    };
+
+    wei.parse().map(Some).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// Adds `fee` to the `recorded` wei amount, failing rather than wrapping on overflow.
+fn add_wei(recorded: Option<u128>, fee: u128) -> Result<u128> {
+    recorded
+        .unwrap_or_default()
+        .checked_add(fee)
+        .ok_or_else(|| {
+            rusqlite::Error::ToSqlConversionFailure(
+                format!("{fee} wei overflows the recorded fee").into(),
+            )
+        })
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_columns_of_outdated_database() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        assert_eq!(db.missing_columns()?.len(), 21);
+
+        // The schema before pruning was supported
+        db.conn().execute_batch(
+            "CREATE TABLE batches (
+                id TEXT PRIMARY KEY,
+                block_numbers TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                submitted_at INTEGER,
+                celestia_height INTEGER,
+                retry_count INTEGER DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'Pending'
+            )",
+        )?;
+        assert_eq!(
+            db.missing_columns()?,
+            MIGRATED_COLUMNS.map(|(column, _)| column)
+        );
+
+        db.initialize_database()?;
+        assert!(db.missing_columns()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn fees_above_i64_are_recorded() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        db.initialize_database()?;
+        db.conn().execute(
+            "INSERT INTO batches (id, block_numbers, data, created_at, status, submitted_at)
+             VALUES ('batch', '[1]', '[]', 0, 'Submitted', 100)",
+            [],
+        )?;
+
+        let cost = L1Cost {
+            da_type: DaType::Blobs,
+            estimated_fee: u128::from(u64::MAX),
+            base_fee: 1,
+            blob_base_fee: u128::from(u64::MAX),
+        };
+        db.record_estimated_l1_cost("batch", &cost)?;
+        db.record_estimated_l1_cost("batch", &cost)?;
+        db.record_da_fee("batch", u128::from(u64::MAX) + 1)?;
+
+        let batch = db.get_batch("batch")?.unwrap();
+        let recorded = batch.estimated_l1_cost.unwrap();
+        assert_eq!(recorded.estimated_fee, 2 * u128::from(u64::MAX));
+        assert_eq!(recorded.blob_base_fee, u128::from(u64::MAX));
+        assert_eq!(batch.da_fee, Some(u128::from(u64::MAX) + 1));
+        assert_eq!(db.l1_fees_since(100)?, 2 * u128::from(u64::MAX));
+        assert_eq!(db.l1_fees_since(101)?, 0);
+
+        let overflow = L1Cost {
+            estimated_fee: u128::MAX,
+            ..cost
+        };
+        assert!(db.record_estimated_l1_cost("batch", &overflow).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_integer_fees_are_moved() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        db.conn().execute_batch(
+            "CREATE TABLE batches (
+                id TEXT PRIMARY KEY,
+                block_numbers TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                submitted_at INTEGER,
+                celestia_height INTEGER,
+                retry_count INTEGER DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'Pending',
+                start_block INTEGER,
+                end_block INTEGER,
+                pruned_at INTEGER,
+                first_block_timestamp INTEGER,
+                da_commitment TEXT,
+                codec TEXT NOT NULL DEFAULT 'json',
+                l1_da_type TEXT,
+                l1_fee INTEGER,
+                l1_base_fee INTEGER,
+                l1_blob_base_fee INTEGER,
+                da_fee INTEGER,
+                compressed_size INTEGER
+            );
+            INSERT INTO batches (id, block_numbers, data, created_at, status, submitted_at,
+                l1_da_type, l1_fee, l1_base_fee, l1_blob_base_fee, da_fee, compressed_size)
+            VALUES ('batch', '[1]', '[]', 0, 'Submitted', 100, 'calldata', 42, 7, 1, 5, 3)",
+        )?;
+
+        db.initialize_database()?;
+        assert!(db.missing_columns()?.is_empty());
+        for (legacy, _) in LEGACY_FEE_COLUMNS {
+            assert!(!db.has_column("batches", legacy)?);
+        }
+
+        let batch = db.get_batch("batch")?.unwrap();
+        assert_eq!(
+            batch.estimated_l1_cost,
+            Some(L1Cost {
+                da_type: DaType::Calldata,
+                estimated_fee: 42,
+                base_fee: 7,
+                blob_base_fee: 1,
+            })
+        );
+        assert_eq!(batch.da_fee, Some(5));
+        assert_eq!(batch.compressed_size, Some(3));
+        assert_eq!(db.l1_fees_since(0)?, 42);
+        Ok(())
+    }
+
+    #[test]
+    fn only_submitted_and_failed_batches_are_requeued() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        db.initialize_database()?;
+        for status in [
+            "Pending",
+            "Submitting",
+            "Submitted",
+            "Failed",
+            "Invalidated",
+        ] {
+            db.conn().execute(
+                "INSERT INTO batches (id, block_numbers, data, created_at, status)
+                 VALUES (?1, '[1]', '[]', 0, ?1)",
+                [status],
+            )?;
+        }
+
+        assert!(!db.requeue_batch("Pending")?);
+        assert!(!db.requeue_batch("Submitting")?);
+        assert!(db.requeue_batch("Submitted")?);
+        assert!(db.requeue_batch("Failed")?);
+        assert!(!db.requeue_batch("Invalidated")?);
+
+        // Only the submitter requeues the batches it's submitting
+        assert!(!db.requeue_submitting_batch("Invalidated")?);
+        assert!(db.requeue_submitting_batch("Submitting")?);
+        let batch = db.get_batch("Submitting")?.unwrap();
+        assert_eq!(batch.status, BatchStatus::Pending);
+        assert_eq!(batch.retry_count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn stats_count_payload_bytes() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        db.initialize_database()?;
+        db.conn().execute(
+            "INSERT INTO batches (id, block_numbers, data, created_at, status)
+             VALUES ('batch', '[1]', '[1,22,255]', 0, 'Pending')",
+            [],
+        )?;
+
+        assert_eq!(db.get_stats()?.stored_bytes, 3);
+        assert_eq!(db.unsubmitted_bytes()?, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn columns_are_read_by_name() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        db.conn().execute_batch(
+            "CREATE TABLE batches (
+                id TEXT PRIMARY KEY,
+                block_numbers TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                submitted_at INTEGER,
+                celestia_height INTEGER,
+                retry_count INTEGER DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'Pending'
+            )",
+        )?;
+        // Migrated in another order than the current one
+        for (column, definition) in MIGRATED_COLUMNS.iter().rev() {
+            db.conn().execute(
+                &format!("ALTER TABLE batches ADD COLUMN {column} {definition}"),
+                [],
+            )?;
+        }
+        db.initialize_database()?;
+        db.conn().execute(
+            "INSERT INTO batches (id, block_numbers, data, created_at, status, codec,
+                compressed_size, l1_tx_hash, l1_da_type, estimated_l1_fee, estimated_l1_base_fee,
+                estimated_l1_blob_base_fee, alt_da_fee)
+             VALUES ('batch', '[1]', '[1]', 0, 'Submitted', 'block-rlp', 9, '0xaa', 'blobs', '1',
+                '2', '3', '4')",
+            [],
+        )?;
+
+        let batch = db.get_batch("batch")?.unwrap();
+        assert_eq!(batch.codec, Codec::BlockRlp);
+        assert_eq!(batch.compressed_size, Some(9));
+        assert_eq!(batch.l1_tx_hash.as_deref(), Some("0xaa"));
+        assert_eq!(
+            batch.estimated_l1_cost,
+            Some(L1Cost {
+                da_type: DaType::Blobs,
+                estimated_fee: 1,
+                base_fee: 2,
+                blob_base_fee: 3,
+            })
+        );
+        assert_eq!(batch.da_fee, Some(4));
+        Ok(())
+    }
+
+    #[test]
+    fn vacuum_enables_incremental_vacuum() -> Result<()> {
+        let db = DB::new(":memory:")?;
+        db.conn()
+            .execute_batch("CREATE TABLE legacy (id INTEGER)")?;
+
+        // Too late for the pragma alone, the database has a table
+        db.initialize_database()?;
+        assert!(!db.incremental_auto_vacuum()?);
+
+        db.vacuum()?;
+        assert!(db.incremental_auto_vacuum()?);
+        Ok(())
+    }
+
+    #[test]
+    fn lock_is_exclusive() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(format!("batcher-lock-{}.db", std::process::id()));
+        let path = path.to_string_lossy();
+
+        let lock = DbLock::acquire(&path)?;
+        let err = DbLock::acquire(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+
+        drop(lock);
+        DbLock::acquire(&path)?;
+        std::fs::remove_file(format!("{path}.lock"))
+    }
 }
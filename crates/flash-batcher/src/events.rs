@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::trace;
+
+/// Number of events buffered for each subscriber before it starts lagging.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Lifecycle events of channels, streamed by `batcher_subscribe("channelEvents")`.
+///
+/// Channels are currently submitted as a single frame, so frame events always carry
+/// `frameNumber: 0`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ChannelEvent {
+    /// A block was added to an empty queue, opening a new channel.
+    ChannelOpened {
+        channel_id: String,
+        first_block: u64,
+    },
+    /// The channel was closed and persisted as a pending batch.
+    ChannelClosed {
+        channel_id: String,
+        block_numbers: Vec<u64>,
+        size: usize,
+    },
+    /// A frame of the channel was handed to the DA layer.
+    FrameSubmitted {
+        channel_id: String,
+        frame_number: u16,
+    },
+    /// A frame of the channel was included on L1. Only the alt-DA submitter emits it, once the
+    /// commitment of the channel is included in L1 block `da_height`.
+    FrameIncluded {
+        channel_id: String,
+        frame_number: u16,
+        da_height: Option<u64>,
+    },
+    /// Submitting a frame of the channel failed.
+    FrameFailed {
+        channel_id: String,
+        frame_number: u16,
+        error: String,
+    },
+    /// A reorg invalidated the blocks from `first_invalid_block` onwards.
+    Reorged {
+        first_invalid_block: u64,
+        invalidated_channels: Vec<String>,
+        dropped_blocks: usize,
+    },
+    /// The op-node safe head fell too far behind its unsafe head.
+    DerivationBehind { safe_head: u64, unsafe_head: u64 },
+}
+
+/// Broadcasts [`ChannelEvent`]s to any number of subscribers.
+#[derive(Debug, Clone)]
+pub struct ChannelEvents {
+    sender: broadcast::Sender<ChannelEvent>,
+}
+
+impl ChannelEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ChannelEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Sends the event to current subscribers. Events are dropped if nobody is subscribed.
+    pub fn emit(&self, event: ChannelEvent) {
+        trace!("Emitting channel event {:?}", event);
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for ChannelEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,285 @@
+//! L1 execution client, reading the rollup contracts and posting to the batch inbox.
+
+use crate::signer::KeyRing;
+use alloy_primitives::{Address, B256, Bytes, U64, U128};
+use alloy_rpc_types_eth::{
+    BlockNumberOrTag, Filter, Header, Log, TransactionReceipt, TransactionRequest,
+};
+use jsonrpsee::{
+    core::client::Error as ClientError,
+    http_client::{HttpClient, HttpClientBuilder},
+    proc_macros::rpc,
+    types::ErrorObject,
+};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Interval between polls of the receipt of a batcher transaction.
+const RECEIPT_INTERVAL: Duration = Duration::from_secs(2);
+/// Time a batcher transaction is waited on before it's considered failed.
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(300);
+/// Error code of geth for reverted calls carrying revert data.
+const EXECUTION_REVERTED: i32 = 3;
+
+/// The `eth_` namespace of an L1 execution client.
+#[rpc(server, client, namespace = "eth")]
+pub trait L1Api {
+    #[method(name = "chainId")]
+    async fn chain_id(&self) -> jsonrpsee::core::RpcResult<U64>;
+
+    #[method(name = "blockNumber")]
+    async fn block_number(&self) -> jsonrpsee::core::RpcResult<U64>;
+
+    /// Only the header fields of the block are read.
+    #[method(name = "getBlockByNumber")]
+    async fn block_by_number(
+        &self,
+        block: BlockNumberOrTag,
+        full: bool,
+    ) -> jsonrpsee::core::RpcResult<Option<Header>>;
+
+    #[method(name = "blobBaseFee")]
+    async fn blob_base_fee(&self) -> jsonrpsee::core::RpcResult<U128>;
+
+    #[method(name = "call")]
+    async fn call(
+        &self,
+        request: TransactionRequest,
+        block: U64,
+    ) -> jsonrpsee::core::RpcResult<Bytes>;
+
+    #[method(name = "getLogs")]
+    async fn get_logs(&self, filter: Filter) -> jsonrpsee::core::RpcResult<Vec<Log>>;
+
+    #[method(name = "getTransactionCount")]
+    async fn transaction_count(
+        &self,
+        address: Address,
+        block: BlockNumberOrTag,
+    ) -> jsonrpsee::core::RpcResult<U64>;
+
+    #[method(name = "maxPriorityFeePerGas")]
+    async fn max_priority_fee_per_gas(&self) -> jsonrpsee::core::RpcResult<U128>;
+
+    #[method(name = "estimateGas")]
+    async fn estimate_gas(&self, request: TransactionRequest) -> jsonrpsee::core::RpcResult<U64>;
+
+    #[method(name = "sendRawTransaction")]
+    async fn send_raw_transaction(&self, raw: Bytes) -> jsonrpsee::core::RpcResult<B256>;
+
+    #[method(name = "getTransactionReceipt")]
+    async fn transaction_receipt(
+        &self,
+        hash: B256,
+    ) -> jsonrpsee::core::RpcResult<Option<TransactionReceipt>>;
+}
+
+/// HTTP client of an L1 RPC.
+#[derive(Debug, Clone)]
+pub struct L1Client {
+    url: String,
+    client: HttpClient,
+}
+
+impl L1Client {
+    pub fn new(url: &str) -> eyre::Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .map_err(|e| eyre::eyre!("Failed to create L1 client for {}: {}", url, e))?;
+
+        Ok(Self {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    pub async fn chain_id(&self) -> eyre::Result<u64> {
+        L1ApiClient::chain_id(&self.client)
+            .await
+            .map(|id| id.to())
+            .map_err(|e| eyre::eyre!("Failed to fetch L1 chain id from {}: {}", self.url, e))
+    }
+
+    pub async fn block_number(&self) -> eyre::Result<u64> {
+        L1ApiClient::block_number(&self.client)
+            .await
+            .map(|number| number.to())
+            .map_err(|e| eyre::eyre!("Failed to fetch L1 block number from {}: {}", self.url, e))
+    }
+
+    pub async fn latest_header(&self) -> eyre::Result<Header> {
+        L1ApiClient::block_by_number(&self.client, BlockNumberOrTag::Latest, false)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to fetch latest L1 block from {}: {}", self.url, e))?
+            .ok_or_else(|| eyre::eyre!("{} returned no latest L1 block", self.url))
+    }
+
+    pub async fn blob_base_fee(&self) -> eyre::Result<u128> {
+        L1ApiClient::blob_base_fee(&self.client)
+            .await
+            .map(|fee| fee.to())
+            .map_err(|e| eyre::eyre!("Failed to fetch blob base fee from {}: {}", self.url, e))
+    }
+
+    /// Calls `to` with `input` on L1 block `block`.
+    pub async fn call(&self, to: Address, input: Bytes, block: u64) -> eyre::Result<Bytes> {
+        self.try_call(to, input, block)
+            .await?
+            .ok_or_else(|| eyre::eyre!("Call to {} on {} reverted", to, self.url))
+    }
+
+    /// Like [`Self::call`], but returns `None` when the call reverts.
+    pub async fn try_call(
+        &self,
+        to: Address,
+        input: Bytes,
+        block: u64,
+    ) -> eyre::Result<Option<Bytes>> {
+        let request = TransactionRequest::default().to(to).input(input.into());
+
+        match L1ApiClient::call(&self.client, request, U64::from(block)).await {
+            Ok(output) => Ok(Some(output)),
+            Err(ClientError::Call(e)) if is_revert(&e) => Ok(None),
+            Err(e) => Err(eyre::eyre!("Failed to call {} on {}: {}", to, self.url, e)),
+        }
+    }
+
+    pub async fn logs(&self, filter: Filter) -> eyre::Result<Vec<Log>> {
+        L1ApiClient::get_logs(&self.client, filter)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to fetch logs from {}: {}", self.url, e))
+    }
+
+    /// Returns the nonce of the next transaction of `address`, counting the pending ones.
+    pub async fn pending_nonce(&self, address: Address) -> eyre::Result<u64> {
+        L1ApiClient::transaction_count(&self.client, address, BlockNumberOrTag::Pending)
+            .await
+            .map(|nonce| nonce.to())
+            .map_err(|e| {
+                eyre::eyre!(
+                    "Failed to fetch nonce of {} from {}: {}",
+                    address,
+                    self.url,
+                    e
+                )
+            })
+    }
+
+    pub async fn max_priority_fee_per_gas(&self) -> eyre::Result<u128> {
+        L1ApiClient::max_priority_fee_per_gas(&self.client)
+            .await
+            .map(|fee| fee.to())
+            .map_err(|e| eyre::eyre!("Failed to fetch priority fee from {}: {}", self.url, e))
+    }
+
+    pub async fn estimate_gas(&self, request: TransactionRequest) -> eyre::Result<u64> {
+        L1ApiClient::estimate_gas(&self.client, request)
+            .await
+            .map(|gas| gas.to())
+            .map_err(|e| eyre::eyre!("Failed to estimate gas on {}: {}", self.url, e))
+    }
+
+    /// Sends an EIP-2718 encoded transaction, returning its hash.
+    pub async fn send_raw_transaction(&self, raw: Bytes) -> eyre::Result<B256> {
+        L1ApiClient::send_raw_transaction(&self.client, raw)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to send transaction to {}: {}", self.url, e))
+    }
+
+    pub async fn transaction_receipt(
+        &self,
+        hash: B256,
+    ) -> eyre::Result<Option<TransactionReceipt>> {
+        L1ApiClient::transaction_receipt(&self.client, hash)
+            .await
+            .map_err(|e| {
+                eyre::eyre!(
+                    "Failed to fetch receipt of {} from {}: {}",
+                    hash,
+                    self.url,
+                    e
+                )
+            })
+    }
+}
+
+/// Posts data to the batch inbox, in transactions signed with the authorized batcher key.
+#[derive(Debug, Clone)]
+pub struct BatchInbox {
+    l1: L1Client,
+    address: Address,
+    receipt_interval: Duration,
+    receipt_timeout: Duration,
+}
+
+impl BatchInbox {
+    pub fn new(l1: L1Client, address: Address) -> Self {
+        Self {
+            l1,
+            address,
+            receipt_interval: RECEIPT_INTERVAL,
+            receipt_timeout: RECEIPT_TIMEOUT,
+        }
+    }
+
+    pub fn with_receipt_interval(mut self, interval: Duration) -> Self {
+        self.receipt_interval = interval;
+        self
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Sends `data` to the batch inbox and waits for the transaction to be included, failing if
+    /// it reverts or isn't included within the receipt timeout.
+    pub async fn post(&self, keys: &KeyRing, data: Bytes) -> eyre::Result<TransactionReceipt> {
+        let from = keys.authorized();
+        let nonce = self.l1.pending_nonce(from).await?;
+        let chain_id = self.l1.chain_id().await?;
+        let header = self.l1.latest_header().await?;
+        let base_fee = header
+            .base_fee_per_gas
+            .ok_or_else(|| eyre::eyre!("L1 block {} has no base fee", header.number))?;
+        let priority_fee = self.l1.max_priority_fee_per_gas().await?;
+
+        let request = TransactionRequest::default()
+            .from(from)
+            .to(self.address)
+            .input(data.into())
+            .nonce(nonce)
+            .max_priority_fee_per_gas(priority_fee)
+            // Stays includable while the base fee doubles
+            .max_fee_per_gas(u128::from(base_fee) * 2 + priority_fee);
+        let gas = self.l1.estimate_gas(request.clone()).await?;
+        let mut request = request.gas_limit(gas);
+        request.chain_id = Some(chain_id);
+
+        let raw = keys.sign_transaction(request).await?;
+        let hash = self.l1.send_raw_transaction(raw).await?;
+        debug!("Sent batcher transaction {} with nonce {}", hash, nonce);
+
+        let started_at = Instant::now();
+        loop {
+            if let Some(receipt) = self.l1.transaction_receipt(hash).await? {
+                if !receipt.status() {
+                    return Err(eyre::eyre!("Batcher transaction {} reverted", hash));
+                }
+                return Ok(receipt);
+            }
+            if started_at.elapsed() >= self.receipt_timeout {
+                return Err(eyre::eyre!(
+                    "Batcher transaction {} not included after {}s",
+                    hash,
+                    self.receipt_timeout.as_secs()
+                ));
+            }
+            tokio::time::sleep(self.receipt_interval).await;
+        }
+    }
+}
+
+/// Whether `error` reports a reverted call, rather than a failure of the client.
+fn is_revert(error: &ErrorObject<'_>) -> bool {
+    error.code() == EXECUTION_REVERTED || error.message().contains("execution reverted")
+}
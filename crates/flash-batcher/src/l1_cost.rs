@@ -0,0 +1,463 @@
+//! L1 data fee of channels, and the budget their submission is held to.
+//!
+//! Fees are estimated from the latest L1 base fee and blob base fee, for the channel data
+//! compressed and split into frames as op-batcher does, without priority fees. With a budget,
+//! channels are held while L1 is too expensive for it, up to a maximum age so the sequencing
+//! window isn't exceeded.
+
+use crate::{
+    channel_builder::ChannelBuilder,
+    db::{BatchInfo, DB},
+    l1::L1Client,
+};
+use clap::ValueEnum;
+use flate2::{Compression, write::ZlibEncoder};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::Write,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, warn};
+
+/// Period `max_fee_per_hour` budgets apply to.
+pub const BUDGET_PERIOD: Duration = Duration::from_secs(3600);
+
+/// Intrinsic gas of a transaction.
+const TX_GAS: u64 = 21_000;
+/// Calldata gas per byte, the EIP-7623 floor of non-zero bytes. Channel data is mostly
+/// non-zero, and batcher transactions always pay the floor.
+const CALLDATA_GAS_PER_BYTE: u64 = 40;
+/// Maximum size of a calldata batcher transaction, op-batcher's default.
+const MAX_CALLDATA_TX_SIZE: u64 = 120_000;
+/// Blob gas of a blob.
+const BLOB_GAS_PER_BLOB: u64 = 131_072;
+/// Bytes a blob holds with the op-stack blob encoding.
+const BLOB_CAPACITY: u64 = 130_044;
+/// Maximum number of blobs of a batcher transaction.
+const MAX_BLOBS_PER_TX: u64 = 6;
+/// Bytes added to the data of each frame: the derivation version byte, the channel id, the frame
+/// number, the frame data length and the last frame flag.
+const FRAME_OVERHEAD: u64 = 1 + 16 + 2 + 4 + 1;
+
+/// How channels are posted to L1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DaType {
+    /// Frames in transaction calldata.
+    #[default]
+    Calldata,
+    /// Frames in blobs.
+    Blobs,
+    /// Calldata or blobs, whichever is cheaper for each channel.
+    Auto,
+}
+
+impl DaType {
+    pub const ALL: [DaType; 3] = [DaType::Calldata, DaType::Blobs, DaType::Auto];
+}
+
+impl fmt::Display for DaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DaType::Calldata => "calldata",
+            DaType::Blobs => "blobs",
+            DaType::Auto => "auto",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for DaType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DaType::ALL
+            .into_iter()
+            .find(|da_type| da_type.to_string() == s)
+            .ok_or_else(|| format!("unknown DA type '{s}'"))
+    }
+}
+
+/// Size of `data` once zlib compressed, as op-batcher compresses channels before framing them.
+pub fn compressed_size(data: &[u8]) -> u64 {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    match encoder.write_all(data).and_then(|()| encoder.finish()) {
+        Ok(compressed) => compressed.len() as u64,
+        // Writing to a `Vec` doesn't fail
+        Err(_) => data.len() as u64,
+    }
+}
+
+/// Size of the data a channel is posted as, compressed if its compressed size is recorded.
+pub fn posted_size(channel: &BatchInfo) -> u64 {
+    channel
+        .compressed_size
+        .unwrap_or_else(|| compressed_size(&channel.data))
+}
+
+/// Latest L1 fees, in wei.
+///
+/// Wei amounts are `u128`: the blob base fee has no upper bound, and fees of large channels
+/// overflow `u64` well before the amounts become unrealistic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L1Fees {
+    /// L1 block the fees are read from.
+    pub l1_block: u64,
+    pub base_fee: u128,
+    pub blob_base_fee: u128,
+}
+
+impl L1Fees {
+    /// Fee of posting `size` bytes of channel data in calldata transactions.
+    pub fn calldata_fee(&self, size: u64) -> u128 {
+        let txs = u128::from(size.div_ceil(MAX_CALLDATA_TX_SIZE - FRAME_OVERHEAD).max(1));
+        let gas = txs * u128::from(TX_GAS)
+            + (u128::from(size) + txs * u128::from(FRAME_OVERHEAD))
+                * u128::from(CALLDATA_GAS_PER_BYTE);
+        gas.saturating_mul(self.base_fee)
+    }
+
+    /// Fee of posting `size` bytes of channel data in blobs, a frame per blob.
+    pub fn blob_fee(&self, size: u64) -> u128 {
+        let blobs = size.div_ceil(BLOB_CAPACITY - FRAME_OVERHEAD).max(1);
+        let txs = blobs.div_ceil(MAX_BLOBS_PER_TX);
+        u128::from(txs * TX_GAS)
+            .saturating_mul(self.base_fee)
+            .saturating_add(
+                u128::from(blobs * BLOB_GAS_PER_BLOB).saturating_mul(self.blob_base_fee),
+            )
+    }
+
+    /// Estimates the fee of posting `size` bytes of channel data with `da_type`.
+    pub fn estimate(&self, size: u64, da_type: DaType) -> L1Cost {
+        let (da_type, fee) = match da_type {
+            DaType::Calldata => (DaType::Calldata, self.calldata_fee(size)),
+            DaType::Blobs => (DaType::Blobs, self.blob_fee(size)),
+            DaType::Auto => {
+                let (calldata, blobs) = (self.calldata_fee(size), self.blob_fee(size));
+                if calldata < blobs {
+                    (DaType::Calldata, calldata)
+                } else {
+                    (DaType::Blobs, blobs)
+                }
+            }
+        };
+
+        L1Cost {
+            da_type,
+            estimated_fee: fee,
+            base_fee: self.base_fee,
+            blob_base_fee: self.blob_base_fee,
+        }
+    }
+}
+
+/// Estimated L1 data fee of a channel, and the fees it's estimated from.
+///
+/// Only an estimate, from the fees of the latest L1 block, without priority fees: the batcher
+/// doesn't record what its L1 transactions actually paid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L1Cost {
+    /// How the channel is posted, never [`DaType::Auto`].
+    pub da_type: DaType,
+    /// Estimated fee of the channel, in wei.
+    pub estimated_fee: u128,
+    pub base_fee: u128,
+    pub blob_base_fee: u128,
+}
+
+/// Limits on the L1 fees spent posting channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L1Budget {
+    /// Maximum fees of the channels submitted over the last hour, in wei.
+    pub max_fee_per_hour: Option<u128>,
+    /// Maximum fee per byte of channel data, in wei.
+    pub max_fee_per_byte: Option<u128>,
+    /// Age after which a channel is submitted whatever its fee.
+    pub max_hold: Duration,
+}
+
+impl L1Budget {
+    /// Returns why a channel of `size` bytes costing `cost` is over budget, given the fees of
+    /// the channels submitted over the last hour.
+    pub fn exceeded_by(&self, cost: &L1Cost, size: u64, spent_last_hour: u128) -> Option<String> {
+        if let Some(max) = self.max_fee_per_byte {
+            let fee_per_byte = cost.estimated_fee / u128::from(size.max(1));
+            if fee_per_byte > max {
+                return Some(format!(
+                    "{} wei per byte exceeds {} wei per byte",
+                    fee_per_byte, max
+                ));
+            }
+        }
+
+        if let Some(max) = self.max_fee_per_hour {
+            let spent = spent_last_hour.saturating_add(cost.estimated_fee);
+            if spent > max {
+                return Some(format!(
+                    "{} wei over the last hour exceeds {} wei per hour",
+                    spent, max
+                ));
+            }
+        }
+
+        None
+    }
+
+    /// Returns why `channel` is held, if posting `size` bytes of it at the estimated `cost` is
+    /// over budget. Channels held for the max hold are submitted whatever their cost.
+    ///
+    /// Checked by every submitter before submitting a channel, on each of its rounds.
+    pub fn hold_reason(
+        &self,
+        db: &DB,
+        channel: &BatchInfo,
+        cost: Option<&L1Cost>,
+        size: u64,
+    ) -> eyre::Result<Option<String>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let exceeded_by = match cost {
+            Some(cost) => {
+                let spent = db
+                    .l1_fees_since(now.saturating_sub(BUDGET_PERIOD.as_secs()) as i64)
+                    .map_err(|e| eyre::eyre!("Failed to sum L1 fees: {}", e))?;
+                self.exceeded_by(cost, size, spent)
+            }
+            None => Some("L1 fees are unknown".to_string()),
+        };
+        let Some(reason) = exceeded_by else {
+            return Ok(None);
+        };
+
+        let held_for = now.saturating_sub(channel.created_at as u64);
+        if held_for < self.max_hold.as_secs() {
+            return Ok(Some(reason));
+        }
+
+        warn!(
+            "Submitting batch {} over the L1 budget after holding it {}s: {}",
+            channel.id, held_for, reason
+        );
+        Ok(None)
+    }
+}
+
+/// Keeps the channel builder up to date with the L1 fees.
+pub struct L1FeePoller {
+    l1: L1Client,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    interval: Duration,
+}
+
+impl L1FeePoller {
+    pub fn new(
+        l1: L1Client,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            l1,
+            channel_builder,
+            interval,
+        }
+    }
+
+    /// Fetches the latest L1 fees and records them.
+    pub async fn poll(&self) -> eyre::Result<()> {
+        let header = self.l1.latest_header().await?;
+        let base_fee = header
+            .base_fee_per_gas
+            .ok_or_else(|| eyre::eyre!("L1 block {} has no base fee", header.number))?;
+        let blob_base_fee = self.l1.blob_base_fee().await?;
+
+        let fees = L1Fees {
+            l1_block: header.number,
+            base_fee: u128::from(base_fee),
+            blob_base_fee,
+        };
+        debug!(
+            "L1 block {} base fee {} wei, blob base fee {} wei",
+            fees.l1_block, fees.base_fee, fees.blob_base_fee
+        );
+
+        self.channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?
+            .set_l1_fees(fees);
+        Ok(())
+    }
+
+    /// Polls on every tick of the configured interval, forever.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.poll().await {
+                error!("Failed to poll L1 fees: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codec::Codec, db::BatchStatus};
+
+    const FEES: L1Fees = L1Fees {
+        l1_block: 1,
+        base_fee: 10,
+        blob_base_fee: 1,
+    };
+
+    fn unix_now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn channel(id: &str, created_at: i64) -> BatchInfo {
+        BatchInfo {
+            id: id.to_string(),
+            block_numbers: vec![1],
+            data: Vec::new(),
+            created_at,
+            submitted_at: None,
+            celestia_height: None,
+            retry_count: 0,
+            status: BatchStatus::Pending,
+            pruned_at: None,
+            first_block_timestamp: None,
+            da_commitment: None,
+            l1_tx_hash: None,
+            codec: Codec::Json,
+            estimated_l1_cost: None,
+            da_fee: None,
+            compressed_size: None,
+        }
+    }
+
+    #[test]
+    fn estimates_calldata() {
+        // A transaction, its frame overhead and 40 gas per byte
+        assert_eq!(FEES.calldata_fee(0), (21_000 + 24 * 40) * 10);
+        assert_eq!(FEES.calldata_fee(1_000), (21_000 + 1_024 * 40) * 10);
+
+        // Split over 3 transactions
+        let cost = FEES.estimate(240_000, DaType::Calldata);
+        assert_eq!(cost.da_type, DaType::Calldata);
+        assert_eq!(
+            cost.estimated_fee,
+            (3 * 21_000 + (240_000 + 3 * 24) * 40) * 10
+        );
+        assert_eq!((cost.base_fee, cost.blob_base_fee), (10, 1));
+    }
+
+    #[test]
+    fn estimates_blobs() {
+        assert_eq!(FEES.blob_fee(1_000), 21_000 * 10 + 131_072);
+
+        // 7 blobs don't fit in a single transaction
+        let cost = FEES.estimate(6 * (BLOB_CAPACITY - FRAME_OVERHEAD) + 1, DaType::Blobs);
+        assert_eq!(cost.da_type, DaType::Blobs);
+        assert_eq!(cost.estimated_fee, 2 * 21_000 * 10 + 7 * 131_072);
+    }
+
+    #[test]
+    fn auto_picks_the_cheaper() {
+        assert_eq!(FEES.estimate(1_000, DaType::Auto).da_type, DaType::Blobs);
+
+        let expensive_blobs = L1Fees {
+            blob_base_fee: 100,
+            ..FEES
+        };
+        let cost = expensive_blobs.estimate(1_000, DaType::Auto);
+        assert_eq!(cost.da_type, DaType::Calldata);
+        assert_eq!(cost.estimated_fee, expensive_blobs.calldata_fee(1_000));
+    }
+
+    #[test]
+    fn estimates_saturate() {
+        let fees = L1Fees {
+            l1_block: 1,
+            base_fee: u128::MAX,
+            blob_base_fee: u128::MAX,
+        };
+        assert_eq!(fees.estimate(1_000, DaType::Auto).estimated_fee, u128::MAX);
+    }
+
+    #[test]
+    fn budget_limits_fee_per_byte_and_per_hour() {
+        let cost = FEES.estimate(1_000, DaType::Blobs);
+        let budget = L1Budget {
+            max_fee_per_hour: None,
+            max_fee_per_byte: Some(cost.estimated_fee / 1_000),
+            max_hold: Duration::from_secs(60),
+        };
+        assert_eq!(budget.exceeded_by(&cost, 1_000, 0), None);
+        assert!(budget.exceeded_by(&cost, 100, 0).is_some());
+
+        let budget = L1Budget {
+            max_fee_per_hour: Some(cost.estimated_fee + 10),
+            max_fee_per_byte: None,
+            ..budget
+        };
+        assert_eq!(budget.exceeded_by(&cost, 1_000, 10), None);
+        assert!(budget.exceeded_by(&cost, 1_000, 11).is_some());
+        assert!(budget.exceeded_by(&cost, 1_000, u128::MAX).is_some());
+    }
+
+    #[test]
+    fn holds_channels_until_max_hold() -> eyre::Result<()> {
+        let db = DB::new(":memory:")?;
+        db.initialize_database()?;
+        let now = unix_now();
+
+        // Spent over the last hour, by a channel submitted a minute ago
+        let cost = FEES.estimate(1_000, DaType::Blobs);
+        db.conn().execute(
+            "INSERT INTO batches (id, block_numbers, data, created_at, status, submitted_at)
+             VALUES ('spent', '[1]', '[]', ?1, 'Submitted', ?1)",
+            [now - 60],
+        )?;
+        db.record_estimated_l1_cost("spent", &cost)?;
+
+        let budget = L1Budget {
+            max_fee_per_hour: Some(cost.estimated_fee * 3 / 2),
+            max_fee_per_byte: None,
+            max_hold: Duration::from_secs(600),
+        };
+        let recent = channel("recent", now);
+        let held = budget.hold_reason(&db, &recent, Some(&cost), 1_000)?;
+        assert!(held.is_some_and(|reason| reason.contains("over the last hour")));
+
+        // Half the fee fits in what's left of the budget
+        let cheap = L1Cost {
+            estimated_fee: cost.estimated_fee / 2,
+            ..cost
+        };
+        assert_eq!(budget.hold_reason(&db, &recent, Some(&cheap), 1_000)?, None);
+
+        // Held while the fees are unknown
+        let held = budget.hold_reason(&db, &recent, None, 1_000)?;
+        assert_eq!(held.as_deref(), Some("L1 fees are unknown"));
+
+        // Submitted whatever its cost once held for the max hold
+        let old = channel("old", now - 600);
+        assert_eq!(budget.hold_reason(&db, &old, Some(&cost), 1_000)?, None);
+        assert_eq!(budget.hold_reason(&db, &old, None, 1_000)?, None);
+        Ok(())
+    }
+}
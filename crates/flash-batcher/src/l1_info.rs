@@ -0,0 +1,256 @@
+use alloy_primitives::{B256, U256};
+use serde::{Deserialize, Serialize};
+
+/// EIP-2718 type of deposit transactions.
+pub const DEPOSIT_TX_TYPE: u8 = 0x7E;
+
+/// Selector of `setL1BlockValues`, used from Bedrock until Ecotone.
+const BEDROCK_SELECTOR: [u8; 4] = [0x01, 0x5d, 0x8e, 0xb9];
+/// Selector of `setL1BlockValuesEcotone`.
+const ECOTONE_SELECTOR: [u8; 4] = [0x44, 0x0a, 0x5e, 0x20];
+/// Selector of `setL1BlockValuesIsthmus`.
+const ISTHMUS_SELECTOR: [u8; 4] = [0x09, 0x89, 0x99, 0xbe];
+
+/// Bedrock calldata: selector followed by 8 ABI encoded words.
+const BEDROCK_LEN: usize = 4 + 8 * 32;
+/// Ecotone calldata: selector followed by tightly packed values.
+const ECOTONE_LEN: usize = 4 + 160;
+/// Isthmus calldata: Ecotone calldata followed by the operator fee scalar and constant.
+const ISTHMUS_LEN: usize = ECOTONE_LEN + 4 + 8;
+
+/// The L1 origin of an L2 block, decoded from its L1 info deposit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L1BlockInfo {
+    /// Number of the L1 origin, the epoch of the L2 block.
+    pub number: u64,
+    pub timestamp: u64,
+    pub hash: B256,
+    /// Position of the L2 block in its epoch.
+    pub sequence_number: u64,
+    pub base_fee: U256,
+    /// Only set from Ecotone.
+    pub blob_base_fee: Option<U256>,
+    pub batcher_hash: B256,
+    /// Only set from Ecotone.
+    pub base_fee_scalar: Option<u32>,
+    /// Only set from Ecotone.
+    pub blob_base_fee_scalar: Option<u32>,
+}
+
+impl L1BlockInfo {
+    /// Decodes the calldata of an L1 info deposit, in any of the Bedrock, Ecotone and Isthmus
+    /// formats.
+    pub fn decode_calldata(calldata: &[u8]) -> anyhow::Result<Self> {
+        if calldata.len() < 4 {
+            return Err(anyhow::anyhow!(
+                "L1 info calldata too short: {} bytes",
+                calldata.len()
+            ));
+        }
+
+        let (selector, data) = calldata.split_at(4);
+        match (selector, calldata.len()) {
+            (s, BEDROCK_LEN) if s == BEDROCK_SELECTOR => Ok(Self::decode_bedrock(data)),
+            (s, ECOTONE_LEN) if s == ECOTONE_SELECTOR => Ok(Self::decode_ecotone(data)),
+            (s, ISTHMUS_LEN) if s == ISTHMUS_SELECTOR => Ok(Self::decode_ecotone(data)),
+            (s, len) => Err(anyhow::anyhow!(
+                "Unknown L1 info calldata with selector 0x{} and length {}",
+                alloy_primitives::hex::encode(s),
+                len
+            )),
+        }
+    }
+
+    /// Encodes the calldata of an L1 info deposit, in the Isthmus format if `isthmus`, or else
+    /// the Ecotone one. Unset Ecotone values and the Isthmus operator fee are encoded as zero.
+    pub fn encode_calldata(&self, isthmus: bool) -> Vec<u8> {
+        let mut calldata = Vec::with_capacity(ISTHMUS_LEN);
+        calldata.extend_from_slice(if isthmus {
+            &ISTHMUS_SELECTOR
+        } else {
+            &ECOTONE_SELECTOR
+        });
+        calldata.extend_from_slice(&self.base_fee_scalar.unwrap_or_default().to_be_bytes());
+        calldata.extend_from_slice(&self.blob_base_fee_scalar.unwrap_or_default().to_be_bytes());
+        calldata.extend_from_slice(&self.sequence_number.to_be_bytes());
+        calldata.extend_from_slice(&self.timestamp.to_be_bytes());
+        calldata.extend_from_slice(&self.number.to_be_bytes());
+        calldata.extend_from_slice(&self.base_fee.to_be_bytes::<32>());
+        calldata.extend_from_slice(&self.blob_base_fee.unwrap_or_default().to_be_bytes::<32>());
+        calldata.extend_from_slice(self.hash.as_slice());
+        calldata.extend_from_slice(self.batcher_hash.as_slice());
+        if isthmus {
+            // Operator fee scalar and constant
+            calldata.extend_from_slice(&[0; 4 + 8]);
+        }
+        calldata
+    }
+
+    fn decode_bedrock(data: &[u8]) -> Self {
+        let word = |index: usize| &data[index * 32..(index + 1) * 32];
+
+        Self {
+            number: u64_at(word(0), 24),
+            timestamp: u64_at(word(1), 24),
+            base_fee: U256::from_be_slice(word(2)),
+            hash: B256::from_slice(word(3)),
+            sequence_number: u64_at(word(4), 24),
+            batcher_hash: B256::from_slice(word(5)),
+            blob_base_fee: None,
+            base_fee_scalar: None,
+            blob_base_fee_scalar: None,
+        }
+    }
+
+    /// Decodes the Ecotone layout, which Isthmus extends with trailing operator fee values.
+    fn decode_ecotone(data: &[u8]) -> Self {
+        Self {
+            base_fee_scalar: Some(u32_at(data, 0)),
+            blob_base_fee_scalar: Some(u32_at(data, 4)),
+            sequence_number: u64_at(data, 8),
+            timestamp: u64_at(data, 16),
+            number: u64_at(data, 24),
+            base_fee: U256::from_be_slice(&data[32..64]),
+            blob_base_fee: Some(U256::from_be_slice(&data[64..96])),
+            hash: B256::from_slice(&data[96..128]),
+            batcher_hash: B256::from_slice(&data[128..160]),
+        }
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{b256, hex};
+
+    const HASH: B256 = b256!("0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa");
+    const BATCHER_HASH: B256 =
+        b256!("0x000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb");
+
+    /// `setL1BlockValues` calldata, one ABI word per field.
+    const BEDROCK: [u8; BEDROCK_LEN] = hex!(
+        "015d8eb9"
+        "000000000000000000000000000000000000000000000000000000000121eac0" // number
+        "000000000000000000000000000000000000000000000000000000006553f100" // timestamp
+        "00000000000000000000000000000000000000000000000000000001a13b8600" // base fee
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" // hash
+        "0000000000000000000000000000000000000000000000000000000000000003" // sequence number
+        "000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb" // batcher hash
+        "00000000000000000000000000000000000000000000000000000000000000bc" // fee overhead
+        "00000000000000000000000000000000000000000000000000000000000a6fe0" // fee scalar
+    );
+
+    /// `setL1BlockValuesEcotone` calldata, tightly packed.
+    const ECOTONE: [u8; ECOTONE_LEN] = hex!(
+        "440a5e20"
+        "00000558" // base fee scalar
+        "000c5fc5" // blob base fee scalar
+        "0000000000000003" // sequence number
+        "000000006553f100" // timestamp
+        "000000000121eac0" // number
+        "00000000000000000000000000000000000000000000000000000001a13b8600" // base fee
+        "0000000000000000000000000000000000000000000000000000000000000001" // blob base fee
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa" // hash
+        "000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb" // batcher hash
+    );
+
+    /// `setL1BlockValuesIsthmus` calldata, the Ecotone one followed by the operator fee.
+    const ISTHMUS: [u8; ISTHMUS_LEN] = hex!(
+        "098999be"
+        "00000558"
+        "000c5fc5"
+        "0000000000000003"
+        "000000006553f100"
+        "000000000121eac0"
+        "00000000000000000000000000000000000000000000000000000001a13b8600"
+        "0000000000000000000000000000000000000000000000000000000000000001"
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+        "000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"
+        "000007d0" // operator fee scalar
+        "00000000000001f4" // operator fee constant
+    );
+
+    fn ecotone_info() -> L1BlockInfo {
+        L1BlockInfo {
+            number: 19_000_000,
+            timestamp: 1_700_000_000,
+            hash: HASH,
+            sequence_number: 3,
+            base_fee: U256::from(7_000_000_000u64),
+            blob_base_fee: Some(U256::from(1)),
+            batcher_hash: BATCHER_HASH,
+            base_fee_scalar: Some(1368),
+            blob_base_fee_scalar: Some(810_949),
+        }
+    }
+
+    #[test]
+    fn decodes_bedrock() {
+        assert_eq!(
+            L1BlockInfo::decode_calldata(&BEDROCK).unwrap(),
+            L1BlockInfo {
+                blob_base_fee: None,
+                base_fee_scalar: None,
+                blob_base_fee_scalar: None,
+                ..ecotone_info()
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_ecotone_and_isthmus() {
+        assert_eq!(
+            L1BlockInfo::decode_calldata(&ECOTONE).unwrap(),
+            ecotone_info()
+        );
+        assert_eq!(
+            L1BlockInfo::decode_calldata(&ISTHMUS).unwrap(),
+            ecotone_info()
+        );
+    }
+
+    #[test]
+    fn encodes_ecotone_and_isthmus() {
+        assert_eq!(ecotone_info().encode_calldata(false), ECOTONE);
+
+        // The operator fee isn't known, and encoded as zero
+        let mut isthmus = ISTHMUS;
+        isthmus[ECOTONE_LEN..].fill(0);
+        assert_eq!(ecotone_info().encode_calldata(true), isthmus);
+    }
+
+    #[test]
+    fn rejects_wrong_length() {
+        // Each selector only comes with its own layout
+        for calldata in [
+            &BEDROCK[..BEDROCK_LEN - 1],
+            &ECOTONE[..ECOTONE_LEN - 1],
+            &ISTHMUS[..ECOTONE_LEN],
+            &[BEDROCK_SELECTOR.as_slice(), &ECOTONE[4..]].concat(),
+            &[ECOTONE_SELECTOR.as_slice(), &ISTHMUS[4..]].concat(),
+            &[ISTHMUS_SELECTOR.as_slice(), &BEDROCK[4..]].concat(),
+        ] {
+            assert!(L1BlockInfo::decode_calldata(calldata).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_selector() {
+        let mut calldata = ECOTONE;
+        calldata[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let err = L1BlockInfo::decode_calldata(&calldata).unwrap_err();
+        assert!(err.to_string().contains("0xdeadbeef"), "{err}");
+
+        assert!(L1BlockInfo::decode_calldata(&[]).is_err());
+        assert!(L1BlockInfo::decode_calldata(&ECOTONE_SELECTOR[..3]).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use futures::{FutureExt, TryStreamExt};
 use reth::core::primitives::AlloyBlockHeader;
 use reth_exex::{ExExContext, ExExEvent, ExExNotification};
-use reth_node_api::FullNodeComponents;
+use reth_node_api::{BlockTy, FullNodeComponents};
 use reth_primitives_traits::Block;
 use std::sync::{Arc, Mutex};
 use std::{
@@ -12,34 +12,256 @@ use std::{
 use tracing::{debug, error, info, warn};
 
 use crate::channel_builder::ChannelBuilder;
-use crate::db::{BatchStatus, BlockData, DB};
+use crate::codec::Codec;
+use crate::db::BlockData;
+use crate::events::ChannelEvent;
+use crate::op_node::OpNodeClient;
+use reth::providers::{BlockNumReader, BlockReader};
 use reth_primitives::SealedBlock;
 
+pub mod accounting;
+pub mod alt_da;
+pub mod args;
+pub mod batch;
 pub mod channel_builder;
+pub mod cli;
+pub mod codec;
 pub mod db;
+pub mod events;
+pub mod l1;
+pub mod l1_cost;
+pub mod l1_info;
+pub mod metrics;
+pub mod op_node;
+pub mod pruner;
+pub mod rebuild;
+pub mod rpc;
+pub mod signer;
+pub mod submitter;
+pub mod system_config;
+pub mod throttle;
+pub mod verifier;
 
-fn serialize_block<B: Block>(block: &SealedBlock<B>) -> anyhow::Result<Vec<u8>>
-where
-    SealedBlock<B>: serde::Serialize,
-{
-    serde_json::to_vec(block).map_err(|e| anyhow::anyhow!("Failed to serialize block: {}", e))
+#[cfg(test)]
+mod tests;
+
+/// Encodes a block into the data stored in channels.
+type BlockEncoder<B> = fn(&SealedBlock<B>, Codec) -> anyhow::Result<BlockData>;
+
+fn block_data<B: Block>(block: &SealedBlock<B>, codec: Codec) -> anyhow::Result<BlockData> {
+    Ok(BlockData {
+        block_number: block.number(),
+        block_hash: block.hash().to_string(),
+        timestamp: block.timestamp(),
+        block_data: codec.encode_block(block)?,
+        batch_id: None,
+    })
 }
 
 pub struct BatcherExEx<Node: FullNodeComponents> {
     ctx: ExExContext<Node>,
-    channel_builder: ChannelBuilder,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    encode_block: BlockEncoder<BlockTy<Node::Types>>,
 }
 
 impl<Node: FullNodeComponents> BatcherExEx<Node> {
     pub async fn new(
         ctx: ExExContext<Node>,
-        channel_builder: ChannelBuilder,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        op_node: Option<OpNodeClient>,
     ) -> eyre::Result<Self> {
-        Ok(Self {
+        Self::with_block_encoder(ctx, channel_builder, op_node, block_data).await
+    }
+
+    /// Creates the ExEx with a custom block encoder, so tests can batch blocks without an L1 info
+    /// deposit.
+    async fn with_block_encoder(
+        ctx: ExExContext<Node>,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        op_node: Option<OpNodeClient>,
+        encode_block: BlockEncoder<BlockTy<Node::Types>>,
+    ) -> eyre::Result<Self> {
+        let exex = Self {
             ctx,
             channel_builder,
-        })
+            encode_block,
+        };
+
+        if let Some(op_node) = op_node {
+            match op_node.sync_status().await {
+                Ok(status) => {
+                    let mut channel_builder = exex
+                        .channel_builder
+                        .lock()
+                        .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+                    channel_builder.set_sync_status(status);
+                    exex.catch_up(&mut channel_builder)?;
+                }
+                Err(e) => warn!("Not catching up from the safe head: {}", e),
+            }
+        }
+
+        Ok(exex)
+    }
+
+    /// Queues the blocks the node has past both the safe head and the latest batched block, which
+    /// were lost from the open channel when the node stopped.
+    fn catch_up(&self, channel_builder: &mut ChannelBuilder) -> eyre::Result<()> {
+        let latest_batched = channel_builder
+            .db()
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .latest_batched_block()
+            .map_err(|e| eyre::eyre!("Failed to fetch latest batched block: {}", e))?;
+
+        let start = channel_builder
+            .safe_head()
+            .max(latest_batched)
+            .map_or(1, |block| block + 1);
+        let tip = self.ctx.provider().best_block_number()?;
+        if start > tip {
+            return Ok(());
+        }
+
+        info!("Catching up on blocks {} to {}", start, tip);
+        for number in start..=tip {
+            let block = self
+                .ctx
+                .provider()
+                .block_by_number(number)?
+                .ok_or_else(|| eyre::eyre!("Block {} not found", number))?;
+            commit_block(
+                channel_builder,
+                &SealedBlock::seal_slow(block),
+                self.encode_block,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl<Node: FullNodeComponents> BatcherExEx<Node> {
+    /// Drops everything batched from `first_invalid` onwards, and requeues the canonical blocks
+    /// of invalidated batches.
+    fn invalidate_from(
+        &self,
+        channel_builder: &mut ChannelBuilder,
+        first_invalid: u64,
+    ) -> eyre::Result<()> {
+        let dropped_blocks = channel_builder.truncate_from(first_invalid);
+
+        let invalidated = channel_builder
+            .db()
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .invalidate_batches_from(first_invalid)
+            .map_err(|e| eyre::eyre!("Failed to invalidate batches: {}", e))?;
+
+        // Blocks preceding the reorg are still canonical, batch them again
+        let requeue_from = invalidated
+            .iter()
+            .filter_map(|batch| batch.block_numbers.iter().min().copied())
+            .min()
+            .filter(|first| *first < first_invalid);
+
+        if let Some(requeue_from) = requeue_from {
+            let mut blocks = Vec::new();
+            for number in requeue_from..first_invalid {
+                let block = self
+                    .ctx
+                    .provider()
+                    .block_by_number(number)?
+                    .ok_or_else(|| eyre::eyre!("Canonical block {} not found", number))?;
+
+                blocks.push(
+                    (self.encode_block)(&SealedBlock::seal_slow(block), channel_builder.codec())
+                        .map_err(|e| eyre::eyre!("Failed to requeue block {}: {}", number, e))?,
+                );
+            }
+            channel_builder.requeue_blocks(blocks);
+        }
+
+        let invalidated_channels: Vec<String> =
+            invalidated.into_iter().map(|batch| batch.id).collect();
+        warn!(
+            "Invalidated {} batches and {} pending blocks from block {}",
+            invalidated_channels.len(),
+            dropped_blocks,
+            first_invalid
+        );
+
+        channel_builder.events().emit(ChannelEvent::Reorged {
+            first_invalid_block: first_invalid,
+            invalidated_channels,
+            dropped_blocks,
+        });
+
+        Ok(())
+    }
+}
+
+/// Returns the latest block in the open channel, or in a valid batch if the channel is empty.
+fn last_queued_block(channel_builder: &ChannelBuilder) -> Option<u64> {
+    if let Some(last) = channel_builder.pending_blocks().back() {
+        return Some(last.block_number);
+    }
+
+    let db = channel_builder.db();
+    let db = db.lock().ok()?;
+    db.latest_batched_block()
+        .map_err(|e| error!("Failed to fetch latest batched block: {}", e))
+        .ok()
+        .flatten()
+}
+
+/// Adds the block to the open channel, closing it once the flush policy is met.
+fn commit_block<B: Block>(
+    channel_builder: &mut ChannelBuilder,
+    block: &SealedBlock<B>,
+    encode_block: BlockEncoder<B>,
+) {
+    if channel_builder
+        .safe_head()
+        .is_some_and(|safe_head| block.number() <= safe_head)
+    {
+        debug!("Skipping block {}, already safe", block.number());
+        return;
+    }
+
+    // Blocks queued when catching up may be notified again
+    if last_queued_block(channel_builder).is_some_and(|last| block.number() <= last) {
+        debug!("Skipping block {}, already batched", block.number());
+        return;
+    }
+
+    let block_data = match encode_block(block, channel_builder.codec()) {
+        Ok(block_data) => block_data,
+        Err(e) => {
+            error!("Failed to serialize block {}: {}", block.number(), e);
+            return; // Skip this block but continue processing
+        }
+    };
+
+    channel_builder.add_block(block_data);
+    debug!(
+        "Added block {} to queue. Pending: {}/{}",
+        block.number(),
+        channel_builder.pending_blocks().len(),
+        channel_builder.batch_size()
+    );
+
+    if channel_builder.should_close() {
+        debug!("Flush policy reached, creating batch...");
+
+        // The closed channel is submitted by the submitter task
+        if let Err(e) = channel_builder.close_channel() {
+            error!("Failed to insert batch: {}", e);
+            return;
+        }
     }
+
+    debug!("Processed block: {}", block.number());
 }
 
 impl<Node: FullNodeComponents> Future for BatcherExEx<Node> {
@@ -49,101 +271,54 @@ impl<Node: FullNodeComponents> Future for BatcherExEx<Node> {
         let this = self.get_mut();
 
         while let Some(notification) = ready!(this.ctx.notifications.try_next().poll_unpin(cx))? {
+            let channel_builder = this.channel_builder.clone();
+            let mut channel_builder = channel_builder
+                .lock()
+                .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+
             match &notification {
                 ExExNotification::ChainCommitted { new } => {
                     for block in new.blocks_iter() {
-                        let data = match serialize_block(block.sealed_block()) {
-                            Ok(data) => data,
-                            Err(e) => {
-                                error!("Failed to serialize block {}: {}", block.number(), e);
-                                continue; // Skip this block but continue processing
-                            }
-                        };
-
-                        let block_data = BlockData {
-                            block_number: block.number(),
-                            block_hash: block.hash().to_string(),
-                            timestamp: block.timestamp(),
-                            block_data: data,
-                            batch_id: None,
-                        };
-
-                        this.channel_builder.add_block(block_data.clone());
-                        debug!(
-                            "Added block {} to queue. Pending: {}/{}",
-                            block.number(),
-                            this.channel_builder.pending_blocks().len(),
-                            this.channel_builder.batch_size()
+                        commit_block(
+                            &mut channel_builder,
+                            block.sealed_block(),
+                            this.encode_block,
                         );
-
-                        if this.channel_builder.pending_blocks().len()
-                            >= this.channel_builder.batch_size() as usize
-                        {
-                            debug!("Batch size reached, creating batch...");
-
-                            if let Err(e) = this.channel_builder.insert_batch() {
-                                error!("Failed to insert batch: {}", e);
-                                continue;
-                            }
-
-                            this.channel_builder.clear_queue();
-
-                            let db = this.channel_builder.db();
-                            if let Err(e) = submit_batches(db) {
-                                error!("Failed to submit batches: {}", e);
-                            }
-                        }
-
-                        debug!("Processed block: {}", block.number());
                     }
 
                     info!(committed_chain = ?new.range(), "Received commit");
-
-                    this.ctx
-                        .events
-                        .send(ExExEvent::FinishedHeight(new.tip().num_hash()))?;
                 }
                 ExExNotification::ChainReorged { old, new } => {
                     warn!(from_chain = ?old.range(), to_chain = ?new.range(), "Received reorg");
+
+                    this.invalidate_from(&mut channel_builder, old.first().number())?;
+                    for block in new.blocks_iter() {
+                        commit_block(
+                            &mut channel_builder,
+                            block.sealed_block(),
+                            this.encode_block,
+                        );
+                    }
                 }
                 ExExNotification::ChainReverted { old } => {
                     warn!(reverted_chain = ?old.range(), "Received revert");
+
+                    this.invalidate_from(&mut channel_builder, old.first().number())?;
                 }
             };
-        }
 
-        Poll::Ready(Ok(()))
-    }
-}
-
-fn submit_batches(db: Arc<Mutex<DB>>) -> eyre::Result<()> {
-    let db = db
-        .lock()
-        .map_err(|_| eyre::eyre!("Database lock poisoned"))?;
-
-    let batches = db
-        .get_pending_batches()
-        .map_err(|e| eyre::eyre!("Failed to get pending batches: {}", e))?;
-
-    debug!("Found {} pending batches to submit", batches.len());
+            let db = channel_builder.db();
+            if let Ok(db) = db.lock() {
+                channel_builder.metrics().record_backlog(&db);
+            }
 
-    for batch in batches {
-        // NOTE: right now we are not submitting the batches to the flash chain, we are just marking them as submitted
-        // ideally we would make a call via the celestia-client to submit the batches for the flash chain
-        debug!(
-            "Processing batch: {} with {} blocks",
-            batch.id,
-            batch.block_numbers.len()
-        );
-
-        if let Err(e) = db.update_batch_status(&batch.id, BatchStatus::Submitted) {
-            error!("Failed to update batch status for {}: {}", batch.id, e);
-            continue;
+            if let Some(committed_chain) = notification.committed_chain() {
+                this.ctx
+                    .events
+                    .send(ExExEvent::FinishedHeight(committed_chain.tip().num_hash()))?;
+            }
         }
 
-        info!("Successfully submitted batch: {}", batch.id);
+        Poll::Ready(Ok(()))
     }
-
-    info!("Batch submission completed");
-    Ok(())
 }
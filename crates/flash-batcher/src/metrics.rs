@@ -0,0 +1,111 @@
+use crate::db::{BatchStatus, DB};
+use reth_metrics::{
+    Metrics,
+    metrics::{Counter, Gauge, Histogram},
+};
+use std::collections::HashMap;
+use tracing::error;
+
+/// Channel metrics, exposed on the node's `--metrics` endpoint.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "batcher")]
+pub struct ChannelMetrics {
+    /// Number of blocks in the open channel
+    pub pending_blocks: Gauge,
+    /// Size of the block data in the open channel, in bytes
+    pub open_channel_size: Gauge,
+    /// Number of channels created
+    pub channels_created: Counter,
+    /// Number of channel bytes submitted to the DA layer
+    pub bytes_submitted: Counter,
+    /// Number of channels being submitted
+    pub channels_in_flight: Gauge,
+    /// Number of failed channel submissions
+    pub submission_failures: Counter,
+    /// Ratio of the channel data size to its zlib compressed size
+    pub compression_ratio: Histogram,
+    /// Time spent putting a channel to the alt-DA server, in seconds
+    pub submission_latency: Histogram,
+    /// Time between the first block of a channel and the L1 inclusion of its commitment, in
+    /// seconds
+    pub block_to_inclusion_latency: Histogram,
+}
+
+/// op-node sync status metrics.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "batcher.sync")]
+pub struct SyncMetrics {
+    /// Safe head reported by op-node
+    pub safe_head: Gauge,
+    /// Unsafe head reported by op-node
+    pub unsafe_head: Gauge,
+    /// Number of unsafe blocks derivation still has to reach
+    pub safe_lag: Gauge,
+}
+
+/// Sequencer throttling metrics.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "batcher.throttle")]
+pub struct ThrottleMetrics {
+    /// Size of the channels and open channel data not submitted yet, in bytes
+    pub unsubmitted_bytes: Gauge,
+    /// Whether the sequencer DA limits are currently throttled
+    pub active: Gauge,
+}
+
+/// Channel backlog metrics, labeled by [`BatchStatus`].
+#[derive(Metrics, Clone)]
+#[metrics(scope = "batcher.backlog")]
+struct BacklogMetrics {
+    /// Number of channels with the given status
+    channels: Gauge,
+}
+
+/// Handles to all batcher metrics.
+#[derive(Clone)]
+pub struct BatcherMetrics {
+    pub channel: ChannelMetrics,
+    pub sync: SyncMetrics,
+    pub throttle: ThrottleMetrics,
+    backlog: HashMap<String, BacklogMetrics>,
+}
+
+impl BatcherMetrics {
+    pub fn new() -> Self {
+        let backlog = BatchStatus::ALL
+            .into_iter()
+            .map(|status| {
+                let status = status.to_string();
+                let metrics = BacklogMetrics::new_with_labels(&[("status", status.clone())]);
+                (status, metrics)
+            })
+            .collect();
+
+        Self {
+            channel: ChannelMetrics::default(),
+            sync: SyncMetrics::default(),
+            throttle: ThrottleMetrics::default(),
+            backlog,
+        }
+    }
+
+    /// Refreshes the backlog gauges from the database.
+    pub fn record_backlog(&self, db: &DB) {
+        for status in BatchStatus::ALL {
+            match db.get_batch_count_by_status(status) {
+                Ok(count) => {
+                    if let Some(metrics) = self.backlog.get(&status.to_string()) {
+                        metrics.channels.set(count as f64);
+                    }
+                }
+                Err(e) => error!("Failed to record backlog for status {}: {}", status, e),
+            }
+        }
+    }
+}
+
+impl Default for BatcherMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
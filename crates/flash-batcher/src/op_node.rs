@@ -0,0 +1,184 @@
+//! op-node client, following the safe head derivation reached.
+//!
+//! Blocks up to the safe head were already derived from the DA layer, the batcher doesn't
+//! need to batch them again.
+
+use crate::{channel_builder::ChannelBuilder, events::ChannelEvent};
+use alloy_primitives::B256;
+use jsonrpsee::{
+    http_client::{HttpClient, HttpClientBuilder},
+    proc_macros::rpc,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// Reference to an L1 block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L1BlockRef {
+    pub hash: B256,
+    pub number: u64,
+    pub parent_hash: B256,
+    pub timestamp: u64,
+}
+
+/// Hash and number of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockId {
+    pub hash: B256,
+    pub number: u64,
+}
+
+/// Reference to an L2 block and its L1 origin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct L2BlockRef {
+    pub hash: B256,
+    pub number: u64,
+    pub parent_hash: B256,
+    pub timestamp: u64,
+    #[serde(rename = "l1origin")]
+    pub l1_origin: BlockId,
+    pub sequence_number: u64,
+}
+
+/// Sync status of op-node, as returned by `optimism_syncStatus`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// L1 block derivation is currently at.
+    pub current_l1: L1BlockRef,
+    pub head_l1: L1BlockRef,
+    pub safe_l1: L1BlockRef,
+    pub finalized_l1: L1BlockRef,
+    pub unsafe_l2: L2BlockRef,
+    /// Latest L2 block derived from the DA layer.
+    pub safe_l2: L2BlockRef,
+    pub finalized_l2: L2BlockRef,
+}
+
+impl SyncStatus {
+    /// Number of unsafe blocks derivation still has to reach.
+    pub fn safe_lag(&self) -> u64 {
+        self.unsafe_l2.number.saturating_sub(self.safe_l2.number)
+    }
+}
+
+/// The `optimism_` namespace of op-node.
+#[rpc(client, namespace = "optimism")]
+pub trait OpNodeApi {
+    #[method(name = "syncStatus")]
+    async fn sync_status(&self) -> jsonrpsee::core::RpcResult<SyncStatus>;
+}
+
+/// HTTP client of an op-node RPC.
+#[derive(Debug, Clone)]
+pub struct OpNodeClient {
+    url: String,
+    client: HttpClient,
+}
+
+impl OpNodeClient {
+    pub fn new(url: &str) -> eyre::Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .map_err(|e| eyre::eyre!("Failed to create op-node client for {}: {}", url, e))?;
+
+        Ok(Self {
+            url: url.to_string(),
+            client,
+        })
+    }
+
+    pub async fn sync_status(&self) -> eyre::Result<SyncStatus> {
+        OpNodeApiClient::sync_status(&self.client)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to fetch sync status from {}: {}", self.url, e))
+    }
+}
+
+/// Keeps the channel builder up to date with the op-node sync status.
+pub struct SyncStatusPoller {
+    client: OpNodeClient,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    interval: Duration,
+    /// Number of unsafe blocks after which derivation is considered behind.
+    max_safe_lag: u64,
+}
+
+impl SyncStatusPoller {
+    pub fn new(
+        client: OpNodeClient,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        interval: Duration,
+        max_safe_lag: u64,
+    ) -> Self {
+        Self {
+            client,
+            channel_builder,
+            interval,
+            max_safe_lag,
+        }
+    }
+
+    /// Fetches the sync status and records it, warning when derivation falls behind.
+    pub async fn poll(&self) -> eyre::Result<()> {
+        let status = self.client.sync_status().await?;
+        let lag = status.safe_lag();
+        debug!(
+            "op-node safe head {}, unsafe head {}",
+            status.safe_l2.number, status.unsafe_l2.number
+        );
+
+        let mut channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+
+        let metrics = &channel_builder.metrics().sync;
+        metrics.safe_head.set(status.safe_l2.number as f64);
+        metrics.unsafe_head.set(status.unsafe_l2.number as f64);
+        metrics.safe_lag.set(lag as f64);
+
+        let behind = lag > self.max_safe_lag;
+        let was_behind = channel_builder
+            .sync_status()
+            .is_some_and(|previous| previous.safe_lag() > self.max_safe_lag);
+        if behind && !was_behind {
+            warn!(
+                "Derivation fell behind: safe head {} is {} blocks behind unsafe head {}",
+                status.safe_l2.number, lag, status.unsafe_l2.number
+            );
+            channel_builder
+                .events()
+                .emit(ChannelEvent::DerivationBehind {
+                    safe_head: status.safe_l2.number,
+                    unsafe_head: status.unsafe_l2.number,
+                });
+        } else if !behind && was_behind {
+            info!(
+                "Derivation caught up: safe head {} is {} blocks behind",
+                status.safe_l2.number, lag
+            );
+        }
+
+        channel_builder.set_sync_status(status);
+        Ok(())
+    }
+
+    /// Polls on every tick of the configured interval, forever.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.poll().await {
+                error!("Failed to poll op-node sync status: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{channel_builder::ChannelBuilder, db::DB};
+use std::{
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, info, warn};
+
+/// Number of batches pruned at once, between which the database lock is released.
+const PRUNE_CHUNK: usize = 100;
+/// Number of pages freed at once, between which the database lock is released.
+const VACUUM_CHUNK_PAGES: u32 = 1_000;
+
+/// Decides which submitted channels have their payload dropped.
+///
+/// Only channels whose blocks op-node finalized are pruned, so their payload stays available
+/// until L1 can no longer reorg them out. In dev mode, blocks are final once produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Keep payloads of channels submitted within the given duration.
+    Age(Duration),
+    /// Keep payloads of channels within the given number of blocks of the latest batched block.
+    Depth(u64),
+}
+
+/// Periodically drops the payload of finalized channels, keeping their metadata.
+///
+/// Payloads are dropped and pages freed in chunks, releasing the database lock in between so
+/// the ExEx and the submitters aren't stalled.
+pub struct Pruner {
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    policy: RetentionPolicy,
+    interval: Duration,
+    /// Whether the skipped passes were warned about, since the finalized head was last known.
+    warned_finalized_unknown: AtomicBool,
+}
+
+impl Pruner {
+    pub fn new(
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        policy: RetentionPolicy,
+        interval: Duration,
+    ) -> Self {
+        debug!(
+            "Creating Pruner with policy {:?}, interval {:?}",
+            policy, interval
+        );
+
+        Self {
+            channel_builder,
+            policy,
+            interval,
+            warned_finalized_unknown: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs a single pruning pass, returning the number of pruned channels.
+    pub async fn prune(&self) -> anyhow::Result<usize> {
+        let (db, finalized) = {
+            let channel_builder = self
+                .channel_builder
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Channel builder lock poisoned"))?;
+            (channel_builder.db(), channel_builder.finalized_head())
+        };
+
+        let Some(finalized) = finalized else {
+            if !self.warned_finalized_unknown.swap(true, Ordering::Relaxed) {
+                warn!("Finalized head unknown, not pruning until one is reported");
+            } else {
+                debug!("Finalized head unknown, not pruning");
+            }
+            return Ok(0);
+        };
+        self.warned_finalized_unknown
+            .store(false, Ordering::Relaxed);
+
+        let mut pruned = 0;
+        loop {
+            let chunk = self.prune_chunk(&*lock(&db)?, finalized)?;
+            pruned += chunk;
+            if chunk < PRUNE_CHUNK {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        if pruned > 0 {
+            info!(
+                "Pruned payloads of {} submitted batches up to finalized block {}",
+                pruned, finalized
+            );
+
+            let mut freed = 0;
+            loop {
+                let chunk = lock(&db)?.incremental_vacuum(VACUUM_CHUNK_PAGES)?;
+                freed += chunk;
+                if chunk == 0 {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+            debug!("Freed {} database pages", freed);
+        } else {
+            debug!("No batches to prune");
+        }
+
+        Ok(pruned)
+    }
+
+    /// Prunes up to [`PRUNE_CHUNK`] batches ending at or below the `finalized` block.
+    fn prune_chunk(&self, db: &DB, finalized: u64) -> anyhow::Result<usize> {
+        let pruned = match self.policy {
+            RetentionPolicy::Age(max_age) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| anyhow::anyhow!("System time error: {}", e))?;
+                let cutoff = now.saturating_sub(max_age).as_secs() as i64;
+
+                db.prune_submitted_before(cutoff, finalized, PRUNE_CHUNK)?
+            }
+            RetentionPolicy::Depth(depth) => match db.latest_batched_block()? {
+                Some(latest) if latest > depth => {
+                    db.prune_submitted_up_to_block((latest - depth).min(finalized), PRUNE_CHUNK)?
+                }
+                _ => 0,
+            },
+        };
+
+        Ok(pruned)
+    }
+
+    /// Prunes on every tick of the configured interval, forever.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.prune().await {
+                error!("Failed to prune batcher database: {}", e);
+            }
+        }
+    }
+}
+
+fn lock(db: &Mutex<DB>) -> anyhow::Result<MutexGuard<'_, DB>> {
+    db.lock()
+        .map_err(|_| anyhow::anyhow!("Database lock poisoned"))
+}
@@ -0,0 +1,268 @@
+//! Regeneration of the batcher database from the node's chain history.
+//!
+//! Blocks not covered by a valid channel are grouped into new pending channels following the
+//! flush policy, so they get submitted by the batcher. The max duration is measured on block
+//! timestamps rather than wall-clock time. Channel ids and creation times are new, but channels
+//! grouping the same blocks with the same codec hold the same data as the lost ones, and are
+//! found on the DA layer under the same keccak commitment.
+
+use crate::{
+    alt_da::{AltDaClient, CommitmentMode},
+    block_data,
+    channel_builder::{ChannelBuilder, FlushPolicy},
+    codec::Codec,
+    db::{BatchInfo, BatchStatus, DB},
+};
+use flash_da_server::commitment::Commitment;
+use reth::providers::BlockReader;
+use reth_primitives::SealedBlock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+use tokio::runtime::Runtime;
+use tracing::{debug, info, warn};
+
+/// Looks channels up on the DA layer.
+pub trait DaLayer {
+    /// Returns the commitment the channel's data is stored under, `None` if the DA layer
+    /// doesn't have it.
+    fn find(&self, channel: &BatchInfo) -> eyre::Result<Option<Commitment>>;
+}
+
+/// Looks channels up on an alt-DA server, under their recorded commitment or, failing that,
+/// under the keccak commitment of their data.
+///
+/// Commitments returned by the server in generic mode can't be computed, channels without a
+/// recorded one are only found in keccak mode.
+pub struct AltDaLayer {
+    client: AltDaClient,
+    runtime: Runtime,
+}
+
+impl AltDaLayer {
+    pub fn new(client: AltDaClient) -> eyre::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self { client, runtime })
+    }
+
+    fn commitment(&self, channel: &BatchInfo) -> eyre::Result<Option<Commitment>> {
+        if let Some(commitment) = &channel.da_commitment {
+            return commitment.parse().map(Some);
+        }
+
+        let computable =
+            self.client.mode() == CommitmentMode::Keccak && channel.pruned_at.is_none();
+        Ok(computable.then(|| Commitment::keccak(&channel.data)))
+    }
+}
+
+impl DaLayer for AltDaLayer {
+    fn find(&self, channel: &BatchInfo) -> eyre::Result<Option<Commitment>> {
+        let Some(commitment) = self.commitment(channel)? else {
+            return Ok(None);
+        };
+
+        let data = self.runtime.block_on(self.client.get(&commitment))?;
+        Ok(data
+            .filter(|data| commitment.matches(data))
+            .map(|_| commitment))
+    }
+}
+
+/// Outcome of rebuilding a block range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebuildReport {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Blocks already covered by a valid channel, left untouched.
+    pub covered_blocks: usize,
+    /// Channels created for the uncovered blocks.
+    pub created_channels: Vec<String>,
+    /// Created channels already on the DA layer, marked submitted under their commitment.
+    pub found_channels: Vec<String>,
+    /// Submitted channels not found on the DA layer, requeued for submission.
+    pub unposted_channels: Vec<String>,
+    /// Submitted channels not found on the DA layer whose data was pruned.
+    pub pruned_unposted_channels: Vec<String>,
+}
+
+/// Rebuilds channels from the blocks of a provider.
+pub struct Rebuilder<P> {
+    provider: P,
+    db: Arc<Mutex<DB>>,
+    policy: FlushPolicy,
+    codec: Codec,
+}
+
+impl<P: BlockReader> Rebuilder<P> {
+    pub fn new(provider: P, db: Arc<Mutex<DB>>, policy: FlushPolicy, codec: Codec) -> Self {
+        Self {
+            provider,
+            db,
+            policy: FlushPolicy::new(policy.batch_size, policy.max_duration),
+            codec,
+        }
+    }
+
+    /// Creates channels for the blocks in `from..=to` no valid channel contains. If `da` is
+    /// given, the created channels the DA layer has are marked submitted, and the submitted
+    /// channels of the range it doesn't have are requeued.
+    pub fn rebuild(
+        &self,
+        from: u64,
+        to: u64,
+        da: Option<&dyn DaLayer>,
+    ) -> eyre::Result<RebuildReport> {
+        info!("Rebuilding channels for blocks {} to {}", from, to);
+
+        let existing = self
+            .db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .get_batches_in_range(from, to)
+            .map_err(|e| eyre::eyre!("Failed to fetch channels: {}", e))?;
+        let covered: BTreeSet<u64> = existing
+            .iter()
+            .flat_map(|channel| channel.block_numbers.iter().copied())
+            .filter(|n| (from..=to).contains(n))
+            .collect();
+
+        let mut report = RebuildReport {
+            from_block: from,
+            to_block: to,
+            covered_blocks: covered.len(),
+            ..Default::default()
+        };
+
+        let mut channel_builder = ChannelBuilder::new(self.db.clone(), self.policy);
+        channel_builder.set_codec(self.codec);
+        for number in from..=to {
+            if covered.contains(&number) {
+                // Channels hold consecutive blocks, close the one preceding the gap
+                self.close(&mut channel_builder, &mut report)?;
+                continue;
+            }
+
+            let block = self
+                .provider
+                .block_by_number(number)?
+                .ok_or_else(|| eyre::eyre!("Block {} not found", number))?;
+            let block = block_data(&SealedBlock::seal_slow(block), self.codec)
+                .map_err(|e| eyre::eyre!("Failed to serialize block {}: {}", number, e))?;
+
+            channel_builder.add_block(block);
+            if self.should_close(&channel_builder) {
+                self.close(&mut channel_builder, &mut report)?;
+            }
+        }
+        self.close(&mut channel_builder, &mut report)?;
+
+        if let Some(da) = da {
+            self.reconcile(existing, da, &mut report)?;
+        }
+
+        info!(
+            "Rebuilt blocks {} to {}: {} channels created, {} found on the DA layer, {} unposted \
+             channels requeued",
+            from,
+            to,
+            report.created_channels.len(),
+            report.found_channels.len(),
+            report.unposted_channels.len()
+        );
+
+        Ok(report)
+    }
+
+    /// Whether the open channel reached the batch size, or spans the max duration.
+    fn should_close(&self, channel_builder: &ChannelBuilder) -> bool {
+        let pending = channel_builder.pending_blocks();
+        if pending.len() >= self.policy.batch_size as usize {
+            return true;
+        }
+
+        match (pending.front(), pending.back(), self.policy.max_duration) {
+            (Some(first), Some(last), Some(max_duration)) => {
+                last.timestamp.saturating_sub(first.timestamp) >= max_duration
+            }
+            _ => false,
+        }
+    }
+
+    fn close(
+        &self,
+        channel_builder: &mut ChannelBuilder,
+        report: &mut RebuildReport,
+    ) -> eyre::Result<()> {
+        if let Some(channel_id) = channel_builder
+            .close_channel()
+            .map_err(|e| eyre::eyre!("Failed to insert channel: {}", e))?
+        {
+            report.created_channels.push(channel_id);
+        }
+        Ok(())
+    }
+
+    fn reconcile(
+        &self,
+        existing: Vec<BatchInfo>,
+        da: &dyn DaLayer,
+        report: &mut RebuildReport,
+    ) -> eyre::Result<()> {
+        let db = self
+            .db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?;
+
+        for id in &report.created_channels {
+            let channel = db
+                .get_batch(id)
+                .map_err(|e| eyre::eyre!("Failed to fetch channel {}: {}", id, e))?
+                .ok_or_else(|| eyre::eyre!("Channel {} not found", id))?;
+            let Some(commitment) = da.find(&channel)? else {
+                continue;
+            };
+
+            debug!("Channel {} is on the DA layer under {}", id, commitment);
+            if db
+                .mark_pending_batch_posted(id, &commitment.to_string())
+                .map_err(|e| eyre::eyre!("Failed to update channel {}: {}", id, e))?
+            {
+                report.found_channels.push(id.clone());
+            }
+        }
+
+        for channel in existing
+            .into_iter()
+            .filter(|channel| channel.status == BatchStatus::Submitted)
+        {
+            if da.find(&channel)?.is_some() {
+                continue;
+            }
+
+            if channel.pruned_at.is_some() {
+                warn!(
+                    "Channel {} is not on the DA layer but its data was pruned",
+                    channel.id
+                );
+                report.pruned_unposted_channels.push(channel.id);
+                continue;
+            }
+
+            debug!("Channel {} is not on the DA layer, requeueing", channel.id);
+            if db
+                .requeue_batch(&channel.id)
+                .map_err(|e| eyre::eyre!("Failed to requeue channel {}: {}", channel.id, e))?
+            {
+                report.unposted_channels.push(channel.id);
+            }
+        }
+
+        Ok(())
+    }
+}
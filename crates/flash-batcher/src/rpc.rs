@@ -0,0 +1,597 @@
+use crate::{
+    accounting::{Accountant, AccountingReport},
+    alt_da::AltDaClient,
+    channel_builder::{ChannelBuilder, FlushPolicy, OpenChannel},
+    codec::Codec,
+    db::{BatchInfo, BatchStatus, DB},
+    l1_cost::{L1Cost, L1Fees},
+    op_node::SyncStatus,
+    system_config::SystemConfig,
+    verifier::{VerificationReport, Verifier},
+};
+use alloy_primitives::Bytes;
+use jsonrpsee::{
+    PendingSubscriptionSink, SubscriptionMessage,
+    core::{RpcResult, SubscriptionResult, async_trait},
+    proc_macros::rpc,
+    types::{
+        ErrorObjectOwned,
+        error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
+    },
+};
+use reth::providers::{BlockReader, ChainSpecProvider};
+use reth_optimism_forks::OpHardforks;
+use reth_primitives::SealedBlock;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
+
+/// Error code of `admin_startBatcher` and `admin_stopBatcher` when the batcher is already in
+/// the requested state, go-ethereum's default server error code op-batcher returns.
+pub const BATCHER_ERROR_CODE: i32 = -32000;
+/// Maximum time `admin_stopBatcher` waits for the channels being put.
+const STOP_DRAIN_TIMEOUT: Duration = Duration::from_secs(60);
+/// Interval between checks for the channels being put while stopping.
+const STOP_DRAIN_INTERVAL: Duration = Duration::from_millis(100);
+/// Default page size of `batcher_listChannels`.
+const DEFAULT_PAGE_SIZE: u64 = 50;
+/// Maximum page size of `batcher_listChannels`.
+const MAX_PAGE_SIZE: u64 = 500;
+/// Maximum number of blocks verified by a single `batcher_verify` call.
+const MAX_VERIFY_RANGE: u64 = 10_000;
+/// Maximum number of blocks reported by a single `batcher_accounting` call, as every block is
+/// read from the database.
+const MAX_ACCOUNTING_RANGE: u64 = 10_000;
+
+/// Overview of the batcher state, returned by `batcher_status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatcherStatus {
+    pub pending_blocks: usize,
+    pub open_channel: Option<OpenChannel>,
+    /// Number of channels per [`BatchStatus`].
+    pub backlog: BTreeMap<String, u32>,
+    /// Latest op-node sync status, if an op-node is configured.
+    pub sync_status: Option<SyncStatus>,
+    /// Latest L1 fees, if an L1 RPC is configured.
+    pub l1_fees: Option<L1Fees>,
+}
+
+/// A channel as stored in the batcher database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelInfo {
+    pub id: String,
+    pub block_numbers: Vec<u64>,
+    pub status: BatchStatus,
+    pub created_at: i64,
+    pub submitted_at: Option<i64>,
+    pub celestia_height: Option<u64>,
+    pub retry_count: u32,
+    pub pruned_at: Option<i64>,
+    /// Alt-DA commitment, once put to the DA server.
+    pub da_commitment: Option<String>,
+    /// L1 transaction posting the alt-DA commitment to the batch inbox.
+    pub l1_tx_hash: Option<String>,
+    /// Codec the batches in `data` are serialized with.
+    pub codec: Codec,
+    /// Size of the payload once zlib compressed, if recorded.
+    pub compressed_size: Option<u64>,
+    /// Estimated L1 data fee, once submitted with the L1 fees known.
+    pub estimated_l1_cost: Option<L1Cost>,
+    /// Channel payload, `None` once pruned, or when left out of a list.
+    pub data: Option<Bytes>,
+}
+
+impl From<BatchInfo> for ChannelInfo {
+    fn from(batch: BatchInfo) -> Self {
+        Self {
+            data: batch.pruned_at.is_none().then(|| batch.data.into()),
+            id: batch.id,
+            block_numbers: batch.block_numbers,
+            status: batch.status,
+            created_at: batch.created_at,
+            submitted_at: batch.submitted_at,
+            celestia_height: batch.celestia_height,
+            retry_count: batch.retry_count,
+            pruned_at: batch.pruned_at,
+            da_commitment: batch.da_commitment,
+            l1_tx_hash: batch.l1_tx_hash,
+            codec: batch.codec,
+            compressed_size: batch.compressed_size,
+            estimated_l1_cost: batch.estimated_l1_cost,
+        }
+    }
+}
+
+/// A page of channels, returned by `batcher_listChannels`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelPage {
+    pub channels: Vec<ChannelInfo>,
+    /// Offset of the next page, `None` if this is the last one.
+    pub next_offset: Option<u64>,
+}
+
+/// The `batcher_` namespace.
+#[rpc(server, namespace = "batcher")]
+pub trait BatcherApi {
+    /// Returns the pending block count, the open channel, the channel backlog by status, the
+    /// op-node sync status and the L1 fees.
+    #[method(name = "status")]
+    fn status(&self) -> RpcResult<BatcherStatus>;
+
+    /// Returns the channel with the given id.
+    #[method(name = "getChannel")]
+    fn get_channel(&self, id: String) -> RpcResult<Option<ChannelInfo>>;
+
+    /// Returns the channel containing the given block.
+    #[method(name = "getChannelForBlock")]
+    fn get_channel_for_block(&self, block_number: u64) -> RpcResult<Option<ChannelInfo>>;
+
+    /// Lists channels in creation order, optionally filtered by status.
+    ///
+    /// Payloads are left out, unless `include_data` is set.
+    #[method(name = "listChannels")]
+    fn list_channels(
+        &self,
+        status: Option<BatchStatus>,
+        offset: Option<u64>,
+        limit: Option<u64>,
+        include_data: Option<bool>,
+    ) -> RpcResult<ChannelPage>;
+
+    /// Force-closes the open channel, returning the id of the created channel.
+    #[method(name = "flush")]
+    fn flush(&self) -> RpcResult<Option<String>>;
+
+    /// Returns the latest L1 SystemConfig, if the batcher follows it.
+    #[method(name = "systemConfig")]
+    fn system_config(&self) -> RpcResult<Option<SystemConfig>>;
+
+    /// Subscribes to batcher events.
+    #[subscription(
+        name = "subscribe" => "subscription",
+        unsubscribe = "unsubscribe",
+        item = crate::events::ChannelEvent
+    )]
+    async fn subscribe(&self, kind: BatcherSubscriptionKind) -> SubscriptionResult;
+}
+
+/// Kinds of `batcher_subscribe` subscriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatcherSubscriptionKind {
+    /// Channel lifecycle events, see [`crate::events::ChannelEvent`].
+    ChannelEvents,
+}
+
+/// Implementation of the `batcher_` namespace.
+pub struct BatcherRpc {
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+}
+
+impl BatcherRpc {
+    pub fn new(channel_builder: Arc<Mutex<ChannelBuilder>>) -> Self {
+        Self { channel_builder }
+    }
+
+    fn with_db<T>(&self, f: impl FnOnce(&DB) -> rusqlite::Result<T>) -> RpcResult<T> {
+        let db = self
+            .channel_builder
+            .lock()
+            .map_err(|_| internal_error("Channel builder lock poisoned"))?
+            .db();
+        let db = db
+            .lock()
+            .map_err(|_| internal_error("Database lock poisoned"))?;
+
+        f(&db).map_err(internal_error)
+    }
+}
+
+#[async_trait]
+impl BatcherApiServer for BatcherRpc {
+    fn status(&self) -> RpcResult<BatcherStatus> {
+        let (pending_blocks, open_channel, sync_status, l1_fees) = {
+            let channel_builder = self
+                .channel_builder
+                .lock()
+                .map_err(|_| internal_error("Channel builder lock poisoned"))?;
+            (
+                channel_builder.pending_blocks().len(),
+                channel_builder.open_channel(),
+                channel_builder.sync_status().cloned(),
+                channel_builder.l1_fees().copied(),
+            )
+        };
+
+        let backlog = self.with_db(|db| {
+            BatchStatus::ALL
+                .into_iter()
+                .map(|status| {
+                    db.get_batch_count_by_status(status)
+                        .map(|count| (status.to_string(), count))
+                })
+                .collect()
+        })?;
+
+        Ok(BatcherStatus {
+            pending_blocks,
+            open_channel,
+            backlog,
+            sync_status,
+            l1_fees,
+        })
+    }
+
+    fn get_channel(&self, id: String) -> RpcResult<Option<ChannelInfo>> {
+        Ok(self.with_db(|db| db.get_batch(&id))?.map(Into::into))
+    }
+
+    fn get_channel_for_block(&self, block_number: u64) -> RpcResult<Option<ChannelInfo>> {
+        Ok(self
+            .with_db(|db| db.get_batch_for_block(block_number))?
+            .map(Into::into))
+    }
+
+    fn list_channels(
+        &self,
+        status: Option<BatchStatus>,
+        offset: Option<u64>,
+        limit: Option<u64>,
+        include_data: Option<bool>,
+    ) -> RpcResult<ChannelPage> {
+        let offset = offset.unwrap_or_default();
+        let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(invalid_params(format!(
+                "invalid limit {limit}, pages hold 1 to {MAX_PAGE_SIZE} channels"
+            )));
+        }
+
+        let channels: Vec<ChannelInfo> = self
+            .with_db(|db| db.list_batches(status, offset, limit))?
+            .into_iter()
+            .map(|batch| {
+                let mut channel = ChannelInfo::from(batch);
+                if !include_data.unwrap_or_default() {
+                    channel.data = None;
+                }
+                channel
+            })
+            .collect();
+
+        let next_offset = (channels.len() as u64 == limit).then(|| offset + channels.len() as u64);
+
+        Ok(ChannelPage {
+            channels,
+            next_offset,
+        })
+    }
+
+    fn flush(&self) -> RpcResult<Option<String>> {
+        let mut channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| internal_error("Channel builder lock poisoned"))?;
+
+        let batch_id = channel_builder.close_channel().map_err(internal_error)?;
+
+        // Submitted by the submitter task
+        if let Some(batch_id) = &batch_id {
+            info!("Flushed open channel into batch {}", batch_id);
+        }
+
+        Ok(batch_id)
+    }
+
+    fn system_config(&self) -> RpcResult<Option<SystemConfig>> {
+        Ok(self
+            .channel_builder
+            .lock()
+            .map_err(|_| internal_error("Channel builder lock poisoned"))?
+            .system_config()
+            .cloned())
+    }
+
+    async fn subscribe(
+        &self,
+        pending: PendingSubscriptionSink,
+        kind: BatcherSubscriptionKind,
+    ) -> SubscriptionResult {
+        let mut events = match kind {
+            BatcherSubscriptionKind::ChannelEvents => self
+                .channel_builder
+                .lock()
+                .map_err(|_| "Channel builder lock poisoned")?
+                .events()
+                .subscribe(),
+        };
+
+        let sink = pending.accept().await?;
+        debug!("New {:?} subscription {:?}", kind, sink.subscription_id());
+
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Channel events subscriber lagged, skipped {} events",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let message = match SubscriptionMessage::new(
+                    sink.method_name(),
+                    sink.subscription_id(),
+                    &event,
+                ) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        error!("Failed to serialize channel event: {}", e);
+                        continue;
+                    }
+                };
+
+                if sink.send(message).await.is_err() {
+                    debug!("Channel events subscriber disconnected");
+                    break;
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// Derivation round-trip check in the `batcher_` namespace.
+#[rpc(server, namespace = "batcher")]
+pub trait BatcherVerifierApi {
+    /// Verifies the channels covering blocks `from..=to` against the node's blocks.
+    ///
+    /// `to` defaults to the latest batched block.
+    #[method(name = "verify")]
+    async fn verify(&self, from: u64, to: Option<u64>) -> RpcResult<VerificationReport>;
+}
+
+/// Implementation of `batcher_verify`.
+pub struct BatcherVerifierRpc<P> {
+    verifier: Verifier<P>,
+}
+
+impl<P> BatcherVerifierRpc<P>
+where
+    P: BlockReader,
+    SealedBlock<P::Block>: DeserializeOwned,
+{
+    pub fn new(
+        provider: P,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        alt_da: Option<AltDaClient>,
+    ) -> Self {
+        Self {
+            verifier: Verifier::new(provider, channel_builder, alt_da),
+        }
+    }
+}
+
+#[async_trait]
+impl<P> BatcherVerifierApiServer for BatcherVerifierRpc<P>
+where
+    P: BlockReader + 'static,
+    SealedBlock<P::Block>: DeserializeOwned,
+{
+    async fn verify(&self, from: u64, to: Option<u64>) -> RpcResult<VerificationReport> {
+        let to = match to {
+            Some(to) => to,
+            None => self
+                .verifier
+                .latest_batched_block()
+                .map_err(internal_error)?
+                .unwrap_or(from),
+        };
+
+        if to < from || to - from >= MAX_VERIFY_RANGE {
+            return Err(invalid_params(format!(
+                "invalid range {from}..={to}, at most {MAX_VERIFY_RANGE} blocks can be verified"
+            )));
+        }
+
+        self.verifier
+            .verify_range(from, to)
+            .await
+            .map_err(internal_error)
+    }
+}
+
+/// Cost and revenue accounting in the `batcher_` namespace.
+#[rpc(server, namespace = "batcher")]
+pub trait BatcherAccountingApi {
+    /// Reports the L1 data fee revenue of blocks `from..=to` against the recorded costs of their
+    /// channels, per channel and per day.
+    ///
+    /// `to` defaults to the latest batched block.
+    #[method(name = "accounting", blocking)]
+    fn accounting(&self, from: u64, to: Option<u64>) -> RpcResult<AccountingReport>;
+}
+
+/// Implementation of `batcher_accounting`.
+pub struct BatcherAccountingRpc<P> {
+    accountant: Accountant<P>,
+}
+
+impl<P> BatcherAccountingRpc<P>
+where
+    P: BlockReader + ChainSpecProvider<ChainSpec: OpHardforks>,
+{
+    pub fn new(provider: P, db: Arc<Mutex<DB>>) -> Self {
+        Self {
+            accountant: Accountant::new(provider, db),
+        }
+    }
+}
+
+impl<P> BatcherAccountingApiServer for BatcherAccountingRpc<P>
+where
+    P: BlockReader + ChainSpecProvider<ChainSpec: OpHardforks> + 'static,
+{
+    fn accounting(&self, from: u64, to: Option<u64>) -> RpcResult<AccountingReport> {
+        let to = match to {
+            Some(to) => to,
+            None => self
+                .accountant
+                .latest_batched_block()
+                .map_err(internal_error)?
+                .unwrap_or(from),
+        };
+
+        if to < from || to - from >= MAX_ACCOUNTING_RANGE {
+            return Err(invalid_params(format!(
+                "invalid range {from}..={to}, at most {MAX_ACCOUNTING_RANGE} blocks can be reported"
+            )));
+        }
+
+        self.accountant.report(from, to).map_err(internal_error)
+    }
+}
+
+/// Batcher controls in the `admin_` namespace, matching op-batcher's admin API.
+#[rpc(server, namespace = "admin")]
+pub trait BatcherAdminApi {
+    /// Resumes submission, and submits the batches that were kept pending while stopped.
+    #[method(name = "startBatcher")]
+    fn start_batcher(&self) -> RpcResult<()>;
+
+    /// Closes the open channel and stops submission, returning once the channels being put are
+    /// posted or requeued. Blocks keep being batched and persisted.
+    #[method(name = "stopBatcher")]
+    async fn stop_batcher(&self) -> RpcResult<()>;
+
+    /// Returns the current flush policy.
+    #[method(name = "getFlushPolicy")]
+    fn get_flush_policy(&self) -> RpcResult<FlushPolicy>;
+
+    /// Replaces the flush policy, returning the applied one.
+    #[method(name = "setFlushPolicy")]
+    fn set_flush_policy(&self, policy: FlushPolicy) -> RpcResult<FlushPolicy>;
+}
+
+/// Implementation of the batcher `admin_` methods.
+pub struct BatcherAdminRpc {
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+}
+
+impl BatcherAdminRpc {
+    pub fn new(channel_builder: Arc<Mutex<ChannelBuilder>>) -> Self {
+        Self { channel_builder }
+    }
+}
+
+#[async_trait]
+impl BatcherAdminApiServer for BatcherAdminRpc {
+    fn start_batcher(&self) -> RpcResult<()> {
+        let mut channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| internal_error("Channel builder lock poisoned"))?;
+
+        if channel_builder.is_running() {
+            return Err(batcher_error("batcher is already running"));
+        }
+
+        // Batches kept pending while stopped are submitted on the next submitter round
+        channel_builder.set_running(true);
+        Ok(())
+    }
+
+    async fn stop_batcher(&self) -> RpcResult<()> {
+        let db = {
+            let mut channel_builder = self
+                .channel_builder
+                .lock()
+                .map_err(|_| internal_error("Channel builder lock poisoned"))?;
+
+            if !channel_builder.is_running() {
+                return Err(batcher_error("batcher is not running"));
+            }
+
+            // Persist the in-flight channel so nothing is lost while stopped
+            if let Some(batch_id) = channel_builder.close_channel().map_err(internal_error)? {
+                info!(
+                    "Persisted open channel into batch {} before stopping",
+                    batch_id
+                );
+            }
+
+            channel_builder.set_running(false);
+            channel_builder.db()
+        };
+
+        // The alt-DA submitter starts no new put once stopped, wait for the ones in flight
+        let started = Instant::now();
+        loop {
+            let submitting = db
+                .lock()
+                .map_err(|_| internal_error("Database lock poisoned"))?
+                .get_batch_count_by_status(BatchStatus::Submitting)
+                .map_err(internal_error)?;
+            if submitting == 0 {
+                return Ok(());
+            }
+            if started.elapsed() >= STOP_DRAIN_TIMEOUT {
+                return Err(batcher_error(format!(
+                    "batcher stopped, but {submitting} channels are still being put"
+                )));
+            }
+
+            debug!("Waiting for {} channels being put", submitting);
+            tokio::time::sleep(STOP_DRAIN_INTERVAL).await;
+        }
+    }
+
+    fn get_flush_policy(&self) -> RpcResult<FlushPolicy> {
+        Ok(self
+            .channel_builder
+            .lock()
+            .map_err(|_| internal_error("Channel builder lock poisoned"))?
+            .flush_policy())
+    }
+
+    fn set_flush_policy(&self, policy: FlushPolicy) -> RpcResult<FlushPolicy> {
+        if policy.batch_size == 0 {
+            return Err(invalid_params("batch size must be greater than 0"));
+        }
+
+        let mut channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| internal_error("Channel builder lock poisoned"))?;
+
+        channel_builder.set_flush_policy(policy);
+        Ok(channel_builder.flush_policy())
+    }
+}
+
+fn internal_error(e: impl Display) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
+}
+
+fn invalid_params(e: impl Display) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INVALID_PARAMS_CODE, e.to_string(), None::<()>)
+}
+
+/// An error of the batcher state, as op-batcher returns through go-ethereum's RPC server.
+fn batcher_error(e: impl Display) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(BATCHER_ERROR_CODE, e.to_string(), None::<()>)
+}
@@ -0,0 +1,541 @@
+//! Keys of the batcher, signing its L1 transactions: the alt-DA commitments the
+//! [`AltDaSubmitter`](crate::alt_da::AltDaSubmitter) posts to the batch inbox.
+//!
+//! Keys are read from encrypted keystores or from an environment variable, or held by a remote
+//! signer speaking `eth_signTransaction`, so plaintext keys never sit in config files. Several
+//! keys can be configured to rotate the batcher key: the one signing is the one the L1
+//! SystemConfig authorizes, followed by the
+//! [`SystemConfigWatcher`](crate::system_config::SystemConfigWatcher).
+
+use alloy_consensus::{SignableTransaction, TxEnvelope, transaction::SignerRecoverable};
+use alloy_eips::eip2718::{Decodable2718, Encodable2718};
+use alloy_primitives::{Address, Bytes};
+use alloy_rpc_types_eth::TransactionRequest;
+use alloy_signer::SignerSync;
+use alloy_signer_local::PrivateKeySigner;
+use clap::Args;
+use jsonrpsee::{
+    core::{RpcResult, async_trait},
+    http_client::{HttpClient, HttpClientBuilder},
+    proc_macros::rpc,
+    server::Server,
+    types::{ErrorObjectOwned, error::INTERNAL_ERROR_CODE},
+};
+use std::{
+    fmt::Display,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+use tracing::info;
+
+/// Environment variable holding the password of the keystores, unless read from a file.
+pub const KEYSTORE_PASSWORD_ENV: &str = "BATCHER_KEYSTORE_PASSWORD";
+
+/// Default address of the mock signer.
+pub const DEFAULT_SIGNER_ADDR: &str = "127.0.0.1:8555";
+
+/// The remote signer API, the `eth_` methods of clef and web3signer the batcher uses.
+#[rpc(server, client, namespace = "eth")]
+pub trait SignerApi {
+    /// Addresses of the keys of the signer.
+    #[method(name = "accounts")]
+    async fn accounts(&self) -> RpcResult<Vec<Address>>;
+
+    /// Signs a transaction, returning it EIP-2718 encoded.
+    #[method(name = "signTransaction")]
+    async fn sign_transaction(&self, request: TransactionRequest) -> RpcResult<Bytes>;
+}
+
+/// A batcher key.
+#[derive(Debug, Clone)]
+pub enum BatcherSigner {
+    /// A key held in memory, read from a keystore or the environment.
+    Local(PrivateKeySigner),
+    /// A key of a remote signer.
+    Remote {
+        address: Address,
+        url: String,
+        client: Box<HttpClient>,
+    },
+}
+
+impl BatcherSigner {
+    pub fn address(&self) -> Address {
+        match self {
+            BatcherSigner::Local(signer) => signer.address(),
+            BatcherSigner::Remote { address, .. } => *address,
+        }
+    }
+
+    /// Signs a transaction from this key, returning it EIP-2718 encoded.
+    pub async fn sign_transaction(&self, mut request: TransactionRequest) -> eyre::Result<Bytes> {
+        let address = self.address();
+        request.from = Some(address);
+
+        match self {
+            BatcherSigner::Local(signer) => sign_local(signer, request),
+            BatcherSigner::Remote { url, client, .. } => {
+                let raw = SignerApiClient::sign_transaction(&**client, request)
+                    .await
+                    .map_err(|e| eyre::eyre!("Failed to sign with {}: {}", url, e))?;
+
+                // Never send what the remote signer returned from another key
+                let signer = TxEnvelope::decode_2718(&mut raw.as_ref())
+                    .map_err(|e| eyre::eyre!("Invalid transaction signed by {}: {}", url, e))?
+                    .recover_signer()
+                    .map_err(|e| eyre::eyre!("Invalid signature from {}: {}", url, e))?;
+                if signer != address {
+                    return Err(eyre::eyre!(
+                        "{} signed with {} instead of {}",
+                        url,
+                        signer,
+                        address
+                    ));
+                }
+
+                Ok(raw)
+            }
+        }
+    }
+}
+
+fn sign_local(signer: &PrivateKeySigner, request: TransactionRequest) -> eyre::Result<Bytes> {
+    let tx = request
+        .build_typed_tx()
+        .map_err(|request| eyre::eyre!("Incomplete transaction request: {:?}", request))?;
+    let signature = signer.sign_hash_sync(&tx.signature_hash())?;
+
+    Ok(TxEnvelope::from(tx.into_signed(signature))
+        .encoded_2718()
+        .into())
+}
+
+/// Reads the keys of encrypted keystores, decrypted with `password`.
+fn load_keystores(paths: &[PathBuf], password: &str) -> eyre::Result<Vec<PrivateKeySigner>> {
+    paths
+        .iter()
+        .map(|path| {
+            PrivateKeySigner::decrypt_keystore(path, password)
+                .map_err(|e| eyre::eyre!("Failed to decrypt keystore {}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+/// Where the batcher keys come from.
+#[derive(Debug, Clone, Default, Args)]
+pub struct KeySources {
+    /// Encrypted keystore of a batcher key. Can be repeated to rotate keys.
+    #[arg(long = "batcher.keystore", value_name = "PATH")]
+    pub keystores: Vec<PathBuf>,
+
+    /// File holding the keystore password, read from `BATCHER_KEYSTORE_PASSWORD` when unset.
+    #[arg(long = "batcher.keystore-password-file", value_name = "PATH")]
+    pub keystore_password_file: Option<PathBuf>,
+
+    /// Environment variable holding a hex encoded batcher key.
+    #[arg(long = "batcher.private-key-env", value_name = "VAR")]
+    pub private_key_env: Option<String>,
+
+    /// Remote signer URL, all of its `eth_accounts` are batcher keys.
+    #[arg(long = "batcher.remote-signer", value_name = "URL")]
+    pub remote_signer: Option<String>,
+}
+
+impl KeySources {
+    fn keystore_password(&self) -> eyre::Result<String> {
+        match &self.keystore_password_file {
+            Some(path) => read_password(path),
+            None => std::env::var(KEYSTORE_PASSWORD_ENV).map_err(|_| {
+                eyre::eyre!(
+                    "Keystores need a password, set with --batcher.keystore-password-file or {}",
+                    KEYSTORE_PASSWORD_ENV
+                )
+            }),
+        }
+    }
+
+    /// Reads the keystore and environment keys.
+    pub fn load_local(&self) -> eyre::Result<Vec<PrivateKeySigner>> {
+        let mut signers = Vec::new();
+        if !self.keystores.is_empty() {
+            signers.extend(load_keystores(&self.keystores, &self.keystore_password()?)?);
+        }
+
+        if let Some(var) = &self.private_key_env {
+            let key = std::env::var(var)
+                .map_err(|_| eyre::eyre!("Batcher key variable {} is not set", var))?;
+            let signer = key
+                .trim()
+                .parse::<PrivateKeySigner>()
+                .map_err(|e| eyre::eyre!("Invalid batcher key in {}: {}", var, e))?;
+            signers.push(signer);
+        }
+
+        Ok(signers)
+    }
+
+    /// Loads every configured key, `None` if none is.
+    pub async fn load(&self) -> eyre::Result<Option<KeyRing>> {
+        let mut signers: Vec<_> = self
+            .load_local()?
+            .into_iter()
+            .map(BatcherSigner::Local)
+            .collect();
+
+        if let Some(url) = &self.remote_signer {
+            let client = HttpClientBuilder::default()
+                .build(url)
+                .map_err(|e| eyre::eyre!("Failed to create signer client for {}: {}", url, e))?;
+            let accounts = SignerApiClient::accounts(&client)
+                .await
+                .map_err(|e| eyre::eyre!("Failed to list accounts of {}: {}", url, e))?;
+
+            signers.extend(accounts.into_iter().map(|address| BatcherSigner::Remote {
+                address,
+                url: url.clone(),
+                client: Box::new(client.clone()),
+            }));
+        }
+
+        if signers.is_empty() {
+            return Ok(None);
+        }
+        KeyRing::new(signers).map(Some)
+    }
+}
+
+fn read_password(path: &Path) -> eyre::Result<String> {
+    let password = std::fs::read_to_string(path)
+        .map_err(|e| eyre::eyre!("Failed to read password file {}: {}", path.display(), e))?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// The batcher keys, and the address the SystemConfig authorizes.
+#[derive(Debug)]
+pub struct KeyRing {
+    signers: Vec<BatcherSigner>,
+    /// Batcher address L1 authorizes, the first key until read from the SystemConfig.
+    authorized: RwLock<Address>,
+}
+
+impl KeyRing {
+    pub fn new(signers: Vec<BatcherSigner>) -> eyre::Result<Self> {
+        let first = signers
+            .first()
+            .ok_or_else(|| eyre::eyre!("No batcher key configured"))?
+            .address();
+        for (i, signer) in signers.iter().enumerate() {
+            if signers[..i]
+                .iter()
+                .any(|other| other.address() == signer.address())
+            {
+                return Err(eyre::eyre!(
+                    "Batcher key {} is configured twice",
+                    signer.address()
+                ));
+            }
+        }
+
+        Ok(Self {
+            signers,
+            authorized: RwLock::new(first),
+        })
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.signers.iter().map(BatcherSigner::address).collect()
+    }
+
+    pub fn authorized(&self) -> Address {
+        *self.authorized.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sets the batcher address L1 authorizes, returning whether it changed.
+    pub fn set_authorized(&self, address: Address) -> bool {
+        let mut authorized = self.authorized.write().unwrap_or_else(|e| e.into_inner());
+        let changed = *authorized != address;
+        *authorized = address;
+        changed
+    }
+
+    /// The key of the authorized batcher address, if configured.
+    pub fn active(&self) -> Option<&BatcherSigner> {
+        let authorized = self.authorized();
+        self.signers
+            .iter()
+            .find(|signer| signer.address() == authorized)
+    }
+
+    /// Signs a transaction with the authorized key, failing if it isn't configured.
+    pub async fn sign_transaction(&self, request: TransactionRequest) -> eyre::Result<Bytes> {
+        let signer = self.active().ok_or_else(|| {
+            eyre::eyre!(
+                "No key configured for the authorized batcher {}",
+                self.authorized()
+            )
+        })?;
+        signer.sign_transaction(request).await
+    }
+}
+
+/// Remote signer API served from local keys.
+struct LocalSigner {
+    signers: Vec<PrivateKeySigner>,
+}
+
+#[async_trait]
+impl SignerApiServer for LocalSigner {
+    async fn accounts(&self) -> RpcResult<Vec<Address>> {
+        Ok(self.signers.iter().map(PrivateKeySigner::address).collect())
+    }
+
+    async fn sign_transaction(&self, request: TransactionRequest) -> RpcResult<Bytes> {
+        let from = request
+            .from
+            .ok_or_else(|| signer_error("Transaction has no from address"))?;
+        let signer = self
+            .signers
+            .iter()
+            .find(|signer| signer.address() == from)
+            .ok_or_else(|| signer_error(format!("Unknown account {from}")))?;
+
+        sign_local(signer, request).map_err(signer_error)
+    }
+}
+
+fn signer_error(e: impl Display) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
+}
+
+/// Serve `eth_signTransaction` from local keys, mocking a remote signer.
+#[derive(Debug, Args)]
+pub struct MockSignerCommand {
+    /// Address to listen on.
+    #[arg(long, default_value = DEFAULT_SIGNER_ADDR)]
+    pub addr: SocketAddr,
+
+    #[command(flatten)]
+    pub keys: KeySources,
+}
+
+impl MockSignerCommand {
+    pub fn execute(self) -> eyre::Result<()> {
+        if self.keys.remote_signer.is_some() {
+            return Err(eyre::eyre!("The mock signer only serves local keys"));
+        }
+        let signers = self.keys.load_local()?;
+        if signers.is_empty() {
+            return Err(eyre::eyre!(
+                "No key to serve, set --batcher.keystore or --batcher.private-key-env"
+            ));
+        }
+
+        tokio::runtime::Runtime::new()?.block_on(async move {
+            let server = Server::builder().build(self.addr).await?;
+            info!(
+                "Serving {:?} on {}",
+                signers
+                    .iter()
+                    .map(PrivateKeySigner::address)
+                    .collect::<Vec<_>>(),
+                server.local_addr()?
+            );
+
+            let handle = server.start(LocalSigner { signers }.into_rpc());
+            handle.stopped().await;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{U256, address};
+
+    /// Key of the keystore in `testdata/keystore.json`, encrypted with [`KEYSTORE_PASSWORD`].
+    const KEY: &str = "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+    const KEY_ADDRESS: Address = address!("0x70997970C51812dc3A010C7d01b50e0d17dc79C8");
+    const KEYSTORE_PASSWORD: &str = "batcher";
+
+    fn keystore() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/keystore.json")
+    }
+
+    /// Writes `password` to a temporary file, removed when dropped.
+    fn password_file(password: &str) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), format!("{password}\n")).unwrap();
+        file
+    }
+
+    /// Sets a variable only the calling test reads.
+    fn set_key_var(var: &str, key: &str) {
+        // SAFETY: each test sets its own variable, and the std environment accesses are
+        // serialized
+        unsafe { std::env::set_var(var, key) };
+    }
+
+    fn transfer() -> TransactionRequest {
+        let mut request = TransactionRequest::default()
+            .to(Address::ZERO)
+            .value(U256::from(1))
+            .nonce(0)
+            .gas_limit(21_000)
+            .max_fee_per_gas(2_000_000_000)
+            .max_priority_fee_per_gas(1_000_000);
+        request.chain_id = Some(11155111);
+        request
+    }
+
+    fn recover(raw: &Bytes) -> Address {
+        TxEnvelope::decode_2718(&mut raw.as_ref())
+            .unwrap()
+            .recover_signer()
+            .unwrap()
+    }
+
+    /// Serves the signer API on a local port, returning its URL.
+    async fn serve(signer: impl SignerApiServer) -> String {
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let handle = server.start(signer.into_rpc());
+        tokio::spawn(handle.stopped());
+        url
+    }
+
+    /// Lists one account, but signs with another key.
+    struct WrongKeySigner {
+        account: Address,
+        signer: PrivateKeySigner,
+    }
+
+    #[async_trait]
+    impl SignerApiServer for WrongKeySigner {
+        async fn accounts(&self) -> RpcResult<Vec<Address>> {
+            Ok(vec![self.account])
+        }
+
+        async fn sign_transaction(&self, request: TransactionRequest) -> RpcResult<Bytes> {
+            sign_local(&self.signer, request).map_err(signer_error)
+        }
+    }
+
+    #[test]
+    fn loads_keystore() {
+        let password = password_file(KEYSTORE_PASSWORD);
+        let keys = KeySources {
+            keystores: vec![keystore()],
+            keystore_password_file: Some(password.path().to_path_buf()),
+            ..Default::default()
+        };
+        let signers = keys.load_local().unwrap();
+        assert_eq!(signers.len(), 1);
+        assert_eq!(signers[0].address(), KEY_ADDRESS);
+
+        let wrong_password = password_file("wrong");
+        let keys = KeySources {
+            keystore_password_file: Some(wrong_password.path().to_path_buf()),
+            ..keys
+        };
+        let err = keys.load_local().unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to decrypt keystore"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn loads_key_from_env() {
+        set_key_var("FLASH_BATCHER_TEST_KEY", KEY);
+        let keys = KeySources {
+            private_key_env: Some("FLASH_BATCHER_TEST_KEY".to_string()),
+            ..Default::default()
+        };
+
+        let signers = keys.load_local().unwrap();
+        assert_eq!(signers.len(), 1);
+        assert_eq!(signers[0].address(), KEY_ADDRESS);
+    }
+
+    #[test]
+    fn rejects_bad_keys() {
+        set_key_var("FLASH_BATCHER_TEST_BAD_KEY", "0x1234");
+        let keys = KeySources {
+            private_key_env: Some("FLASH_BATCHER_TEST_BAD_KEY".to_string()),
+            ..Default::default()
+        };
+        let err = keys.load_local().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Invalid batcher key in FLASH_BATCHER_TEST_BAD_KEY"),
+            "unexpected error: {err}"
+        );
+
+        let keys = KeySources {
+            private_key_env: Some("FLASH_BATCHER_TEST_UNSET_KEY".to_string()),
+            ..Default::default()
+        };
+        assert!(keys.load_local().is_err());
+    }
+
+    #[tokio::test]
+    async fn signs_with_the_authorized_local_key() {
+        let signer = KEY.parse::<PrivateKeySigner>().unwrap();
+        let other = PrivateKeySigner::random();
+        let keys = KeyRing::new(vec![
+            BatcherSigner::Local(signer),
+            BatcherSigner::Local(other.clone()),
+        ])
+        .unwrap();
+
+        let raw = keys.sign_transaction(transfer()).await.unwrap();
+        assert_eq!(recover(&raw), KEY_ADDRESS);
+
+        // Rotated
+        keys.set_authorized(other.address());
+        let raw = keys.sign_transaction(transfer()).await.unwrap();
+        assert_eq!(recover(&raw), other.address());
+
+        keys.set_authorized(Address::ZERO);
+        assert!(keys.sign_transaction(transfer()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn signs_with_the_mock_signer() {
+        let signers = vec![KEY.parse::<PrivateKeySigner>().unwrap()];
+        let keys = KeySources {
+            remote_signer: Some(serve(LocalSigner { signers }).await),
+            ..Default::default()
+        };
+
+        let keys = keys.load().await.unwrap().unwrap();
+        assert_eq!(keys.addresses(), vec![KEY_ADDRESS]);
+        assert!(matches!(keys.active(), Some(BatcherSigner::Remote { .. })));
+
+        let raw = keys.sign_transaction(transfer()).await.unwrap();
+        assert_eq!(recover(&raw), KEY_ADDRESS);
+    }
+
+    #[tokio::test]
+    async fn rejects_transactions_signed_by_another_key() {
+        let url = serve(WrongKeySigner {
+            account: KEY_ADDRESS,
+            signer: PrivateKeySigner::random(),
+        })
+        .await;
+        let keys = KeySources {
+            remote_signer: Some(url),
+            ..Default::default()
+        };
+
+        let keys = keys.load().await.unwrap().unwrap();
+        let err = keys.sign_transaction(transfer()).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("instead of {KEY_ADDRESS}")),
+            "unexpected error: {err}"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Submission of the pending channels when no alt-DA server is configured.
+//!
+//! Channels are submitted from a background task, never from the ExEx nor the RPC handlers, so
+//! closing a channel doesn't wait on submission. The batcher doesn't send its own L1
+//! transactions yet: submitting a channel marks it submitted, and it's never reported as
+//! included. Concurrent submission, with `--batcher.max-in-flight`, only applies to the alt-DA
+//! submitter, the flag is rejected without `--batcher.alt-da-url`. Submission holds the database
+//! lock from reading the pending channels to marking them, so concurrent calls still submit
+//! each channel once, in order.
+
+use crate::{
+    channel_builder::ChannelBuilder, db::BatchStatus, events::ChannelEvent, l1_cost::posted_size,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// Interval between checks for pending channels.
+const SUBMISSION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Submits pending channels, in order.
+pub struct BatchSubmitter {
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+}
+
+impl BatchSubmitter {
+    pub fn new(channel_builder: Arc<Mutex<ChannelBuilder>>) -> Self {
+        Self { channel_builder }
+    }
+
+    /// Submits the pending channels, stopping at the first one held so channels stay ordered.
+    pub fn submit_pending(&self) -> eyre::Result<()> {
+        let (db, events, metrics, l1_fees, da_type, l1_budget) = {
+            let channel_builder = self
+                .channel_builder
+                .lock()
+                .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+            if !channel_builder.is_running() {
+                return Ok(());
+            }
+
+            if let Some(batcher) = channel_builder.unauthorized_batcher() {
+                warn!(
+                    "No key configured for the authorized batcher {}, holding pending batches",
+                    batcher
+                );
+                return Ok(());
+            }
+
+            (
+                channel_builder.db(),
+                channel_builder.events().clone(),
+                channel_builder.metrics().channel.clone(),
+                channel_builder.l1_fees().copied(),
+                channel_builder.da_type(),
+                channel_builder.l1_budget().copied(),
+            )
+        };
+
+        let db = db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?;
+
+        let batches = db
+            .get_pending_batches()
+            .map_err(|e| eyre::eyre!("Failed to get pending batches: {}", e))?;
+        if batches.is_empty() {
+            return Ok(());
+        }
+
+        debug!("Found {} pending batches to submit", batches.len());
+
+        for batch in batches {
+            let size = posted_size(&batch);
+            let cost = l1_fees.map(|fees| fees.estimate(size, da_type));
+
+            if let Some(budget) = &l1_budget
+                && let Some(reason) = budget.hold_reason(&db, &batch, cost.as_ref(), size)?
+            {
+                // Channels are submitted in order, the ones after a held channel are held too
+                warn!("Holding batch {} over the L1 budget: {}", batch.id, reason);
+                break;
+            }
+
+            debug!(
+                "Processing batch: {} with {} blocks",
+                batch.id,
+                batch.block_numbers.len()
+            );
+
+            events.emit(ChannelEvent::FrameSubmitted {
+                channel_id: batch.id.clone(),
+                frame_number: 0,
+            });
+
+            if let Err(e) = db.update_batch_status(&batch.id, BatchStatus::Submitted) {
+                error!("Failed to update batch status for {}: {}", batch.id, e);
+                metrics.submission_failures.increment(1);
+                events.emit(ChannelEvent::FrameFailed {
+                    channel_id: batch.id,
+                    frame_number: 0,
+                    error: e.to_string(),
+                });
+                // Later batches must not be submitted ahead of this one
+                break;
+            }
+
+            metrics.bytes_submitted.increment(batch.data.len() as u64);
+            if let Some(cost) = &cost
+                && let Err(e) = db.record_estimated_l1_cost(&batch.id, cost)
+            {
+                error!("Failed to record L1 fee of batch {}: {}", batch.id, e);
+            }
+
+            info!("Successfully submitted batch: {}", batch.id);
+        }
+
+        Ok(())
+    }
+
+    /// Submits pending channels every second, forever.
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(SUBMISSION_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.submit_pending() {
+                error!("Failed to submit batches: {}", e);
+            }
+        }
+    }
+}
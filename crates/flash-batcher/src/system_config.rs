@@ -0,0 +1,551 @@
+//! The L1 SystemConfig contract of the chain, `l1_system_config_address` in `rollup.json`.
+//!
+//! `rollup.json` only holds the genesis system config. The current one is read from the
+//! contract once, then followed through its `ConfigUpdate` events, as op-node derives it.
+
+use crate::{channel_builder::ChannelBuilder, l1::L1Client};
+use alloy_primitives::{Address, B256, Bytes, b256};
+use alloy_rpc_types_eth::Filter;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// Topic of `ConfigUpdate(uint256 indexed version, UpdateType indexed updateType, bytes data)`.
+pub const CONFIG_UPDATE_TOPIC: B256 =
+    b256!("0x1d2b0bda21d56b8bd12d4f94ebacffdfb35f5e226f84b461103bb8beab6353be");
+
+/// Version of the `ConfigUpdate` events.
+const CONFIG_UPDATE_VERSION: B256 = B256::ZERO;
+
+const UPDATE_BATCHER: u8 = 0;
+const UPDATE_FEE_SCALARS: u8 = 1;
+const UPDATE_GAS_LIMIT: u8 = 2;
+const UPDATE_UNSAFE_BLOCK_SIGNER: u8 = 3;
+const UPDATE_EIP_1559_PARAMS: u8 = 4;
+const UPDATE_OPERATOR_FEE_PARAMS: u8 = 5;
+
+const BATCHER_HASH_SELECTOR: [u8; 4] = [0xe8, 0x1b, 0x2c, 0x6d];
+const OVERHEAD_SELECTOR: [u8; 4] = [0x0c, 0x18, 0xc1, 0x62];
+const SCALAR_SELECTOR: [u8; 4] = [0xf4, 0x5e, 0x65, 0xd8];
+const GAS_LIMIT_SELECTOR: [u8; 4] = [0xf6, 0x80, 0x16, 0xb7];
+const UNSAFE_BLOCK_SIGNER_SELECTOR: [u8; 4] = [0x1f, 0xd1, 0x9e, 0xe1];
+/// Since Holocene.
+const EIP_1559_DENOMINATOR_SELECTOR: [u8; 4] = [0xd2, 0x20, 0xa9, 0xe0];
+/// Since Holocene.
+const EIP_1559_ELASTICITY_SELECTOR: [u8; 4] = [0xc9, 0xff, 0x2d, 0x16];
+/// Since Isthmus.
+const OPERATOR_FEE_SCALAR_SELECTOR: [u8; 4] = [0x4d, 0x5d, 0x9a, 0x2a];
+/// Since Isthmus.
+const OPERATOR_FEE_CONSTANT_SELECTOR: [u8; 4] = [0x16, 0xd3, 0xbc, 0x7f];
+
+/// Version byte of Ecotone fee scalars.
+const ECOTONE_SCALAR_VERSION: u8 = 1;
+
+/// Maximum number of L1 blocks whose logs are fetched at once.
+const MAX_LOG_RANGE: u64 = 1_000;
+
+/// The system config of the chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemConfig {
+    /// Address of the batcher, from the batcher hash.
+    pub batcher_address: Address,
+    /// Unused since Ecotone.
+    pub overhead: B256,
+    /// Since Ecotone, the version byte, then the blob base fee and base fee scalars as the last
+    /// 8 bytes.
+    pub scalar: B256,
+    pub gas_limit: u64,
+    pub unsafe_block_signer: Address,
+    /// Holocene EIP-1559 parameters, zero when unset.
+    pub eip1559_denominator: u32,
+    pub eip1559_elasticity: u32,
+    /// Isthmus operator fee parameters, zero when unset.
+    pub operator_fee_scalar: u32,
+    pub operator_fee_constant: u64,
+    /// L1 block this config is current at.
+    pub l1_block: u64,
+}
+
+impl SystemConfig {
+    /// Reads the config of the SystemConfig at `address` on L1 block `block`.
+    pub async fn read(l1: &L1Client, address: Address, block: u64) -> eyre::Result<Self> {
+        // `None` when the getter reverts
+        let call = async |selector: [u8; 4]| -> eyre::Result<Option<B256>> {
+            let Some(output) = l1
+                .try_call(address, Bytes::copy_from_slice(&selector), block)
+                .await?
+            else {
+                return Ok(None);
+            };
+            if output.len() != 32 {
+                return Err(eyre::eyre!(
+                    "SystemConfig {} returned {} bytes for selector 0x{}",
+                    address,
+                    output.len(),
+                    alloy_primitives::hex::encode(selector)
+                ));
+            }
+            Ok(Some(B256::from_slice(&output)))
+        };
+        let word = async |selector: [u8; 4]| -> eyre::Result<B256> {
+            call(selector).await?.ok_or_else(|| {
+                eyre::eyre!(
+                    "SystemConfig {} reverted for selector 0x{}",
+                    address,
+                    alloy_primitives::hex::encode(selector)
+                )
+            })
+        };
+        // Getters of later hardforks, reverting on contracts predating them
+        let optional_word = async |selector| -> eyre::Result<B256> {
+            Ok(call(selector).await?.unwrap_or_default())
+        };
+
+        Ok(Self {
+            batcher_address: Address::from_word(word(BATCHER_HASH_SELECTOR).await?),
+            overhead: word(OVERHEAD_SELECTOR).await?,
+            scalar: word(SCALAR_SELECTOR).await?,
+            gas_limit: u64_at(word(GAS_LIMIT_SELECTOR).await?, 24),
+            unsafe_block_signer: Address::from_word(word(UNSAFE_BLOCK_SIGNER_SELECTOR).await?),
+            eip1559_denominator: u32_at(optional_word(EIP_1559_DENOMINATOR_SELECTOR).await?, 28),
+            eip1559_elasticity: u32_at(optional_word(EIP_1559_ELASTICITY_SELECTOR).await?, 28),
+            operator_fee_scalar: u32_at(optional_word(OPERATOR_FEE_SCALAR_SELECTOR).await?, 28),
+            operator_fee_constant: u64_at(optional_word(OPERATOR_FEE_CONSTANT_SELECTOR).await?, 24),
+            l1_block: block,
+        })
+    }
+
+    /// Ecotone L1 base fee scalar, `None` before the scalar is Ecotone encoded.
+    pub fn base_fee_scalar(&self) -> Option<u32> {
+        (self.scalar[0] == ECOTONE_SCALAR_VERSION).then(|| u32_at(self.scalar, 28))
+    }
+
+    /// Ecotone L1 blob base fee scalar, `None` before the scalar is Ecotone encoded.
+    pub fn blob_base_fee_scalar(&self) -> Option<u32> {
+        (self.scalar[0] == ECOTONE_SCALAR_VERSION).then(|| u32_at(self.scalar, 24))
+    }
+
+    /// Applies a `ConfigUpdate` event with the given topics and data.
+    pub fn apply_update(&mut self, topics: &[B256], data: &[u8]) -> eyre::Result<()> {
+        let [topic, version, update_type] = topics else {
+            return Err(eyre::eyre!(
+                "ConfigUpdate must have 3 topics, not {}",
+                topics.len()
+            ));
+        };
+        if *topic != CONFIG_UPDATE_TOPIC {
+            return Err(eyre::eyre!("Not a ConfigUpdate event: {}", topic));
+        }
+        if *version != CONFIG_UPDATE_VERSION {
+            return Err(eyre::eyre!("Unknown ConfigUpdate version {}", version));
+        }
+
+        let payload = abi_bytes(data)?;
+        let word = |index: usize| -> eyre::Result<&[u8]> {
+            payload
+                .get(index * 32..(index + 1) * 32)
+                .ok_or_else(|| eyre::eyre!("ConfigUpdate data too short: {} bytes", payload.len()))
+        };
+
+        match update_type[31] {
+            UPDATE_BATCHER => {
+                self.batcher_address = Address::from_word(B256::from_slice(word(0)?));
+            }
+            UPDATE_FEE_SCALARS => {
+                let (overhead, scalar) = (word(0)?, word(1)?);
+                self.overhead = B256::from_slice(overhead);
+                self.scalar = B256::from_slice(scalar);
+            }
+            UPDATE_GAS_LIMIT => self.gas_limit = u64_at(word(0)?, 24),
+            UPDATE_UNSAFE_BLOCK_SIGNER => {
+                self.unsafe_block_signer = Address::from_word(B256::from_slice(word(0)?));
+            }
+            // `uint256(denominator) << 32 | elasticity`
+            UPDATE_EIP_1559_PARAMS => {
+                let params = word(0)?;
+                self.eip1559_denominator = u32_at(params, 24);
+                self.eip1559_elasticity = u32_at(params, 28);
+            }
+            // `uint256(scalar) << 64 | constant`
+            UPDATE_OPERATOR_FEE_PARAMS => {
+                let params = word(0)?;
+                self.operator_fee_scalar = u32_at(params, 20);
+                self.operator_fee_constant = u64_at(params, 24);
+            }
+            update_type => debug!("Ignoring unknown ConfigUpdate type {}", update_type),
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes the ABI encoding of a single `bytes` value.
+fn abi_bytes(data: &[u8]) -> eyre::Result<&[u8]> {
+    let too_short = || eyre::eyre!("ABI bytes too short: {} bytes", data.len());
+    let offset = data.get(..32).ok_or_else(too_short)?;
+    let offset = usize::try_from(u64_at(offset, 24)).map_err(|_| too_short())?;
+    let start = offset.checked_add(32).ok_or_else(too_short)?;
+    let length = data.get(offset..start).ok_or_else(too_short)?;
+    let length = usize::try_from(u64_at(length, 24)).map_err(|_| too_short())?;
+
+    data.get(start..start.checked_add(length).ok_or_else(too_short)?)
+        .ok_or_else(too_short)
+}
+
+fn u32_at(data: impl AsRef<[u8]>, offset: usize) -> u32 {
+    u32::from_be_bytes(
+        data.as_ref()[offset..offset + 4]
+            .try_into()
+            .expect("4 bytes"),
+    )
+}
+
+fn u64_at(data: impl AsRef<[u8]>, offset: usize) -> u64 {
+    u64::from_be_bytes(
+        data.as_ref()[offset..offset + 8]
+            .try_into()
+            .expect("8 bytes"),
+    )
+}
+
+/// Follows the SystemConfig, recording its config in the channel builder and switching the
+/// batcher key to the one it authorizes.
+pub struct SystemConfigWatcher {
+    l1: L1Client,
+    address: Address,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    interval: Duration,
+    /// L1 blocks an update must be buried under before it's applied.
+    confirmations: u64,
+    config: SystemConfig,
+}
+
+impl SystemConfigWatcher {
+    /// Reads the config of the SystemConfig at `address` as of `confirmations` L1 blocks ago,
+    /// and records it.
+    pub async fn new(
+        l1: L1Client,
+        address: Address,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        interval: Duration,
+        confirmations: u64,
+    ) -> eyre::Result<Self> {
+        let block = l1.block_number().await?.saturating_sub(confirmations);
+        let config = SystemConfig::read(&l1, address, block).await?;
+        info!(
+            "SystemConfig {} authorizes batcher {} at L1 block {}",
+            address, config.batcher_address, block
+        );
+
+        let watcher = Self {
+            l1,
+            address,
+            channel_builder,
+            interval,
+            confirmations,
+            config,
+        };
+        watcher.record(None)?;
+        Ok(watcher)
+    }
+
+    /// Records the config in the channel builder, and follows the authorized batcher if it
+    /// changed from `previous`.
+    fn record(&self, previous: Option<&SystemConfig>) -> eyre::Result<()> {
+        let mut channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+
+        let batcher = self.config.batcher_address;
+        if let Some(keys) = channel_builder.keys()
+            && keys.set_authorized(batcher)
+        {
+            match keys.active() {
+                Some(_) => info!(
+                    "Batcher key rotated from {:?} to {}",
+                    previous.map(|config| config.batcher_address),
+                    batcher
+                ),
+                None => warn!(
+                    "SystemConfig authorizes batcher {}, but only {:?} are configured, submission stops",
+                    batcher,
+                    keys.addresses()
+                ),
+            }
+        }
+
+        channel_builder.set_system_config(self.config.clone());
+        Ok(())
+    }
+
+    /// Applies the `ConfigUpdate` events of the confirmed L1 blocks since the last poll.
+    ///
+    /// Invalid updates are skipped, as op-node does when deriving the config.
+    pub async fn poll(&mut self) -> eyre::Result<()> {
+        let confirmed = self
+            .l1
+            .block_number()
+            .await?
+            .saturating_sub(self.confirmations);
+        if confirmed <= self.config.l1_block {
+            return Ok(());
+        }
+        let from = self.config.l1_block + 1;
+        let to = confirmed.min(self.config.l1_block + MAX_LOG_RANGE);
+
+        let filter = Filter::new()
+            .address(self.address)
+            .event_signature(CONFIG_UPDATE_TOPIC)
+            .from_block(from)
+            .to_block(to);
+        let logs = self.l1.logs(filter).await?;
+
+        let previous = self.config.clone();
+        let mut applied = 0;
+        for log in &logs {
+            match self.config.apply_update(log.topics(), &log.data().data) {
+                Ok(()) => applied += 1,
+                Err(e) => warn!(
+                    "Skipping invalid ConfigUpdate in L1 block {:?}, transaction {:?}: {}",
+                    log.block_number, log.transaction_hash, e
+                ),
+            }
+        }
+        self.config.l1_block = to;
+
+        if applied > 0 {
+            info!(
+                "Applied {} SystemConfig updates up to L1 block {}",
+                applied, to
+            );
+        }
+        self.record(Some(&previous))
+    }
+
+    /// Polls on every tick of the configured interval, forever.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.poll().await {
+                // Updates are applied again from the last recorded block on the next poll
+                error!("Failed to follow SystemConfig {}: {}", self.address, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, hex};
+
+    /// Topics of a `ConfigUpdate` event of type `update_type`.
+    fn topics(update_type: u8) -> [B256; 3] {
+        [
+            CONFIG_UPDATE_TOPIC,
+            CONFIG_UPDATE_VERSION,
+            B256::with_last_byte(update_type),
+        ]
+    }
+
+    fn apply(update_type: u8, data: &[u8]) -> eyre::Result<SystemConfig> {
+        let mut config = SystemConfig::default();
+        config.apply_update(&topics(update_type), data)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn applies_batcher_update() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020" // offset
+            "0000000000000000000000000000000000000000000000000000000000000020" // length
+            "000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb" // batcher hash
+        );
+
+        assert_eq!(
+            apply(UPDATE_BATCHER, &data).unwrap().batcher_address,
+            address!("0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb")
+        );
+    }
+
+    #[test]
+    fn applies_fee_scalars_update() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000040"
+            "00000000000000000000000000000000000000000000000000000000000000bc" // overhead
+            "010000000000000000000000000000000000000000000000000c5fc500000558" // scalar
+        );
+
+        let config = apply(UPDATE_FEE_SCALARS, &data).unwrap();
+        assert_eq!(config.overhead, B256::with_last_byte(0xbc));
+        assert_eq!(config.base_fee_scalar(), Some(1368));
+        assert_eq!(config.blob_base_fee_scalar(), Some(810_949));
+    }
+
+    #[test]
+    fn pre_ecotone_scalar_has_no_fee_scalars() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000040"
+            "00000000000000000000000000000000000000000000000000000000000000bc"
+            "00000000000000000000000000000000000000000000000000000000000a6fe0"
+        );
+
+        let config = apply(UPDATE_FEE_SCALARS, &data).unwrap();
+        assert_eq!(config.base_fee_scalar(), None);
+        assert_eq!(config.blob_base_fee_scalar(), None);
+    }
+
+    #[test]
+    fn applies_gas_limit_update() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000001c9c380" // gas limit
+        );
+
+        assert_eq!(
+            apply(UPDATE_GAS_LIMIT, &data).unwrap().gas_limit,
+            30_000_000
+        );
+    }
+
+    #[test]
+    fn applies_unsafe_block_signer_update() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "000000000000000000000000cccccccccccccccccccccccccccccccccccccccc" // signer
+        );
+
+        assert_eq!(
+            apply(UPDATE_UNSAFE_BLOCK_SIGNER, &data)
+                .unwrap()
+                .unsafe_block_signer,
+            address!("0xcccccccccccccccccccccccccccccccccccccccc")
+        );
+    }
+
+    #[test]
+    fn applies_eip_1559_params_update() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "000000000000000000000000000000000000000000000000000000fa00000006" // params
+        );
+
+        let config = apply(UPDATE_EIP_1559_PARAMS, &data).unwrap();
+        assert_eq!(config.eip1559_denominator, 250);
+        assert_eq!(config.eip1559_elasticity, 6);
+    }
+
+    #[test]
+    fn applies_operator_fee_params_update() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000007d000000000000001f4" // params
+        );
+
+        let config = apply(UPDATE_OPERATOR_FEE_PARAMS, &data).unwrap();
+        assert_eq!(config.operator_fee_scalar, 2000);
+        assert_eq!(config.operator_fee_constant, 500);
+    }
+
+    #[test]
+    fn ignores_unknown_update_types() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+
+        assert_eq!(apply(6, &data).unwrap(), SystemConfig::default());
+    }
+
+    #[test]
+    fn rejects_other_events() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000001c9c380"
+        );
+        let mut config = SystemConfig::default();
+
+        let mut other_topic = topics(UPDATE_GAS_LIMIT);
+        other_topic[0] = B256::with_last_byte(1);
+        assert!(config.apply_update(&other_topic, &data).is_err());
+
+        let mut other_version = topics(UPDATE_GAS_LIMIT);
+        other_version[1] = B256::with_last_byte(1);
+        assert!(config.apply_update(&other_version, &data).is_err());
+
+        assert!(
+            config
+                .apply_update(&topics(UPDATE_GAS_LIMIT)[..2], &data)
+                .is_err()
+        );
+        assert_eq!(config, SystemConfig::default());
+    }
+
+    #[test]
+    fn rejects_short_payloads() {
+        // A fee scalars update with only the overhead
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "00000000000000000000000000000000000000000000000000000000000000bc"
+        );
+
+        assert!(apply(UPDATE_FEE_SCALARS, &data).is_err());
+    }
+
+    #[test]
+    fn decodes_abi_bytes() {
+        let data = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000003"
+            "0102030000000000000000000000000000000000000000000000000000000000"
+        );
+
+        assert_eq!(abi_bytes(&data).unwrap(), [1, 2, 3]);
+        assert_eq!(
+            abi_bytes(&[]).unwrap_err().to_string(),
+            "ABI bytes too short: 0 bytes"
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_abi_bytes() {
+        let length_past_end = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000021"
+            "0102030000000000000000000000000000000000000000000000000000000000"
+        );
+        let offset_past_end = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000040"
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        let overflowing_offset = hex!(
+            "000000000000000000000000000000000000000000000000ffffffffffffffff"
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        let overflowing_length = hex!(
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "000000000000000000000000000000000000000000000000ffffffffffffffff"
+        );
+
+        assert!(abi_bytes(&length_past_end).is_err());
+        assert!(abi_bytes(&offset_past_end).is_err());
+        assert!(abi_bytes(&overflowing_offset).is_err());
+        assert!(abi_bytes(&overflowing_length).is_err());
+    }
+}
@@ -0,0 +1,1307 @@
+//! Drives `BatcherExEx` with synthetic notifications through reth's ExEx test harness, on an
+//! in-memory batcher database.
+//!
+//! Test blocks are Ethereum blocks: they have no L1 info deposit, so they are encoded with
+//! [`encode_test_block`], which leaves the epoch empty, unless a test checks encoding failures.
+//! The encoder the ExEx runs with, [`block_data`], is tested on OP blocks built with
+//! [`op_block`].
+
+use crate::{
+    BatcherExEx, BlockEncoder,
+    alt_da::{AltDaClient, AltDaSubmitter, CommitmentMode},
+    batch::SingularBatch,
+    block_data,
+    channel_builder::{CHANNEL_TIMER_INTERVAL, ChannelBuilder, ChannelTimer, FlushPolicy},
+    codec::Codec,
+    db::{BatchInfo, BatchStatus, BlockData, DB},
+    events::ChannelEvent,
+    l1::{BatchInbox, L1ApiServer, L1Client},
+    l1_info::L1BlockInfo,
+    pruner::{Pruner, RetentionPolicy},
+    signer::{BatcherSigner, KeyRing},
+    submitter::BatchSubmitter,
+    system_config::SystemConfig,
+};
+use alloy_consensus::{
+    Receipt, ReceiptEnvelope, ReceiptWithBloom, SignableTransaction, Transaction, TxEip1559,
+    TxEnvelope,
+};
+use alloy_eips::eip2718::{Decodable2718, Encodable2718};
+use alloy_primitives::{
+    Address, B256, Bloom, Sealable, Signature, TxKind, U64, U128, U256, address, hex,
+};
+use alloy_rpc_types_eth::{
+    BlockNumberOrTag, Filter, Header, Log, TransactionReceipt, TransactionRequest,
+};
+use alloy_signer_local::PrivateKeySigner;
+use flash_da_server::{FileStore, commitment::Commitment};
+use http_body_util::Full;
+use hyper::{Response, body::Bytes, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use jsonrpsee::{
+    core::{RpcResult, async_trait},
+    server::Server,
+    types::ErrorObjectOwned,
+};
+use op_alloy_consensus::{OpTxEnvelope, TxDeposit};
+use reth_execution_types::{Chain, ExecutionOutcome};
+use reth_exex_test_utils::{Adapter, PollOnce, TestExExHandle, test_exex_context};
+use reth_primitives::{Block, SealedBlock};
+use reth_primitives_traits::BlockBody as _;
+use reth_provider::BlockWriter;
+use reth_storage_api::StorageLocation;
+use reth_testing_utils::generators::{self, BlockRangeParams, random_block_range};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    ops::RangeInclusive,
+    pin::pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    net::TcpListener,
+    sync::{Notify, mpsc},
+};
+
+/// Encodes the batch of a block, without requiring an L1 info deposit.
+fn encode_test_block(block: &SealedBlock<Block>, codec: Codec) -> anyhow::Result<BlockData> {
+    let batch = SingularBatch {
+        parent_hash: block.header().parent_hash,
+        epoch_num: 0,
+        epoch_hash: B256::ZERO,
+        timestamp: block.header().timestamp,
+        transactions: block
+            .body()
+            .transactions()
+            .iter()
+            .map(|tx| tx.encoded_2718().into())
+            .collect(),
+    };
+
+    Ok(BlockData {
+        block_number: block.header().number,
+        block_hash: block.hash().to_string(),
+        timestamp: block.header().timestamp,
+        block_data: codec.encode(&batch)?,
+        batch_id: None,
+    })
+}
+
+/// Like [`encode_test_block`], but fails on block 2.
+fn encode_all_but_block_2(block: &SealedBlock<Block>, codec: Codec) -> anyhow::Result<BlockData> {
+    if block.header().number == 2 {
+        return Err(anyhow::anyhow!("Block 2 can't be encoded"));
+    }
+    encode_test_block(block, codec)
+}
+
+struct TestBatcher {
+    exex: BatcherExEx<Adapter>,
+    handle: TestExExHandle,
+    db: Arc<Mutex<DB>>,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+}
+
+async fn test_batcher(
+    batch_size: u64,
+    encode_block: BlockEncoder<Block>,
+) -> eyre::Result<TestBatcher> {
+    let (ctx, handle) = test_exex_context().await?;
+
+    let db = DB::new(":memory:")?;
+    db.initialize_database()?;
+    let db = Arc::new(Mutex::new(db));
+    let channel_builder = Arc::new(Mutex::new(ChannelBuilder::new(
+        db.clone(),
+        FlushPolicy::new(batch_size, None),
+    )));
+
+    let exex =
+        BatcherExEx::with_block_encoder(ctx, channel_builder.clone(), None, encode_block).await?;
+
+    Ok(TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    })
+}
+
+/// Random blocks numbered `range`, the first one a child of `parent`.
+fn blocks(range: RangeInclusive<u64>, parent: B256) -> Vec<SealedBlock<Block>> {
+    let mut rng = generators::rng();
+    random_block_range(
+        &mut rng,
+        range,
+        BlockRangeParams {
+            parent: Some(parent),
+            tx_count: 0..3,
+            ..Default::default()
+        },
+    )
+}
+
+fn chain(blocks: &[SealedBlock<Block>]) -> Chain {
+    Chain::new(
+        blocks.iter().map(|block| {
+            block
+                .clone()
+                .try_recover()
+                .expect("random blocks are signed")
+        }),
+        ExecutionOutcome::default(),
+        None,
+    )
+}
+
+/// Makes `blocks` canonical in the node database, so the ExEx can read them back.
+fn insert_canonical(handle: &TestExExHandle, blocks: &[SealedBlock<Block>]) -> eyre::Result<()> {
+    let provider_rw = handle.provider_factory.provider_rw()?;
+    for block in blocks {
+        provider_rw.insert_block(
+            block
+                .clone()
+                .try_recover()
+                .expect("random blocks are signed"),
+            StorageLocation::Database,
+        )?;
+    }
+    provider_rw.commit()?;
+    Ok(())
+}
+
+fn valid_batches(db: &Arc<Mutex<DB>>) -> Vec<BatchInfo> {
+    db.lock()
+        .unwrap()
+        .get_batches_in_range(0, u64::MAX)
+        .unwrap()
+}
+
+fn pending_blocks(channel_builder: &Arc<Mutex<ChannelBuilder>>) -> Vec<u64> {
+    channel_builder
+        .lock()
+        .unwrap()
+        .pending_blocks()
+        .iter()
+        .map(|block| block.block_number)
+        .collect()
+}
+
+#[tokio::test]
+async fn closes_channels_at_batch_size() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(3, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let blocks = blocks(1..=7, handle.genesis.hash());
+
+    // One block short of the batch size
+    let committed = chain(&blocks[..2]);
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    assert!(valid_batches(&db).is_empty());
+    assert_eq!(pending_blocks(&channel_builder), vec![1, 2]);
+
+    // Reaching the batch size closes the channel, left to the submitter
+    let committed = chain(&blocks[2..3]);
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].block_numbers, vec![1, 2, 3]);
+    assert_eq!(batches[0].status, BatchStatus::Pending);
+    assert!(pending_blocks(&channel_builder).is_empty());
+
+    BatchSubmitter::new(channel_builder.clone()).submit_pending()?;
+    assert_eq!(valid_batches(&db)[0].status, BatchStatus::Submitted);
+
+    // A chain spanning the boundary is split, the rest stays in the open channel
+    let committed = chain(&blocks[3..]);
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1].block_numbers, vec![4, 5, 6]);
+    assert_eq!(pending_blocks(&channel_builder), vec![7]);
+    handle.assert_events_empty();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stores_block_batches() -> eyre::Result<()> {
+    let TestBatcher {
+        exex, handle, db, ..
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let blocks = blocks(1..=2, handle.genesis.hash());
+
+    handle
+        .send_notification_chain_committed(chain(&blocks))
+        .await?;
+    exex.poll_once().await?;
+
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].codec, Codec::Json);
+    assert_eq!(
+        batches[0].first_block_timestamp,
+        Some(blocks[0].header().timestamp)
+    );
+
+    let decoded = batches[0]
+        .codec
+        .decode_channel(&batches[0].data)
+        .expect("channel decodes");
+    assert_eq!(decoded.len(), 2);
+    for (batch, block) in decoded.iter().zip(&blocks) {
+        assert_eq!(batch.parent_hash, block.header().parent_hash);
+        assert_eq!(batch.timestamp, block.header().timestamp);
+        assert_eq!(batch.transactions.len(), block.body().transactions().len());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_size_one_closes_every_block() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(1, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let blocks = blocks(1..=3, handle.genesis.hash());
+
+    let committed = chain(&blocks);
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    let block_numbers: Vec<_> = valid_batches(&db)
+        .into_iter()
+        .map(|batch| batch.block_numbers)
+        .collect();
+    assert_eq!(block_numbers, vec![vec![1], vec![2], vec![3]]);
+    assert!(pending_blocks(&channel_builder).is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn timer_closes_channels_past_max_duration() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(10, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    channel_builder
+        .lock()
+        .unwrap()
+        .set_flush_policy(FlushPolicy::new(10, Some(1)));
+    let timer = ChannelTimer::new(channel_builder.clone(), CHANNEL_TIMER_INTERVAL);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=2, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+    assert_eq!(timer.close_expired()?, None);
+    assert_eq!(pending_blocks(&channel_builder), vec![1, 2]);
+
+    // No block comes in, the channel is closed once open for the max duration
+    tokio::time::sleep(Duration::from_millis(1_100)).await;
+    let batch_id = timer.close_expired()?.expect("channel closed");
+
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].id, batch_id);
+    assert_eq!(batches[0].block_numbers, vec![1, 2]);
+    assert!(pending_blocks(&channel_builder).is_empty());
+    assert_eq!(timer.close_expired()?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stopped_batcher_keeps_channels_pending() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    channel_builder.lock().unwrap().set_running(false);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=2, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+
+    let pending = db.lock().unwrap().get_pending_batches()?;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].block_numbers, vec![1, 2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn prunes_up_to_the_locally_finalized_block() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=6, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+    BatchSubmitter::new(channel_builder.clone()).submit_pending()?;
+    let pruner = Pruner::new(
+        channel_builder.clone(),
+        RetentionPolicy::Depth(0),
+        Duration::from_secs(3600),
+    );
+
+    // Without op-node nor dev mode, the finalized head is unknown
+    assert_eq!(pruner.prune().await.unwrap(), 0);
+
+    // Dev mode finalizes blocks as it produces them
+    channel_builder.lock().unwrap().set_local_finalized(4);
+    assert_eq!(pruner.prune().await.unwrap(), 2);
+    let pruned: Vec<_> = valid_batches(&db)
+        .iter()
+        .map(|batch| batch.pruned_at.is_some())
+        .collect();
+    assert_eq!(pruned, vec![true, true, false]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_submitters_keep_channels_ordered() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let mut events = channel_builder.lock().unwrap().events().subscribe();
+    let blocks = blocks(1..=12, handle.genesis.hash());
+
+    // Submitters racing each other while channels keep closing
+    let stop = Arc::new(AtomicBool::new(false));
+    let submitters: Vec<_> = (0..4)
+        .map(|_| {
+            let submitter = BatchSubmitter::new(channel_builder.clone());
+            let stop = stop.clone();
+            tokio::task::spawn_blocking(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    submitter.submit_pending().unwrap();
+                }
+            })
+        })
+        .collect();
+    for blocks in blocks.chunks(3) {
+        handle
+            .send_notification_chain_committed(chain(blocks))
+            .await?;
+        exex.poll_once().await?;
+    }
+    stop.store(true, Ordering::Relaxed);
+    for submitter in submitters {
+        submitter.await?;
+    }
+    BatchSubmitter::new(channel_builder.clone()).submit_pending()?;
+
+    // Each channel is submitted once, in channel order
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 6);
+    assert!(
+        batches
+            .iter()
+            .all(|batch| batch.status == BatchStatus::Submitted)
+    );
+    let mut submitted = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let ChannelEvent::FrameSubmitted { channel_id, .. } = event {
+            submitted.push(channel_id);
+        }
+    }
+    assert_eq!(
+        submitted,
+        batches
+            .into_iter()
+            .map(|batch| batch.id)
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn skips_blocks_failing_serialization() -> eyre::Result<()> {
+    // Ethereum blocks have no L1 info deposit, the default encoder rejects them
+    let (ctx, handle) = test_exex_context().await?;
+    let db = DB::new(":memory:")?;
+    db.initialize_database()?;
+    let db = Arc::new(Mutex::new(db));
+    let channel_builder = Arc::new(Mutex::new(ChannelBuilder::new(
+        db.clone(),
+        FlushPolicy::new(1, None),
+    )));
+    let exex = BatcherExEx::new(ctx, channel_builder.clone(), None).await?;
+    let mut exex = pin!(exex);
+
+    let committed = chain(&blocks(1..=3, handle.genesis.hash()));
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    // The blocks are skipped, but the height is still reported as processed
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    assert!(valid_batches(&db).is_empty());
+    assert!(pending_blocks(&channel_builder).is_empty());
+
+    Ok(())
+}
+
+/// An OP block at `timestamp`, starting with the Ecotone L1 info deposit of `l1_info`.
+fn op_block(
+    number: u64,
+    timestamp: u64,
+    l1_info: &L1BlockInfo,
+    transactions: Vec<OpTxEnvelope>,
+) -> SealedBlock<alloy_consensus::Block<OpTxEnvelope>> {
+    let l1_info_deposit = TxDeposit {
+        source_hash: B256::with_last_byte(1),
+        from: Address::with_last_byte(1),
+        to: TxKind::Call(Address::with_last_byte(2)),
+        gas_limit: 1_000_000,
+        input: l1_info.encode_calldata(false).into(),
+        ..Default::default()
+    };
+
+    SealedBlock::seal_slow(alloy_consensus::Block {
+        header: alloy_consensus::Header {
+            number,
+            timestamp,
+            parent_hash: B256::with_last_byte(number as u8 - 1),
+            ..Default::default()
+        },
+        body: alloy_consensus::BlockBody {
+            transactions: std::iter::once(l1_info_deposit.seal_slow().into())
+                .chain(transactions)
+                .collect(),
+            ommers: Vec::new(),
+            withdrawals: None,
+        },
+    })
+}
+
+#[test]
+fn block_data_batches_op_blocks() -> anyhow::Result<()> {
+    let l1_info = L1BlockInfo {
+        number: 100,
+        timestamp: 1_700_000_000,
+        hash: B256::repeat_byte(0xaa),
+        sequence_number: 2,
+        base_fee: U256::from(1_000_000_000u64),
+        blob_base_fee: Some(U256::from(1)),
+        batcher_hash: B256::ZERO,
+        base_fee_scalar: Some(1368),
+        blob_base_fee_scalar: Some(810_949),
+    };
+    let transfer: OpTxEnvelope = TxEip1559 {
+        chain_id: 421,
+        nonce: 7,
+        gas_limit: 21_000,
+        to: TxKind::Call(Address::with_last_byte(3)),
+        value: U256::from(1),
+        ..Default::default()
+    }
+    .into_signed(Signature::test_signature())
+    .into();
+    // A user deposit after the L1 info one, derived from L1 too
+    let user_deposit = TxDeposit {
+        source_hash: B256::with_last_byte(2),
+        from: Address::with_last_byte(4),
+        to: TxKind::Call(Address::with_last_byte(5)),
+        gas_limit: 100_000,
+        ..Default::default()
+    }
+    .seal_slow()
+    .into();
+    let block = op_block(
+        8,
+        1_700_000_004,
+        &l1_info,
+        vec![user_deposit, transfer.clone()],
+    );
+
+    for codec in [
+        Codec::Json,
+        Codec::Bincode,
+        Codec::SingularBatch,
+        Codec::BlockRlp,
+    ] {
+        let data = block_data(&block, codec)?;
+        assert_eq!(data.block_number, 8);
+        assert_eq!(data.block_hash, block.hash().to_string());
+        assert_eq!(data.timestamp, 1_700_000_004);
+
+        // The epoch is the L1 origin of the deposit, deposits are left to derivation
+        let batches =
+            codec.decode_channel_of::<alloy_consensus::Block<OpTxEnvelope>>(&data.block_data)?;
+        assert_eq!(
+            batches,
+            vec![SingularBatch {
+                parent_hash: B256::with_last_byte(7),
+                epoch_num: 100,
+                epoch_hash: B256::repeat_byte(0xaa),
+                timestamp: 1_700_000_004,
+                transactions: vec![transfer.encoded_2718().into()],
+            }]
+        );
+    }
+
+    // Block RLP channels keep the whole block, deposits included
+    let data = Codec::BlockRlp.encode_block(&block)?;
+    assert_eq!(
+        <alloy_consensus::Block<OpTxEnvelope> as alloy_rlp::Decodable>::decode(&mut &data[..])?,
+        block.clone_block()
+    );
+
+    // Without the L1 info deposit first, the epoch is unknown
+    let mut block = block.into_block();
+    block.body.transactions.remove(0);
+    assert!(block_data(&SealedBlock::seal_slow(block), Codec::Json).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn skips_single_unencodable_block() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(3, encode_all_but_block_2).await?;
+    let mut exex = pin!(exex);
+
+    let committed = chain(&blocks(1..=4, handle.genesis.hash()));
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].block_numbers, vec![1, 3, 4]);
+    assert!(pending_blocks(&channel_builder).is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reorg_invalidates_and_requeues_canonical_blocks() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(4, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let old = blocks(1..=6, handle.genesis.hash());
+    insert_canonical(&handle, &old[..2])?;
+
+    handle
+        .send_notification_chain_committed(chain(&old))
+        .await?;
+    exex.poll_once().await?;
+    handle.assert_event_finished_height(old[5].num_hash())?;
+    assert_eq!(valid_batches(&db)[0].block_numbers, vec![1, 2, 3, 4]);
+    assert_eq!(pending_blocks(&channel_builder), vec![5, 6]);
+
+    // Blocks 3 to 6 are replaced by a shorter fork
+    let new = blocks(3..=5, old[1].hash());
+    let new_chain = chain(&new);
+    handle
+        .send_notification_chain_reorged(chain(&old[2..]), new_chain.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    handle.assert_event_finished_height(new_chain.tip().num_hash())?;
+
+    let invalidated = db
+        .lock()
+        .unwrap()
+        .list_batches(Some(BatchStatus::Invalidated), 0, 10)?;
+    assert_eq!(invalidated.len(), 1);
+    assert_eq!(invalidated[0].block_numbers, vec![1, 2, 3, 4]);
+
+    // Blocks 1 and 2 are read back from the node, and batched with the fork
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].block_numbers, vec![1, 2, 3, 4]);
+    assert_ne!(batches[0].id, invalidated[0].id);
+
+    let decoded = batches[0]
+        .codec
+        .decode_channel(&batches[0].data)
+        .expect("channel decodes");
+    assert_eq!(decoded[1].parent_hash, old[0].hash());
+    assert_eq!(decoded[2].parent_hash, old[1].hash());
+    assert_eq!(decoded[3].parent_hash, new[0].hash());
+    assert_eq!(pending_blocks(&channel_builder), vec![5]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn revert_drops_pending_blocks() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(4, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let blocks = blocks(1..=6, handle.genesis.hash());
+
+    handle
+        .send_notification_chain_committed(chain(&blocks))
+        .await?;
+    exex.poll_once().await?;
+    handle.assert_event_finished_height(blocks[5].num_hash())?;
+
+    handle
+        .send_notification_chain_reverted(chain(&blocks[4..]))
+        .await?;
+    exex.poll_once().await?;
+
+    // Nothing was committed, so no height is reported
+    handle.assert_events_empty();
+    assert!(pending_blocks(&channel_builder).is_empty());
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].status, BatchStatus::Pending);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn keeps_blocks_pending_on_db_error() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let blocks = blocks(1..=3, handle.genesis.hash());
+
+    db.lock()
+        .unwrap()
+        .conn()
+        .execute_batch("DROP TABLE batches")?;
+
+    let committed = chain(&blocks[..2]);
+    handle
+        .send_notification_chain_committed(committed.clone())
+        .await?;
+    exex.poll_once().await?;
+
+    // The channel can't be stored, its blocks stay in the open channel
+    handle.assert_event_finished_height(committed.tip().num_hash())?;
+    assert_eq!(pending_blocks(&channel_builder), vec![1, 2]);
+
+    // Once the database is back, the next block closes the channel with all of them
+    db.lock().unwrap().initialize_database()?;
+    handle
+        .send_notification_chain_committed(chain(&blocks[2..]))
+        .await?;
+    exex.poll_once().await?;
+
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].block_numbers, vec![1, 2, 3]);
+    assert!(pending_blocks(&channel_builder).is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fails_on_db_error_during_revert() -> eyre::Result<()> {
+    let TestBatcher {
+        exex, handle, db, ..
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let blocks = blocks(1..=2, handle.genesis.hash());
+
+    handle
+        .send_notification_chain_committed(chain(&blocks))
+        .await?;
+    exex.poll_once().await?;
+    handle.assert_event_finished_height(blocks[1].num_hash())?;
+
+    db.lock()
+        .unwrap()
+        .conn()
+        .execute_batch("DROP TABLE batches")?;
+
+    // Batches that can't be invalidated would be submitted with reverted blocks, so the ExEx
+    // stops instead
+    handle
+        .send_notification_chain_reverted(chain(&blocks))
+        .await?;
+    assert!(exex.poll_once().await.is_err());
+
+    Ok(())
+}
+
+/// Batch inbox alt-DA commitments are posted to.
+const BATCH_INBOX: Address = address!("0xff00000000000000000000000000000000000901");
+
+/// L1 RPC including every batcher transaction it accepts right away.
+#[derive(Clone, Default)]
+struct MockL1 {
+    /// Transactions included, in order.
+    included: Arc<Mutex<Vec<TxEnvelope>>>,
+    /// Whether transactions are rejected.
+    reject: Arc<AtomicBool>,
+    /// Whether receipts are withheld, leaving the transactions sent pending.
+    withhold_receipts: Arc<AtomicBool>,
+    /// Results of `eth_call` by input, empty output for other inputs.
+    calls: Arc<Mutex<HashMap<alloy_primitives::Bytes, RpcResult<alloy_primitives::Bytes>>>>,
+}
+
+impl MockL1 {
+    fn set_call(&self, input: &[u8], result: RpcResult<alloy_primitives::Bytes>) {
+        self.calls
+            .lock()
+            .unwrap()
+            .insert(alloy_primitives::Bytes::copy_from_slice(input), result);
+    }
+
+    fn included(&self) -> Vec<TxEnvelope> {
+        self.included.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl L1ApiServer for MockL1 {
+    async fn chain_id(&self) -> RpcResult<U64> {
+        Ok(U64::from(11155111))
+    }
+
+    async fn block_number(&self) -> RpcResult<U64> {
+        Ok(U64::from(1))
+    }
+
+    async fn block_by_number(
+        &self,
+        _block: BlockNumberOrTag,
+        _full: bool,
+    ) -> RpcResult<Option<Header>> {
+        Ok(Some(Header {
+            hash: B256::ZERO,
+            inner: alloy_consensus::Header {
+                number: 1,
+                base_fee_per_gas: Some(1_000_000_000),
+                ..Default::default()
+            },
+            total_difficulty: None,
+            size: None,
+        }))
+    }
+
+    async fn blob_base_fee(&self) -> RpcResult<U128> {
+        Ok(U128::from(1))
+    }
+
+    async fn call(
+        &self,
+        request: TransactionRequest,
+        _block: U64,
+    ) -> RpcResult<alloy_primitives::Bytes> {
+        let input = request.input.into_input().unwrap_or_default();
+        self.calls
+            .lock()
+            .unwrap()
+            .get(&input)
+            .cloned()
+            .unwrap_or_else(|| Ok(alloy_primitives::Bytes::new()))
+    }
+
+    async fn get_logs(&self, _filter: Filter) -> RpcResult<Vec<Log>> {
+        Ok(Vec::new())
+    }
+
+    async fn transaction_count(
+        &self,
+        _address: Address,
+        _block: BlockNumberOrTag,
+    ) -> RpcResult<U64> {
+        Ok(U64::from(self.included.lock().unwrap().len()))
+    }
+
+    async fn max_priority_fee_per_gas(&self) -> RpcResult<U128> {
+        Ok(U128::from(1_000_000))
+    }
+
+    async fn estimate_gas(&self, _request: TransactionRequest) -> RpcResult<U64> {
+        Ok(U64::from(30_000))
+    }
+
+    async fn send_raw_transaction(&self, raw: alloy_primitives::Bytes) -> RpcResult<B256> {
+        if self.reject.load(Ordering::SeqCst) {
+            return Err(ErrorObjectOwned::owned(
+                -32000,
+                "insufficient funds",
+                None::<()>,
+            ));
+        }
+        let tx = TxEnvelope::decode_2718(&mut raw.as_ref())
+            .map_err(|e| ErrorObjectOwned::owned(-32602, e.to_string(), None::<()>))?;
+        let hash = *tx.tx_hash();
+        self.included.lock().unwrap().push(tx);
+        Ok(hash)
+    }
+
+    async fn transaction_receipt(&self, hash: B256) -> RpcResult<Option<TransactionReceipt>> {
+        if self.withhold_receipts.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let included = self.included.lock().unwrap();
+        Ok(included
+            .iter()
+            .position(|tx| *tx.tx_hash() == hash)
+            .map(|index| TransactionReceipt {
+                inner: ReceiptEnvelope::Eip1559(ReceiptWithBloom {
+                    receipt: Receipt {
+                        status: true.into(),
+                        cumulative_gas_used: 30_000,
+                        logs: Vec::new(),
+                    },
+                    logs_bloom: Bloom::ZERO,
+                }),
+                transaction_hash: hash,
+                transaction_index: Some(0),
+                block_hash: Some(B256::ZERO),
+                block_number: Some(index as u64 + 1),
+                gas_used: 30_000,
+                effective_gas_price: 1_000_000_000,
+                blob_gas_used: None,
+                blob_gas_price: None,
+                from: Address::ZERO,
+                to: included[index].to(),
+                contract_address: None,
+            }))
+    }
+}
+
+/// Serves `l1` on a local port, returning a client of it.
+async fn l1_client(l1: MockL1) -> eyre::Result<L1Client> {
+    let server = Server::builder().build("127.0.0.1:0").await?;
+    let url = format!("http://{}", server.local_addr()?);
+    tokio::spawn(server.start(l1.into_rpc()).stopped());
+
+    L1Client::new(&url)
+}
+
+/// Serves `l1` on a local port, returning the batch inbox behind it.
+async fn batch_inbox(l1: MockL1) -> eyre::Result<BatchInbox> {
+    Ok(BatchInbox::new(l1_client(l1).await?, BATCH_INBOX)
+        .with_receipt_interval(Duration::from_millis(10)))
+}
+
+/// Configures a random batcher key, the one signing the batch inbox transactions.
+fn set_batcher_key(channel_builder: &Arc<Mutex<ChannelBuilder>>) {
+    let keys = KeyRing::new(vec![BatcherSigner::Local(PrivateKeySigner::random())]).unwrap();
+    channel_builder.lock().unwrap().set_keys(Arc::new(keys));
+}
+
+/// Serves a file-backed alt-DA server in a temporary directory, returning its URL.
+async fn da_server() -> eyre::Result<(String, tempfile::TempDir)> {
+    let dir = tempfile::tempdir()?;
+    let store = FileStore::new(dir.path())?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(flash_da_server::serve(listener, store));
+    Ok((url, dir))
+}
+
+#[tokio::test]
+async fn posts_commitments_to_the_batch_inbox_in_order() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=6, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+    set_batcher_key(&channel_builder);
+
+    let (url, _dir) = da_server().await?;
+    let l1 = MockL1::default();
+    let submitter = AltDaSubmitter::new(
+        AltDaClient::new(&url, CommitmentMode::Keccak),
+        batch_inbox(l1.clone()).await?,
+        channel_builder.clone(),
+        3,
+    );
+    submitter.submit_pending().await?;
+
+    // One transaction per channel, in channel order
+    let batches = valid_batches(&db);
+    let included = l1.included();
+    assert_eq!(batches.len(), 3);
+    assert_eq!(included.len(), 3);
+    for (nonce, (batch, tx)) in batches.iter().zip(&included).enumerate() {
+        let commitment = Commitment::keccak(&batch.data);
+        assert_eq!(batch.status, BatchStatus::Submitted);
+        assert_eq!(batch.da_commitment, Some(commitment.to_string()));
+        assert_eq!(batch.l1_tx_hash, Some(tx.tx_hash().to_string()));
+        assert_eq!(tx.to(), Some(BATCH_INBOX));
+        assert_eq!(tx.input(), &commitment.tx_data());
+        assert_eq!(tx.nonce(), nonce as u64);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn puts_continue_while_a_commitment_is_posted() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=6, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+    set_batcher_key(&channel_builder);
+
+    let (url, _dir) = da_server().await?;
+    let client = AltDaClient::new(&url, CommitmentMode::Keccak);
+    let l1 = MockL1::default();
+    l1.withhold_receipts.store(true, Ordering::SeqCst);
+    let submitter = AltDaSubmitter::new(
+        client.clone(),
+        batch_inbox(l1.clone()).await?,
+        channel_builder.clone(),
+        1,
+    );
+    let submission = tokio::spawn(async move { submitter.submit_pending().await });
+
+    // One put at a time, the later channels being put while the first commitment is pending
+    let last = Commitment::keccak(&valid_batches(&db)[2].data);
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while client.get(&last).await.unwrap().is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    assert_eq!(l1.included().len(), 1);
+    for batch in valid_batches(&db) {
+        assert_eq!(batch.status, BatchStatus::Submitting);
+    }
+
+    // Their commitments are posted once it's included, in channel order
+    l1.withhold_receipts.store(false, Ordering::SeqCst);
+    submission.await??;
+    let batches = valid_batches(&db);
+    let included = l1.included();
+    assert_eq!(included.len(), 3);
+    for (batch, tx) in batches.iter().zip(&included) {
+        assert_eq!(batch.status, BatchStatus::Submitted);
+        assert_eq!(tx.input(), &Commitment::keccak(&batch.data).tx_data());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_l1_transactions_requeue_channels() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=4, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+    set_batcher_key(&channel_builder);
+
+    let (url, _dir) = da_server().await?;
+    let l1 = MockL1::default();
+    l1.reject.store(true, Ordering::SeqCst);
+    let submitter = AltDaSubmitter::new(
+        AltDaClient::new(&url, CommitmentMode::Keccak),
+        batch_inbox(l1.clone()).await?,
+        channel_builder.clone(),
+        2,
+    );
+
+    // Put to the DA server, but not posted: pending again, with the channel after it
+    submitter.submit_pending().await?;
+    for batch in valid_batches(&db) {
+        assert_eq!(batch.status, BatchStatus::Pending);
+        assert_eq!(batch.retry_count, 1);
+        assert!(batch.l1_tx_hash.is_none());
+    }
+
+    l1.reject.store(false, Ordering::SeqCst);
+    submitter.submit_pending().await?;
+    let batches = valid_batches(&db);
+    assert!(
+        batches
+            .iter()
+            .all(|batch| batch.status == BatchStatus::Submitted)
+    );
+    assert_eq!(l1.included().len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn holds_channels_without_batcher_key() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+
+    handle
+        .send_notification_chain_committed(chain(&blocks(1..=2, handle.genesis.hash())))
+        .await?;
+    exex.poll_once().await?;
+
+    let (url, _dir) = da_server().await?;
+    let l1 = MockL1::default();
+    let submitter = AltDaSubmitter::new(
+        AltDaClient::new(&url, CommitmentMode::Keccak),
+        batch_inbox(l1.clone()).await?,
+        channel_builder.clone(),
+        1,
+    );
+    submitter.submit_pending().await?;
+
+    assert_eq!(valid_batches(&db)[0].status, BatchStatus::Pending);
+    assert!(l1.included().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn system_config_treats_only_reverts_as_unset() -> eyre::Result<()> {
+    let l1 = MockL1::default();
+    let word = |byte| Ok(alloy_primitives::Bytes::copy_from_slice(&[byte; 32]));
+    let revert = |code| {
+        Err(ErrorObjectOwned::owned(
+            code,
+            "execution reverted",
+            None::<()>,
+        ))
+    };
+    // batcherHash(), overhead(), scalar(), gasLimit(), unsafeBlockSigner()
+    l1.set_call(&hex!("e81b2c6d"), word(1));
+    l1.set_call(&hex!("0c18c162"), word(2));
+    l1.set_call(&hex!("f45e65d8"), word(3));
+    l1.set_call(&hex!("f68016b7"), word(4));
+    l1.set_call(&hex!("1fd19ee1"), word(5));
+    // Holocene and Isthmus getters, reverting as geth and other clients report it
+    l1.set_call(&hex!("d220a9e0"), revert(3));
+    l1.set_call(&hex!("c9ff2d16"), revert(3));
+    l1.set_call(&hex!("4d5d9a2a"), revert(-32000));
+    l1.set_call(&hex!("16d3bc7f"), revert(-32000));
+    let client = l1_client(l1.clone()).await?;
+
+    let config = SystemConfig::read(&client, BATCH_INBOX, 7).await?;
+    assert_eq!(config.batcher_address, Address::repeat_byte(1));
+    assert_eq!(config.gas_limit, u64::from_be_bytes([4; 8]));
+    assert_eq!(config.eip1559_denominator, 0);
+    assert_eq!(config.operator_fee_constant, 0);
+    assert_eq!(config.l1_block, 7);
+
+    l1.set_call(
+        &hex!("16d3bc7f"),
+        Err(ErrorObjectOwned::owned(
+            -32000,
+            "header not found",
+            None::<()>,
+        )),
+    );
+    assert!(SystemConfig::read(&client, BATCH_INBOX, 7).await.is_err());
+
+    // The getters of the genesis config must not revert
+    l1.set_call(&hex!("16d3bc7f"), word(6));
+    l1.set_call(&hex!("f68016b7"), revert(3));
+    assert!(SystemConfig::read(&client, BATCH_INBOX, 7).await.is_err());
+
+    Ok(())
+}
+
+/// Alt-DA server holding every put until released.
+struct SlowDaServer {
+    url: String,
+    /// Receives a message when a put starts.
+    started: mpsc::UnboundedReceiver<()>,
+    release: Arc<Notify>,
+    puts: Arc<AtomicUsize>,
+}
+
+async fn slow_da_server() -> SlowDaServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (started_tx, started) = mpsc::unbounded_channel();
+    let release = Arc::new(Notify::new());
+    let puts = Arc::new(AtomicUsize::new(0));
+
+    let (server_release, server_puts) = (release.clone(), puts.clone());
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (started, release, puts) = (
+                started_tx.clone(),
+                server_release.clone(),
+                server_puts.clone(),
+            );
+            tokio::spawn(async move {
+                let service = service_fn(move |_| {
+                    let (started, release, puts) = (started.clone(), release.clone(), puts.clone());
+                    async move {
+                        puts.fetch_add(1, Ordering::SeqCst);
+                        let _ = started.send(());
+                        release.notified().await;
+                        Ok::<_, Infallible>(Response::new(Full::new(Bytes::new())))
+                    }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    SlowDaServer {
+        url,
+        started,
+        release,
+        puts,
+    }
+}
+
+#[tokio::test]
+async fn reorg_during_put_skips_invalidated_batches() -> eyre::Result<()> {
+    let TestBatcher {
+        exex,
+        handle,
+        db,
+        channel_builder,
+    } = test_batcher(2, encode_test_block).await?;
+    let mut exex = pin!(exex);
+    let old = blocks(1..=4, handle.genesis.hash());
+
+    handle
+        .send_notification_chain_committed(chain(&old))
+        .await?;
+    exex.poll_once().await?;
+    let batches = valid_batches(&db);
+    assert_eq!(batches.len(), 2);
+    let (first, second) = (batches[0].id.clone(), batches[1].id.clone());
+
+    // One put at a time: the second channel is only put once the first one is
+    let mut server = slow_da_server().await;
+    let l1 = MockL1::default();
+    set_batcher_key(&channel_builder);
+    let submitter = AltDaSubmitter::new(
+        AltDaClient::new(&server.url, CommitmentMode::Keccak),
+        batch_inbox(l1.clone()).await?,
+        channel_builder.clone(),
+        1,
+    );
+    let submission = tokio::spawn(async move { submitter.submit_pending().await });
+    server.started.recv().await.unwrap();
+
+    // Blocks 3 and 4 are reorged out while the first channel is being put
+    let new_chain = chain(&blocks(3..=4, old[1].hash()));
+    handle
+        .send_notification_chain_reorged(chain(&old[2..]), new_chain.clone())
+        .await?;
+    exex.poll_once().await?;
+    handle.assert_event_finished_height(new_chain.tip().num_hash())?;
+
+    server.release.notify_one();
+    submission.await??;
+
+    let db = db.lock().unwrap();
+    let first = db.get_batch(&first)?.unwrap();
+    assert_eq!(first.status, BatchStatus::Submitted);
+    assert!(first.da_commitment.is_some());
+
+    // Only the commitment of the first channel is posted
+    let included = l1.included();
+    assert_eq!(included.len(), 1);
+    assert_eq!(first.l1_tx_hash, Some(included[0].tx_hash().to_string()));
+
+    // The invalidated channel is never put, and stays invalidated
+    let second = db.get_batch(&second)?.unwrap();
+    assert_eq!(second.status, BatchStatus::Invalidated);
+    assert!(second.da_commitment.is_none());
+    assert_eq!(server.puts.load(Ordering::SeqCst), 1);
+
+    // The fork is batched anew, left for the next round
+    let pending = db.get_pending_batches()?;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].block_numbers, vec![3, 4]);
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Sequencer throttling on DA backlog, following op-batcher.
+//!
+//! When more data is waiting for submission than the DA layer keeps up with, the DA limits of
+//! the payload builder are lowered, so blocks carry less data until the backlog drains. The
+//! limits are the same ones `miner_setMaxDASize` sets, and are restored once throttling ends.
+
+use crate::channel_builder::ChannelBuilder;
+use reth_optimism_payload_builder::config::OpDAConfig;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+/// DA limits applied while the backlog is above the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottleConfig {
+    /// Unsubmitted bytes above which the sequencer is throttled.
+    pub threshold: u64,
+    /// Maximum DA size of a transaction while throttled.
+    pub tx_size: u64,
+    /// Maximum DA size of a block while throttled.
+    pub block_size: u64,
+}
+
+/// Adjusts the payload builder DA limits to the unsubmitted backlog.
+pub struct Throttler {
+    da_config: OpDAConfig,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    config: ThrottleConfig,
+    interval: Duration,
+    /// Transaction and block limits set before throttling started, `0` meaning unlimited.
+    previous_limits: Option<(u64, u64)>,
+}
+
+impl Throttler {
+    pub fn new(
+        da_config: OpDAConfig,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        config: ThrottleConfig,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            da_config,
+            channel_builder,
+            config,
+            interval,
+            previous_limits: None,
+        }
+    }
+
+    pub fn is_throttling(&self) -> bool {
+        self.previous_limits.is_some()
+    }
+
+    /// Returns the size of the data not submitted yet, including the open channel.
+    fn unsubmitted_bytes(&self) -> eyre::Result<u64> {
+        let channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+
+        let open_channel = channel_builder
+            .open_channel()
+            .map_or(0, |channel| channel.size as u64);
+        let stored = channel_builder
+            .db()
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .unsubmitted_bytes()
+            .map_err(|e| eyre::eyre!("Failed to compute unsubmitted bytes: {}", e))?;
+
+        let metrics = &channel_builder.metrics().throttle;
+        metrics
+            .unsubmitted_bytes
+            .set((stored + open_channel) as f64);
+
+        Ok(stored + open_channel)
+    }
+
+    /// Throttles or releases the sequencer depending on the current backlog.
+    pub fn update(&mut self) -> eyre::Result<()> {
+        let backlog = self.unsubmitted_bytes()?;
+        debug!("Unsubmitted backlog: {} bytes", backlog);
+
+        match (backlog > self.config.threshold, self.previous_limits) {
+            (true, None) => {
+                let previous = (
+                    self.da_config.max_da_tx_size().unwrap_or_default(),
+                    self.da_config.max_da_block_size().unwrap_or_default(),
+                );
+                warn!(
+                    "Unsubmitted backlog of {} bytes exceeds {} bytes, throttling sequencer to {} bytes per transaction and {} bytes per block",
+                    backlog, self.config.threshold, self.config.tx_size, self.config.block_size
+                );
+
+                self.da_config
+                    .set_max_da_size(self.config.tx_size, self.config.block_size);
+                self.previous_limits = Some(previous);
+            }
+            (false, Some((tx_size, block_size))) => {
+                info!(
+                    "Unsubmitted backlog down to {} bytes, releasing sequencer throttling",
+                    backlog
+                );
+
+                self.da_config.set_max_da_size(tx_size, block_size);
+                self.previous_limits = None;
+            }
+            _ => {}
+        }
+
+        let active = if self.is_throttling() { 1.0 } else { 0.0 };
+        if let Ok(channel_builder) = self.channel_builder.lock() {
+            channel_builder.metrics().throttle.active.set(active);
+        }
+
+        Ok(())
+    }
+
+    /// Updates on every tick of the configured interval, forever.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.update() {
+                error!("Failed to update sequencer throttling: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! Derivation round-trip check of the stored channels.
+//!
+//! Channels are decoded back into batches the way derivation would, and compared with the
+//! blocks of the node. Channels are not framed nor compressed yet (see README), so their
+//! payload is decoded directly. Channels put to an alt-DA server are read back from it, as
+//! derivation would, when the server is configured.
+//!
+//! Blocks the batcher doesn't batch aren't reported missing: the ones already safe when they
+//! were produced, and the ones of the open channel.
+
+use crate::{
+    alt_da::AltDaClient,
+    batch::SingularBatch,
+    channel_builder::ChannelBuilder,
+    codec::Codec,
+    db::{BatchInfo, DB},
+};
+use flash_da_server::commitment::Commitment;
+use reth::providers::BlockReader;
+use reth_primitives::SealedBlock;
+use reth_primitives_traits::Block;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, info, warn};
+
+/// A difference between the stored channels and the node's blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Mismatch {
+    /// The block is not part of any valid channel.
+    MissingBlock { block_number: u64 },
+    /// The block is part of more than one valid channel.
+    DuplicateBlock {
+        block_number: u64,
+        channel_ids: Vec<String>,
+    },
+    /// The channel payload could not be decoded into batches.
+    UndecodableChannel { channel_id: String, error: String },
+    /// The channel could not be read back from the alt-DA server under its commitment: the
+    /// server doesn't hold it, returned other data, failed, or the commitment is invalid.
+    UnavailableChannel {
+        channel_id: String,
+        commitment: String,
+        error: String,
+    },
+    /// The channel holds a different number of batches than blocks.
+    BatchCount {
+        channel_id: String,
+        blocks: usize,
+        batches: usize,
+    },
+    /// The node does not have a block the channel contains.
+    UnknownBlock {
+        channel_id: String,
+        block_number: u64,
+    },
+    /// The batch differs from the one derived from the node's block.
+    Field {
+        channel_id: String,
+        block_number: u64,
+        field: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// Outcome of verifying a block range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationReport {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub channels_checked: usize,
+    pub blocks_checked: usize,
+    /// Blocks outside any channel at or below the op-node safe head, skipped as already safe.
+    pub safe_blocks: usize,
+    /// Blocks of the open channel, not closed yet.
+    pub open_blocks: usize,
+    /// Channels whose payload was pruned, and could not be verified.
+    pub pruned_channels: Vec<String>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerificationReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Verifies stored channels against the blocks of a provider.
+pub struct Verifier<P> {
+    provider: P,
+    channel_builder: Arc<Mutex<ChannelBuilder>>,
+    alt_da: Option<AltDaClient>,
+}
+
+impl<P> Verifier<P>
+where
+    P: BlockReader,
+    SealedBlock<P::Block>: DeserializeOwned,
+{
+    pub fn new(
+        provider: P,
+        channel_builder: Arc<Mutex<ChannelBuilder>>,
+        alt_da: Option<AltDaClient>,
+    ) -> Self {
+        Self {
+            provider,
+            channel_builder,
+            alt_da,
+        }
+    }
+
+    /// Returns the database, the op-node safe head and the blocks of the open channel.
+    fn snapshot(&self) -> eyre::Result<(Arc<Mutex<DB>>, Option<u64>, BTreeSet<u64>)> {
+        let channel_builder = self
+            .channel_builder
+            .lock()
+            .map_err(|_| eyre::eyre!("Channel builder lock poisoned"))?;
+        let open = channel_builder
+            .pending_blocks()
+            .iter()
+            .map(|block| block.block_number)
+            .collect();
+
+        Ok((channel_builder.db(), channel_builder.safe_head(), open))
+    }
+
+    /// Returns the highest block number contained in a valid channel.
+    pub fn latest_batched_block(&self) -> eyre::Result<Option<u64>> {
+        let (db, _, _) = self.snapshot()?;
+        db.lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .latest_batched_block()
+            .map_err(|e| eyre::eyre!("Failed to fetch latest batched block: {}", e))
+    }
+
+    /// Verifies the channels covering blocks `from..=to`.
+    ///
+    /// A channel that can't be read back from the alt-DA server is reported as unavailable, and
+    /// the other channels are still verified.
+    pub async fn verify_range(&self, from: u64, to: u64) -> eyre::Result<VerificationReport> {
+        debug!("Verifying channels for blocks {} to {}", from, to);
+
+        let (db, safe_head, open) = self.snapshot()?;
+        let channels = db
+            .lock()
+            .map_err(|_| eyre::eyre!("Database lock poisoned"))?
+            .get_batches_in_range(from, to)
+            .map_err(|e| eyre::eyre!("Failed to fetch channels: {}", e))?;
+
+        let mut report = VerificationReport {
+            from_block: from,
+            to_block: to,
+            ..Default::default()
+        };
+        let mut coverage: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+
+        for mut channel in channels {
+            for block_number in channel
+                .block_numbers
+                .iter()
+                .filter(|n| (from..=to).contains(*n))
+            {
+                coverage
+                    .entry(*block_number)
+                    .or_default()
+                    .push(channel.id.clone());
+            }
+
+            let data = match (&self.alt_da, &channel.da_commitment) {
+                (Some(client), Some(commitment)) => match fetch(client, commitment).await {
+                    Ok(data) => data,
+                    Err(error) => {
+                        report.mismatches.push(Mismatch::UnavailableChannel {
+                            channel_id: channel.id,
+                            commitment: commitment.clone(),
+                            error: error.to_string(),
+                        });
+                        continue;
+                    }
+                },
+                _ if channel.pruned_at.is_some() => {
+                    report.pruned_channels.push(channel.id);
+                    continue;
+                }
+                _ => std::mem::take(&mut channel.data),
+            };
+
+            report.channels_checked += 1;
+            self.verify_channel(&channel, &data, from, to, &mut report)?;
+        }
+
+        for block_number in from..=to {
+            match coverage.remove(&block_number) {
+                None if open.contains(&block_number) => report.open_blocks += 1,
+                None if safe_head.is_some_and(|safe_head| block_number <= safe_head) => {
+                    report.safe_blocks += 1
+                }
+                None => report
+                    .mismatches
+                    .push(Mismatch::MissingBlock { block_number }),
+                Some(channel_ids) if channel_ids.len() > 1 => {
+                    report.mismatches.push(Mismatch::DuplicateBlock {
+                        block_number,
+                        channel_ids,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        if report.is_ok() {
+            info!(
+                "Verified {} channels and {} blocks from {} to {}",
+                report.channels_checked, report.blocks_checked, from, to
+            );
+        } else {
+            warn!(
+                "Found {} mismatches verifying blocks {} to {}",
+                report.mismatches.len(),
+                from,
+                to
+            );
+        }
+
+        Ok(report)
+    }
+
+    fn verify_channel(
+        &self,
+        channel: &BatchInfo,
+        data: &[u8],
+        from: u64,
+        to: u64,
+        report: &mut VerificationReport,
+    ) -> eyre::Result<()> {
+        let decoded = channel
+            .codec
+            .decode_channel_of::<P::Block>(data)
+            .map_err(|e| e.to_string())
+            .or_else(|error| match channel.codec {
+                // Channels of older batchers hold whole sealed blocks, also JSON serialized
+                Codec::Json => decode_sealed_blocks::<P::Block>(data).map_err(|_| error),
+                _ => Err(error),
+            });
+        let batches = match decoded {
+            Ok(batches) => batches,
+            Err(error) => {
+                report.mismatches.push(Mismatch::UndecodableChannel {
+                    channel_id: channel.id.clone(),
+                    error,
+                });
+                return Ok(());
+            }
+        };
+
+        if batches.len() != channel.block_numbers.len() {
+            report.mismatches.push(Mismatch::BatchCount {
+                channel_id: channel.id.clone(),
+                blocks: channel.block_numbers.len(),
+                batches: batches.len(),
+            });
+            return Ok(());
+        }
+
+        for (block_number, actual) in channel.block_numbers.iter().copied().zip(batches) {
+            if !(from..=to).contains(&block_number) {
+                continue;
+            }
+
+            let Some(block) = self.provider.block_by_number(block_number)? else {
+                report.mismatches.push(Mismatch::UnknownBlock {
+                    channel_id: channel.id.clone(),
+                    block_number,
+                });
+                continue;
+            };
+
+            let expected =
+                SingularBatch::from_block(&SealedBlock::seal_slow(block)).map_err(|e| {
+                    eyre::eyre!("Failed to derive batch of block {}: {}", block_number, e)
+                })?;
+
+            report.blocks_checked += 1;
+            compare_batches(
+                &channel.id,
+                block_number,
+                channel.codec,
+                &expected,
+                &actual,
+                report,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a channel back from the alt-DA server, checking it's the data of `commitment`.
+async fn fetch(client: &AltDaClient, commitment: &str) -> eyre::Result<Vec<u8>> {
+    let commitment: Commitment = commitment.parse()?;
+    match client.get(&commitment).await? {
+        Some(data) if commitment.matches(&data) => Ok(data),
+        Some(_) => Err(eyre::eyre!(
+            "DA server returned data not matching the commitment"
+        )),
+        None => Err(eyre::eyre!("DA server doesn't have the channel")),
+    }
+}
+
+/// Decodes a channel of JSON serialized sealed blocks, as batchers wrote before batches left out
+/// deposits, into the batches of the blocks.
+fn decode_sealed_blocks<B>(data: &[u8]) -> eyre::Result<Vec<SingularBatch>>
+where
+    B: Block,
+    SealedBlock<B>: DeserializeOwned,
+{
+    serde_json::Deserializer::from_slice(data)
+        .into_iter::<SealedBlock<B>>()
+        .map(|block| {
+            let block = block.map_err(|e| eyre::eyre!("Failed to decode block: {}", e))?;
+            SingularBatch::from_block(&block)
+                .map_err(|e| eyre::eyre!("Failed to derive batch: {}", e))
+        })
+        .collect()
+}
+
+fn compare_batches(
+    channel_id: &str,
+    block_number: u64,
+    codec: Codec,
+    expected: &SingularBatch,
+    actual: &SingularBatch,
+    report: &mut VerificationReport,
+) {
+    let mut check = |field: &str, expected: String, actual: String| {
+        if expected != actual {
+            report.mismatches.push(Mismatch::Field {
+                channel_id: channel_id.to_string(),
+                block_number,
+                field: field.to_string(),
+                expected,
+                actual,
+            });
+        }
+    };
+
+    // Raw transaction channels only carry transactions
+    if codec.has_batch_fields() {
+        check(
+            "parentHash",
+            expected.parent_hash.to_string(),
+            actual.parent_hash.to_string(),
+        );
+        check(
+            "timestamp",
+            expected.timestamp.to_string(),
+            actual.timestamp.to_string(),
+        );
+        check(
+            "epochNum",
+            expected.epoch_num.to_string(),
+            actual.epoch_num.to_string(),
+        );
+        check(
+            "epochHash",
+            expected.epoch_hash.to_string(),
+            actual.epoch_hash.to_string(),
+        );
+    }
+    check(
+        "transactionCount",
+        expected.transactions.len().to_string(),
+        actual.transactions.len().to_string(),
+    );
+
+    for (index, (expected_tx, actual_tx)) in expected
+        .transactions
+        .iter()
+        .zip(&actual.transactions)
+        .enumerate()
+    {
+        check(
+            &format!("transactions[{index}]"),
+            expected_tx.to_string(),
+            actual_tx.to_string(),
+        );
+    }
+}
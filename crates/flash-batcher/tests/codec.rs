@@ -0,0 +1,105 @@
+//! Encodes channel payloads with every codec and decodes them back.
+
+use alloy_primitives::{B256, Bytes};
+use clap::ValueEnum;
+use flash_batcher::{batch::SingularBatch, codec::Codec};
+
+fn batch(n: u8) -> SingularBatch {
+    SingularBatch {
+        parent_hash: B256::repeat_byte(n),
+        epoch_num: n.into(),
+        epoch_hash: B256::repeat_byte(0xee),
+        timestamp: 1_000 + u64::from(n),
+        transactions: vec![
+            Bytes::from(vec![0x02, n, 0x03]),
+            Bytes::from(vec![0xf8; 70]),
+        ],
+    }
+}
+
+/// A channel payload of three batches.
+fn channel(codec: Codec) -> Vec<u8> {
+    (1..=3)
+        .flat_map(|n| codec.encode(&batch(n)).unwrap())
+        .collect()
+}
+
+/// The codecs that store batches rather than whole blocks.
+fn batch_codecs() -> impl Iterator<Item = Codec> {
+    Codec::ALL
+        .into_iter()
+        .filter(|codec| !codec.stores_blocks())
+}
+
+#[test]
+fn channels_round_trip() {
+    for codec in batch_codecs() {
+        let decoded = codec.decode_channel(&channel(codec)).unwrap();
+        assert_eq!(decoded.len(), 3, "{codec}");
+
+        for (n, decoded) in (1..=3).zip(decoded) {
+            if codec.has_batch_fields() {
+                assert_eq!(decoded, batch(n), "{codec}");
+            } else {
+                assert_eq!(decoded.transactions, batch(n).transactions, "{codec}");
+            }
+        }
+    }
+}
+
+#[test]
+fn truncated_channels_fail() {
+    for codec in batch_codecs() {
+        let data = channel(codec);
+        assert!(
+            codec.decode_channel(&data[..data.len() - 1]).is_err(),
+            "{codec}"
+        );
+    }
+}
+
+#[test]
+fn singular_batches_follow_the_op_stack_encoding() {
+    let data = Codec::SingularBatch.encode(&batch(1)).unwrap();
+
+    // Version byte, then an RLP list starting with the 32 byte parent hash and the epoch number
+    assert_eq!(data[0], 0x00);
+    assert_eq!(data[1], 0xf8);
+    assert_eq!(data[3], 0xa0);
+    assert_eq!(&data[4..36], B256::repeat_byte(1).as_slice());
+    assert_eq!(data[36], 0x01);
+
+    let mut unknown_version = data.clone();
+    unknown_version[0] = 0x01;
+    assert!(
+        Codec::SingularBatch
+            .decode_channel(&unknown_version)
+            .is_err()
+    );
+}
+
+#[test]
+fn block_rlp_needs_blocks() {
+    assert!(Codec::BlockRlp.encode(&batch(1)).is_err());
+    assert!(Codec::BlockRlp.decode_channel(&[0xc0]).is_err());
+}
+
+#[test]
+fn codec_names() {
+    for codec in Codec::ALL {
+        assert_eq!(codec.to_string().parse::<Codec>().unwrap(), codec);
+        assert_eq!(
+            serde_json::to_string(&codec).unwrap(),
+            format!("\"{codec}\"")
+        );
+    }
+
+    // Former name of the singular batch codec, recorded by older batchers but no longer
+    // accepted on the command line
+    assert_eq!("rlp".parse::<Codec>().unwrap(), Codec::SingularBatch);
+    assert!(<Codec as ValueEnum>::from_str("rlp", false).is_err());
+    assert_eq!(
+        <Codec as ValueEnum>::from_str("block-rlp", false).unwrap(),
+        Codec::BlockRlp
+    );
+}
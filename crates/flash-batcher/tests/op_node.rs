@@ -0,0 +1,212 @@
+//! Follows a mock op-node: a local HTTP server answering `optimism_syncStatus` JSON-RPC
+//! requests with a configurable sync status, or error.
+
+use flash_batcher::{
+    channel_builder::{ChannelBuilder, FlushPolicy},
+    db::DB,
+    events::ChannelEvent,
+    op_node::{OpNodeClient, SyncStatusPoller},
+};
+use http_body_util::{BodyExt, Full};
+use hyper::{Request, Response, body::Bytes, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use serde_json::{Value, json};
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::net::TcpListener;
+
+/// Answer of the mock op-node to `optimism_syncStatus`.
+#[derive(Clone)]
+enum Answer {
+    Status(Value),
+    Error(i32, &'static str),
+}
+
+/// Serves `optimism_syncStatus` with the current answer, returning the server URL.
+async fn mock_op_node(answer: Arc<Mutex<Answer>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let answer = answer.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| handle(answer.clone(), req));
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+
+    url
+}
+
+async fn handle(
+    answer: Arc<Mutex<Answer>>,
+    req: Request<hyper::body::Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let body = req.into_body().collect().await.unwrap().to_bytes();
+    let request: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(request["method"], "optimism_syncStatus");
+
+    let answer = answer.lock().unwrap().clone();
+    let response = match answer {
+        Answer::Status(status) => json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": status,
+        }),
+        Answer::Error(code, message) => json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": code, "message": message },
+        }),
+    };
+
+    Ok(Response::builder()
+        .header("content-type", "application/json")
+        .body(Full::new(Bytes::from(response.to_string())))
+        .unwrap())
+}
+
+/// Sync status as op-node serializes it.
+fn sync_status(unsafe_head: u64, safe_head: u64, finalized_head: u64) -> Value {
+    let l1_block = json!({
+        "hash": format!("0x{:064x}", 100),
+        "number": 100,
+        "parentHash": format!("0x{:064x}", 99),
+        "timestamp": 1_200,
+    });
+    let l2_block = |number: u64| {
+        json!({
+            "hash": format!("0x{:064x}", number),
+            "number": number,
+            "parentHash": format!("0x{:064x}", number.saturating_sub(1)),
+            "timestamp": 1_000 + 2 * number,
+            "l1origin": { "hash": format!("0x{:064x}", 90), "number": 90 },
+            "sequenceNumber": number % 6,
+        })
+    };
+
+    json!({
+        "current_l1": l1_block,
+        "current_l1_finalized": l1_block,
+        "head_l1": l1_block,
+        "safe_l1": l1_block,
+        "finalized_l1": l1_block,
+        "unsafe_l2": l2_block(unsafe_head),
+        "safe_l2": l2_block(safe_head),
+        "finalized_l2": l2_block(finalized_head),
+        "pending_safe_l2": l2_block(safe_head),
+        "cross_unsafe_l2": l2_block(unsafe_head),
+        "local_safe_l2": l2_block(safe_head),
+    })
+}
+
+fn test_channel_builder() -> Arc<Mutex<ChannelBuilder>> {
+    let db = DB::new(":memory:").unwrap();
+    db.initialize_database().unwrap();
+    Arc::new(Mutex::new(ChannelBuilder::new(
+        Arc::new(Mutex::new(db)),
+        FlushPolicy::new(10, None),
+    )))
+}
+
+#[tokio::test]
+async fn parses_sync_status() {
+    let answer = Arc::new(Mutex::new(Answer::Status(sync_status(120, 100, 80))));
+    let client = OpNodeClient::new(&mock_op_node(answer).await).unwrap();
+
+    let status = client.sync_status().await.unwrap();
+    assert_eq!(status.unsafe_l2.number, 120);
+    assert_eq!(status.safe_l2.number, 100);
+    assert_eq!(status.finalized_l2.number, 80);
+    assert_eq!(status.safe_l2.l1_origin.number, 90);
+    assert_eq!(status.safe_l2.timestamp, 1_200);
+    assert_eq!(status.finalized_l1.number, 100);
+    assert_eq!(status.safe_lag(), 20);
+}
+
+#[tokio::test]
+async fn poller_records_safe_and_finalized_heads() {
+    let answer = Arc::new(Mutex::new(Answer::Status(sync_status(1_000, 5, 3))));
+    let client = OpNodeClient::new(&mock_op_node(answer.clone()).await).unwrap();
+    let channel_builder = test_channel_builder();
+    let mut events = channel_builder.lock().unwrap().events().subscribe();
+    let poller =
+        SyncStatusPoller::new(client, channel_builder.clone(), Duration::from_secs(1), 600);
+
+    poller.poll().await.unwrap();
+    {
+        let channel_builder = channel_builder.lock().unwrap();
+        assert_eq!(channel_builder.safe_head(), Some(5));
+        assert_eq!(
+            channel_builder.sync_status().unwrap().finalized_l2.number,
+            3
+        );
+    }
+    assert_eq!(
+        events.try_recv().unwrap(),
+        ChannelEvent::DerivationBehind {
+            safe_head: 5,
+            unsafe_head: 1_000
+        }
+    );
+
+    // Still behind, not reported again
+    poller.poll().await.unwrap();
+    assert!(events.try_recv().is_err());
+
+    *answer.lock().unwrap() = Answer::Status(sync_status(1_000, 900, 850));
+    poller.poll().await.unwrap();
+    let channel_builder = channel_builder.lock().unwrap();
+    assert_eq!(channel_builder.safe_head(), Some(900));
+    assert_eq!(
+        channel_builder.sync_status().unwrap().finalized_l2.number,
+        850
+    );
+}
+
+#[tokio::test]
+async fn rpc_errors_keep_the_last_sync_status() {
+    let answer = Arc::new(Mutex::new(Answer::Status(sync_status(120, 100, 80))));
+    let client = OpNodeClient::new(&mock_op_node(answer.clone()).await).unwrap();
+    let channel_builder = test_channel_builder();
+    let poller = SyncStatusPoller::new(
+        client.clone(),
+        channel_builder.clone(),
+        Duration::from_secs(1),
+        600,
+    );
+    poller.poll().await.unwrap();
+
+    *answer.lock().unwrap() = Answer::Error(-32000, "sync status unavailable");
+    let err = client.sync_status().await.unwrap_err();
+    assert!(
+        err.to_string().contains("sync status unavailable"),
+        "unexpected error: {err}"
+    );
+    assert!(poller.poll().await.is_err());
+    assert_eq!(channel_builder.lock().unwrap().safe_head(), Some(100));
+
+    // Not a sync status
+    *answer.lock().unwrap() = Answer::Status(json!({ "safe_l2": 100 }));
+    assert!(client.sync_status().await.is_err());
+}
+
+#[tokio::test]
+async fn unreachable_op_node_fails() {
+    // Nothing listens on the port of a dropped listener
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    let client = OpNodeClient::new(&url).unwrap();
+    let err = client.sync_status().await.unwrap_err();
+    assert!(err.to_string().contains(&url), "unexpected error: {err}");
+}
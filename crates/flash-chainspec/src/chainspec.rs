@@ -1,58 +1,91 @@
 //! Odyssey chainspec parsing logic.
-use alloy_primitives::{U256, b256};
+use alloy_genesis::Genesis;
+use alloy_primitives::{B256, U256, b256};
+use reth_chainspec::ChainHardforks;
 use reth_op::{
-    chainspec::{
-        BaseFeeParams, BaseFeeParamsKind, Chain, ChainSpec, EthereumHardfork, Hardfork,
-        OpChainSpec, make_op_genesis_header,
-    },
+    chainspec::{Chain, ChainSpec, OpChainSpec, make_op_genesis_header},
     primitives::SealedHeader,
 };
-use reth_optimism_forks::{OP_SEPOLIA_HARDFORKS, OpHardfork};
-use std::sync::{Arc, LazyLock};
+use std::path::Path;
+
+use crate::{
+    hardforks::{HardforkTimes, validate_hardforks},
+    rollup::{RollupConfig, chain_op_config},
+};
 
 pub const FLASH_CHAIN_ID: u64 = 421;
 
-/// The FLASH CHAIN spec
-pub static FLASH_CHAIN: LazyLock<Arc<OpChainSpec>> = LazyLock::new(|| {
-    let genesis = serde_json::from_str(include_str!("../../../config/genesis.json"))
-        .expect("Can't deserialize Flash genesis json");
+/// Hash of the FLASH CHAIN genesis block.
+pub const FLASH_GENESIS_HASH: B256 =
+    b256!("0x2a68ab477176e988e21ea9ea6eb852a9fb2d39341debc8fb1d69a6f0a20926b7");
 
-    let mut hardforks = OP_SEPOLIA_HARDFORKS.clone();
+/// Reads a genesis file.
+pub fn load_genesis(path: &Path) -> eyre::Result<Genesis> {
+    let genesis = std::fs::read_to_string(path)
+        .map_err(|e| eyre::eyre!("Failed to read genesis file {}: {}", path.display(), e))?;
 
-    // Remove hardforks that aren't supported by our genesis.json
-    // used an old versio of op-deployer, so these hardforks are not supported
-    hardforks.remove(OpHardfork::Holocene);
-    hardforks.remove(OpHardfork::Isthmus);
-    hardforks.remove(EthereumHardfork::Prague);
+    serde_json::from_str(&genesis)
+        .map_err(|e| eyre::eyre!("Failed to parse genesis file {}: {}", path.display(), e))
+}
 
-    // let hardforks = ODYSSEY_FORKS.clone();
+/// A chain with the FLASH CHAIN parameters, from any genesis and base `hardforks`.
+///
+/// The genesis hash is computed from the genesis. Holocene, Isthmus and Prague are scheduled from
+/// the genesis `config`, unless overridden. The EIP-1559 parameters are read from the genesis
+/// `config` and the rollup config, which must agree with each other and with the genesis.
+pub fn flash_chain_from_genesis(
+    genesis: Genesis,
+    mut hardforks: ChainHardforks,
+    overrides: HardforkTimes,
+    rollup: Option<&RollupConfig>,
+) -> eyre::Result<OpChainSpec> {
+    HardforkTimes::from_genesis(&genesis)?
+        .with_overrides(overrides)
+        .resolve()?
+        .apply(&mut hardforks);
+    let base_fee_params = chain_op_config(&genesis, rollup)?.base_fee_params();
 
-    OpChainSpec {
+    let chainspec = OpChainSpec {
         inner: ChainSpec {
-            chain: Chain::from_id(FLASH_CHAIN_ID),
-            genesis_header: SealedHeader::new(
-                make_op_genesis_header(&genesis, &hardforks),
-                b256!("0x2a68ab477176e988e21ea9ea6eb852a9fb2d39341debc8fb1d69a6f0a20926b7"),
-            ),
+            chain: Chain::from_id(genesis.config.chain_id),
+            genesis_header: SealedHeader::seal_slow(make_op_genesis_header(&genesis, &hardforks)),
             genesis,
             paris_block_and_final_difficulty: Some((0, U256::from(0))),
             hardforks,
-            base_fee_params: BaseFeeParamsKind::Variable(
-                vec![
-                    (
-                        EthereumHardfork::London.boxed(),
-                        BaseFeeParams::optimism_sepolia(),
-                    ),
-                    (
-                        OpHardfork::Canyon.boxed(),
-                        BaseFeeParams::optimism_sepolia_canyon(),
-                    ),
-                ]
-                .into(),
-            ),
+            base_fee_params,
             prune_delete_limit: 10000,
             ..Default::default()
         },
+    };
+
+    validate_hardforks(&chainspec)?;
+    if let Some(rollup) = rollup {
+        validate_rollup_config(&chainspec, rollup)?;
     }
-    .into()
-});
+
+    Ok(chainspec)
+}
+
+/// Checks the rollup config op-node runs with is for this chain.
+fn validate_rollup_config(chainspec: &OpChainSpec, rollup: &RollupConfig) -> eyre::Result<()> {
+    let genesis_hash = chainspec.inner.genesis_header.hash();
+    if rollup.genesis.l2.hash != genesis_hash || rollup.genesis.l2.number != 0 {
+        return Err(eyre::eyre!(
+            "Rollup config starts from L2 block {} {}, but the genesis is {}",
+            rollup.genesis.l2.number,
+            rollup.genesis.l2.hash,
+            genesis_hash
+        ));
+    }
+
+    let chain_id = chainspec.inner.genesis.config.chain_id;
+    if rollup.l2_chain_id != chain_id {
+        return Err(eyre::eyre!(
+            "Rollup config is for chain {}, but the genesis is for chain {}",
+            rollup.l2_chain_id,
+            chain_id
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,528 @@
+//! Generation of the genesis and rollup config of a new chain.
+use alloy_genesis::{Genesis, GenesisAccount};
+use alloy_primitives::{Address, B256, U256};
+use clap::Args;
+use reth_op::chainspec::BaseFeeParams;
+use reth_optimism_forks::{OP_SEPOLIA_HARDFORKS, OpHardfork};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    chainspec::flash_chain_from_genesis,
+    hardforks::{HOLOCENE_TIME_FIELD, HardforkTimes, ISTHMUS_TIME_FIELD},
+    rollup::{ChainOpConfig, GENESIS_OPTIMISM_FIELD, chain_op_config, encode_holocene_extra_data},
+};
+
+/// Version byte of the Ecotone system config scalar.
+const ECOTONE_SCALAR_VERSION: u8 = 1;
+
+/// Generate the genesis and rollup config of a new chain.
+#[derive(Debug, Args)]
+pub struct GenesisCommand {
+    /// Directory `genesis.json` and `rollup.json` are written to.
+    #[arg(long, value_name = "DIR")]
+    pub output: PathBuf,
+
+    /// Overwrite existing files.
+    #[arg(long)]
+    pub force: bool,
+
+    /// L2 chain id.
+    #[arg(long, value_name = "CHAIN_ID")]
+    pub chain_id: u64,
+
+    /// Genesis timestamp, now by default.
+    #[arg(long, value_name = "TIMESTAMP")]
+    pub timestamp: Option<u64>,
+
+    /// Genesis block gas limit.
+    #[arg(long, value_name = "GAS", default_value_t = 60_000_000)]
+    pub gas_limit: u64,
+
+    /// L1 chain id.
+    #[arg(
+        long = "l1.chain-id",
+        value_name = "CHAIN_ID",
+        default_value_t = 11155111
+    )]
+    pub l1_chain_id: u64,
+
+    /// Hash of the L1 block the chain starts from.
+    #[arg(long = "l1.genesis-hash", value_name = "HASH")]
+    pub l1_genesis_hash: B256,
+
+    /// Number of the L1 block the chain starts from.
+    #[arg(long = "l1.genesis-number", value_name = "NUMBER")]
+    pub l1_genesis_number: u64,
+
+    /// Batch inbox address batches are sent to.
+    #[arg(long = "l1.batch-inbox", value_name = "ADDRESS")]
+    pub batch_inbox: Address,
+
+    /// SystemConfig contract.
+    #[arg(long = "l1.system-config", value_name = "ADDRESS")]
+    pub system_config: Address,
+
+    /// Deposit contract, the OptimismPortal.
+    #[arg(long = "l1.deposit-contract", value_name = "ADDRESS")]
+    pub deposit_contract: Address,
+
+    /// ProtocolVersions contract.
+    #[arg(long = "l1.protocol-versions", value_name = "ADDRESS", default_value_t = Address::ZERO)]
+    pub protocol_versions: Address,
+
+    /// Batcher address of the genesis system config.
+    #[arg(long, value_name = "ADDRESS")]
+    pub batcher: Address,
+
+    /// Ecotone L1 base fee scalar of the genesis system config.
+    #[arg(long, value_name = "SCALAR", default_value_t = 1368)]
+    pub base_fee_scalar: u32,
+
+    /// Ecotone L1 blob base fee scalar of the genesis system config.
+    #[arg(long, value_name = "SCALAR", default_value_t = 810949)]
+    pub blob_base_fee_scalar: u32,
+
+    /// Seconds between L2 blocks.
+    #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+    pub block_time: u64,
+
+    /// Holocene activation timestamp, active from genesis by default.
+    #[arg(long, value_name = "TIMESTAMP")]
+    pub holocene_time: Option<u64>,
+
+    /// Isthmus and Prague activation timestamp, active from genesis by default.
+    #[arg(long, value_name = "TIMESTAMP")]
+    pub isthmus_time: Option<u64>,
+
+    /// EIP-1559 elasticity multiplier.
+    #[arg(long, default_value_t = ChainOpConfig::OP_SEPOLIA.eip1559_elasticity)]
+    pub eip1559_elasticity: u64,
+
+    /// EIP-1559 base fee max change denominator, before Canyon.
+    #[arg(long, default_value_t = ChainOpConfig::OP_SEPOLIA.eip1559_denominator)]
+    pub eip1559_denominator: u64,
+
+    /// EIP-1559 base fee max change denominator, since Canyon.
+    #[arg(long, default_value_t = ChainOpConfig::OP_SEPOLIA.eip1559_denominator_canyon)]
+    pub eip1559_denominator_canyon: u64,
+
+    /// Account prefunded with a balance in wei. Can be repeated.
+    #[arg(long = "alloc", value_name = "ADDRESS=WEI", value_parser = parse_alloc)]
+    pub allocs: Vec<(Address, U256)>,
+
+    /// Genesis alloc file of the predeploys, as dumped by the OP contracts. Can be repeated.
+    #[arg(long = "predeploys", value_name = "PATH")]
+    pub predeploys: Vec<PathBuf>,
+}
+
+fn parse_alloc(s: &str) -> Result<(Address, U256), String> {
+    let (address, balance) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected ADDRESS=WEI, got '{s}'"))?;
+    let address = address
+        .parse()
+        .map_err(|e| format!("invalid address '{address}': {e}"))?;
+    let balance = balance
+        .parse()
+        .map_err(|e| format!("invalid balance '{balance}': {e}"))?;
+    Ok((address, balance))
+}
+
+/// Reads a genesis alloc file.
+fn load_alloc(path: &Path) -> eyre::Result<BTreeMap<Address, GenesisAccount>> {
+    let alloc = std::fs::read_to_string(path)
+        .map_err(|e| eyre::eyre!("Failed to read alloc file {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&alloc)
+        .map_err(|e| eyre::eyre!("Failed to parse alloc file {}: {}", path.display(), e))
+}
+
+/// The genesis of an OP chain starting at `timestamp`, with every hardfork up to Granite active
+/// from it and Holocene, Isthmus and Prague at `hardforks`.
+pub fn op_genesis(
+    chain_id: u64,
+    timestamp: u64,
+    gas_limit: u64,
+    chain_op_config: ChainOpConfig,
+    hardforks: HardforkTimes,
+    alloc: BTreeMap<Address, GenesisAccount>,
+) -> eyre::Result<Genesis> {
+    // Holocene blocks carry their EIP-1559 parameters, the genesis too when active from it
+    let extra_data = match hardforks.holocene {
+        Some(holocene) if holocene <= timestamp => encode_holocene_extra_data(BaseFeeParams::new(
+            chain_op_config.eip1559_denominator_canyon as u128,
+            chain_op_config.eip1559_elasticity as u128,
+        ))?,
+        _ => Default::default(),
+    };
+
+    let genesis = json!({
+        "config": {
+            "chainId": chain_id,
+            "homesteadBlock": 0,
+            "eip150Block": 0,
+            "eip155Block": 0,
+            "eip158Block": 0,
+            "byzantiumBlock": 0,
+            "constantinopleBlock": 0,
+            "petersburgBlock": 0,
+            "istanbulBlock": 0,
+            "muirGlacierBlock": 0,
+            "berlinBlock": 0,
+            "londonBlock": 0,
+            "arrowGlacierBlock": 0,
+            "grayGlacierBlock": 0,
+            "mergeNetsplitBlock": 0,
+            "shanghaiTime": 0,
+            "cancunTime": 0,
+            "pragueTime": hardforks.prague,
+            "bedrockBlock": 0,
+            "regolithTime": 0,
+            "canyonTime": 0,
+            "deltaTime": 0,
+            "ecotoneTime": 0,
+            "fjordTime": 0,
+            "graniteTime": 0,
+            HOLOCENE_TIME_FIELD: hardforks.holocene,
+            ISTHMUS_TIME_FIELD: hardforks.isthmus,
+            "terminalTotalDifficulty": 0,
+            "terminalTotalDifficultyPassed": true,
+            GENESIS_OPTIMISM_FIELD: {
+                "eip1559Elasticity": chain_op_config.eip1559_elasticity,
+                "eip1559Denominator": chain_op_config.eip1559_denominator,
+                "eip1559DenominatorCanyon": chain_op_config.eip1559_denominator_canyon,
+            },
+        },
+        "nonce": "0x0",
+        "timestamp": format!("{timestamp:#x}"),
+        "extraData": extra_data,
+        "gasLimit": format!("{gas_limit:#x}"),
+        "difficulty": "0x0",
+        "mixHash": B256::ZERO,
+        "coinbase": Address::ZERO,
+        "baseFeePerGas": "0x3b9aca00",
+        "alloc": alloc,
+        "number": "0x0",
+        "gasUsed": "0x0",
+        "parentHash": B256::ZERO,
+    });
+
+    serde_json::from_value(genesis).map_err(|e| eyre::eyre!("Invalid genesis: {}", e))
+}
+
+/// The L1 side of an OP chain: the rollup config fields its genesis doesn't hold.
+#[derive(Debug, Clone, Copy)]
+pub struct L1Deployment {
+    pub l1_chain_id: u64,
+    /// L1 block the chain starts from.
+    pub l1_genesis_hash: B256,
+    pub l1_genesis_number: u64,
+    pub batch_inbox: Address,
+    pub system_config: Address,
+    /// The OptimismPortal.
+    pub deposit_contract: Address,
+    pub protocol_versions: Address,
+    /// Batcher address of the genesis system config.
+    pub batcher: Address,
+    /// Ecotone L1 fee scalars of the genesis system config.
+    pub base_fee_scalar: u32,
+    pub blob_base_fee_scalar: u32,
+    /// Seconds between L2 blocks.
+    pub block_time: u64,
+}
+
+/// The op-node rollup config of the chain starting from `genesis`, which hashes to
+/// `genesis_hash`, with Holocene, Isthmus and Prague at `hardforks`.
+pub fn op_rollup_config(
+    genesis: &Genesis,
+    genesis_hash: B256,
+    hardforks: HardforkTimes,
+    l1: &L1Deployment,
+) -> eyre::Result<serde_json::Value> {
+    let mut scalar = B256::ZERO;
+    scalar[0] = ECOTONE_SCALAR_VERSION;
+    scalar[24..28].copy_from_slice(&l1.blob_base_fee_scalar.to_be_bytes());
+    scalar[28..].copy_from_slice(&l1.base_fee_scalar.to_be_bytes());
+    let chain_op_config = chain_op_config(genesis, None)?;
+
+    Ok(json!({
+        "genesis": {
+            "l1": {
+                "hash": l1.l1_genesis_hash,
+                "number": l1.l1_genesis_number,
+            },
+            "l2": {
+                "hash": genesis_hash,
+                "number": 0,
+            },
+            "l2_time": genesis.timestamp,
+            "system_config": {
+                "batcherAddr": l1.batcher,
+                "overhead": B256::ZERO,
+                "scalar": scalar,
+                "gasLimit": genesis.gas_limit,
+            },
+        },
+        "block_time": l1.block_time,
+        "max_sequencer_drift": 600,
+        "seq_window_size": 3600,
+        "channel_timeout": 300,
+        "l1_chain_id": l1.l1_chain_id,
+        "l2_chain_id": genesis.config.chain_id,
+        "regolith_time": 0,
+        "canyon_time": 0,
+        "delta_time": 0,
+        "ecotone_time": 0,
+        "fjord_time": 0,
+        "granite_time": 0,
+        "holocene_time": hardforks.holocene,
+        "isthmus_time": hardforks.isthmus,
+        "batch_inbox_address": l1.batch_inbox,
+        "deposit_contract_address": l1.deposit_contract,
+        "l1_system_config_address": l1.system_config,
+        "protocol_versions_address": l1.protocol_versions,
+        "chain_op_config": {
+            "eip1559Elasticity": chain_op_config.eip1559_elasticity,
+            "eip1559Denominator": chain_op_config.eip1559_denominator,
+            "eip1559DenominatorCanyon": chain_op_config.eip1559_denominator_canyon,
+        },
+    }))
+}
+
+impl GenesisCommand {
+    /// Genesis accounts: the predeploys, then the prefunded accounts.
+    fn alloc(&self) -> eyre::Result<BTreeMap<Address, GenesisAccount>> {
+        let mut alloc = BTreeMap::new();
+        for path in &self.predeploys {
+            for (address, account) in load_alloc(path)? {
+                if alloc.insert(address, account).is_some() {
+                    return Err(eyre::eyre!("Predeploy {} is set in several files", address));
+                }
+            }
+        }
+
+        for (address, balance) in &self.allocs {
+            let account = alloc
+                .entry(*address)
+                .or_insert_with(GenesisAccount::default);
+            account.balance = account.balance.saturating_add(*balance);
+        }
+
+        Ok(alloc)
+    }
+
+    fn chain_op_config(&self) -> ChainOpConfig {
+        ChainOpConfig {
+            eip1559_elasticity: self.eip1559_elasticity,
+            eip1559_denominator: self.eip1559_denominator,
+            eip1559_denominator_canyon: self.eip1559_denominator_canyon,
+        }
+    }
+
+    /// The genesis, with every hardfork up to Granite active from it.
+    fn genesis(&self, timestamp: u64, hardforks: HardforkTimes) -> eyre::Result<Genesis> {
+        op_genesis(
+            self.chain_id,
+            timestamp,
+            self.gas_limit,
+            self.chain_op_config(),
+            hardforks,
+            self.alloc()?,
+        )
+    }
+
+    /// The L1 side of the chain, set on the command line.
+    fn l1_deployment(&self) -> L1Deployment {
+        L1Deployment {
+            l1_chain_id: self.l1_chain_id,
+            l1_genesis_hash: self.l1_genesis_hash,
+            l1_genesis_number: self.l1_genesis_number,
+            batch_inbox: self.batch_inbox,
+            system_config: self.system_config,
+            deposit_contract: self.deposit_contract,
+            protocol_versions: self.protocol_versions,
+            batcher: self.batcher,
+            base_fee_scalar: self.base_fee_scalar,
+            blob_base_fee_scalar: self.blob_base_fee_scalar,
+            block_time: self.block_time,
+        }
+    }
+
+    fn write(&self, name: &str, value: &impl serde::Serialize) -> eyre::Result<PathBuf> {
+        let path = self.output.join(name);
+        if path.exists() && !self.force {
+            return Err(eyre::eyre!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            ));
+        }
+
+        let json = serde_json::to_string_pretty(value)?;
+        std::fs::write(&path, json + "\n")
+            .map_err(|e| eyre::eyre!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    pub fn execute(self) -> eyre::Result<()> {
+        let (genesis_path, rollup_path, genesis_hash) = self.write_chain()?;
+
+        println!("Wrote {}", genesis_path.display());
+        println!("Wrote {}", rollup_path.display());
+        println!("L2 genesis hash: {genesis_hash}");
+        println!(
+            "Run it with `--chain flash:{} --flash.rollup-config {}`",
+            genesis_path.display(),
+            rollup_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Writes the genesis and rollup config, returning their paths and the genesis hash.
+    fn write_chain(&self) -> eyre::Result<(PathBuf, PathBuf, B256)> {
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp,
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        };
+
+        // Chains run with the OP Sepolia hardforks up to Granite, active from genesis here
+        let granite = OP_SEPOLIA_HARDFORKS
+            .fork(OpHardfork::Granite)
+            .as_timestamp()
+            .unwrap_or_default();
+        if timestamp < granite {
+            return Err(eyre::eyre!(
+                "Genesis at {} predates Granite at {}",
+                timestamp,
+                granite
+            ));
+        }
+
+        let hardforks = HardforkTimes {
+            holocene: Some(self.holocene_time.unwrap_or(timestamp)),
+            isthmus: Some(self.isthmus_time.unwrap_or(timestamp)),
+            prague: None,
+        }
+        .resolve()?;
+
+        let genesis = self.genesis(timestamp, hardforks)?;
+        // The hash the node computes, so the rollup config always matches the genesis
+        let chainspec = flash_chain_from_genesis(
+            genesis.clone(),
+            OP_SEPOLIA_HARDFORKS.clone(),
+            HardforkTimes::default(),
+            None,
+        )?;
+        let genesis_hash = chainspec.inner.genesis_header.hash();
+
+        std::fs::create_dir_all(&self.output)
+            .map_err(|e| eyre::eyre!("Failed to create {}: {}", self.output.display(), e))?;
+        let genesis_path = self.write("genesis.json", &genesis)?;
+        let rollup_config =
+            op_rollup_config(&genesis, genesis_hash, hardforks, &self.l1_deployment())?;
+        let rollup_path = self.write("rollup.json", &rollup_config)?;
+
+        Ok((genesis_path, rollup_path, genesis_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlashChainSpecParser, chainspec::load_genesis, rollup::RollupConfig};
+    use clap::Parser;
+    use reth_cli::chainspec::ChainSpecParser;
+
+    #[derive(Debug, Parser)]
+    struct Cli {
+        #[command(flatten)]
+        genesis: GenesisCommand,
+    }
+
+    /// Genesis timestamp of the tested chains, after the OP Sepolia Granite activation.
+    const TIMESTAMP: &str = "--timestamp=1750000000";
+
+    fn command(output: &Path, extra: &[&str]) -> GenesisCommand {
+        let args = [
+            "genesis".to_string(),
+            format!("--output={}", output.display()),
+            "--chain-id=4242".to_string(),
+            format!("--l1.genesis-hash={}", B256::repeat_byte(1)),
+            "--l1.genesis-number=100".to_string(),
+            format!("--l1.batch-inbox={}", Address::repeat_byte(2)),
+            format!("--l1.system-config={}", Address::repeat_byte(3)),
+            format!("--l1.deposit-contract={}", Address::repeat_byte(4)),
+            format!("--batcher={}", Address::repeat_byte(5)),
+        ];
+
+        Cli::parse_from(
+            args.into_iter()
+                .chain(extra.iter().map(|arg| arg.to_string())),
+        )
+        .genesis
+    }
+
+    #[test]
+    fn written_genesis_hashes_to_the_printed_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let alloc = format!("--alloc={}=1000", Address::repeat_byte(6));
+        for extra in [
+            vec![TIMESTAMP, "--force"],
+            vec![
+                TIMESTAMP,
+                "--force",
+                alloc.as_str(),
+                "--isthmus-time=1750001000",
+            ],
+        ] {
+            let (genesis_path, rollup_path, genesis_hash) =
+                command(dir.path(), &extra).write_chain().unwrap();
+
+            // The node parses the written genesis to the same hash
+            let chainspec =
+                FlashChainSpecParser::parse(&format!("flash:{}", genesis_path.display())).unwrap();
+            assert_eq!(chainspec.inner.genesis_header.hash(), genesis_hash);
+
+            // And op-node starts from it
+            let rollup = RollupConfig::load(&rollup_path).unwrap();
+            assert_eq!(rollup.genesis.l2.hash, genesis_hash);
+            assert_eq!(rollup.l2_chain_id, 4242);
+            flash_chain_from_genesis(
+                load_genesis(&genesis_path).unwrap(),
+                OP_SEPOLIA_HARDFORKS.clone(),
+                HardforkTimes::default(),
+                Some(&rollup),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn keeps_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        command(dir.path(), &[TIMESTAMP]).write_chain().unwrap();
+
+        let error = command(dir.path(), &[TIMESTAMP]).write_chain().unwrap_err();
+        assert!(error.to_string().contains("use --force to overwrite it"));
+        command(dir.path(), &[TIMESTAMP, "--force"])
+            .write_chain()
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_genesis_before_granite() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(
+            command(dir.path(), &["--timestamp=0"])
+                .write_chain()
+                .is_err()
+        );
+        assert!(!dir.path().join("genesis.json").exists());
+    }
+}
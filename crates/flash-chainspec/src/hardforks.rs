@@ -0,0 +1,210 @@
+//! Scheduling of the hardforks the flash chain genesis predates.
+use alloy_genesis::Genesis;
+use reth_chainspec::{ChainHardforks, EthereumHardfork, ForkCondition, Hardforks};
+use reth_op::chainspec::OpChainSpec;
+use reth_optimism_forks::OpHardfork;
+
+use crate::rollup::{decode_holocene_extra_data, encode_holocene_extra_data};
+
+/// Genesis `config` field of the Holocene activation timestamp.
+pub const HOLOCENE_TIME_FIELD: &str = "holoceneTime";
+/// Genesis `config` field of the Isthmus activation timestamp.
+pub const ISTHMUS_TIME_FIELD: &str = "isthmusTime";
+
+/// Activation timestamps of Holocene, Isthmus and Prague, `None` if not scheduled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HardforkTimes {
+    pub holocene: Option<u64>,
+    pub isthmus: Option<u64>,
+    pub prague: Option<u64>,
+}
+
+impl HardforkTimes {
+    /// Reads the timestamps from the genesis `config`, as op-geth does.
+    pub fn from_genesis(genesis: &Genesis) -> eyre::Result<Self> {
+        let field = |name: &str| {
+            genesis
+                .config
+                .extra_fields
+                .get_deserialized::<u64>(name)
+                .transpose()
+                .map_err(|e| eyre::eyre!("Invalid genesis config {}: {}", name, e))
+        };
+
+        Ok(Self {
+            holocene: field(HOLOCENE_TIME_FIELD)?,
+            isthmus: field(ISTHMUS_TIME_FIELD)?,
+            prague: genesis.config.prague_time,
+        })
+    }
+
+    /// Returns these timestamps, replaced by the ones set in `overrides`.
+    pub fn with_overrides(self, overrides: Self) -> Self {
+        Self {
+            holocene: overrides.holocene.or(self.holocene),
+            isthmus: overrides.isthmus.or(self.isthmus),
+            prague: overrides.prague.or(self.prague),
+        }
+    }
+
+    /// Checks the schedule is one op-reth can follow.
+    ///
+    /// Prague activates along Isthmus on OP chains, and defaults to its timestamp.
+    pub fn resolve(self) -> eyre::Result<Self> {
+        let prague = match (self.isthmus, self.prague) {
+            (Some(isthmus), None) => Some(isthmus),
+            (isthmus, prague) if isthmus == prague => prague,
+            (Some(isthmus), Some(prague)) => {
+                return Err(eyre::eyre!(
+                    "Prague must activate with Isthmus, at {}, not at {}",
+                    isthmus,
+                    prague
+                ));
+            }
+            (None, Some(_)) => {
+                return Err(eyre::eyre!(
+                    "Prague activates with Isthmus on OP chains, schedule Isthmus instead"
+                ));
+            }
+        };
+
+        match (self.holocene, self.isthmus) {
+            (None, Some(_)) => {
+                return Err(eyre::eyre!("Isthmus requires Holocene to be scheduled"));
+            }
+            (Some(holocene), Some(isthmus)) if isthmus < holocene => {
+                return Err(eyre::eyre!(
+                    "Isthmus at {} activates before Holocene at {}",
+                    isthmus,
+                    holocene
+                ));
+            }
+            _ => {}
+        }
+
+        Ok(Self { prague, ..self })
+    }
+
+    /// Schedules the forks in `hardforks`, in activation order.
+    pub fn apply(&self, hardforks: &mut ChainHardforks) {
+        hardforks.remove(OpHardfork::Holocene);
+        hardforks.remove(OpHardfork::Isthmus);
+        hardforks.remove(EthereumHardfork::Prague);
+
+        if let Some(holocene) = self.holocene {
+            hardforks.insert(OpHardfork::Holocene, ForkCondition::Timestamp(holocene));
+        }
+        if let Some(prague) = self.prague {
+            hardforks.insert(EthereumHardfork::Prague, ForkCondition::Timestamp(prague));
+        }
+        if let Some(isthmus) = self.isthmus {
+            hardforks.insert(OpHardfork::Isthmus, ForkCondition::Timestamp(isthmus));
+        }
+    }
+}
+
+/// Checks the chain can produce blocks under the scheduled forks.
+///
+/// Holocene moves the EIP-1559 parameters to the block `extraData`: the payload builder encodes
+/// them there, and the base fee of the next block is decoded from the parent one. The parameters
+/// must fit the encoding, and the genesis must carry them when Holocene is active from it.
+pub fn validate_hardforks(chainspec: &OpChainSpec) -> eyre::Result<()> {
+    let Some(holocene) = chainspec.fork(OpHardfork::Holocene).as_timestamp() else {
+        return Ok(());
+    };
+
+    if let Some(granite) = chainspec
+        .fork(OpHardfork::Granite)
+        .as_timestamp()
+        .filter(|granite| holocene < *granite)
+    {
+        return Err(eyre::eyre!(
+            "Holocene at {} activates before Granite at {}",
+            holocene,
+            granite
+        ));
+    }
+
+    // Default parameters the payload builder encodes when the attributes carry none
+    encode_holocene_extra_data(chainspec.inner.base_fee_params_at_timestamp(holocene))
+        .map_err(|e| eyre::eyre!("Invalid EIP-1559 parameters at Holocene: {}", e))?;
+
+    let genesis = &chainspec.inner.genesis;
+    if holocene <= genesis.timestamp {
+        decode_holocene_extra_data(&genesis.extra_data).map_err(|e| {
+            eyre::eyre!(
+                "Holocene is active at genesis, but the genesis extraData {} doesn't hold EIP-1559 parameters: {}",
+                genesis.extra_data,
+                e
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(holocene: Option<u64>, isthmus: Option<u64>, prague: Option<u64>) -> HardforkTimes {
+        HardforkTimes {
+            holocene,
+            isthmus,
+            prague,
+        }
+    }
+
+    #[test]
+    fn prague_defaults_to_isthmus() {
+        assert_eq!(
+            times(Some(10), Some(20), None).resolve().unwrap(),
+            times(Some(10), Some(20), Some(20))
+        );
+        assert_eq!(
+            times(Some(10), Some(20), Some(20)).resolve().unwrap(),
+            times(Some(10), Some(20), Some(20))
+        );
+        assert_eq!(
+            times(Some(10), None, None).resolve().unwrap(),
+            times(Some(10), None, None)
+        );
+        assert_eq!(
+            HardforkTimes::default().resolve().unwrap(),
+            HardforkTimes::default()
+        );
+    }
+
+    #[test]
+    fn prague_activates_with_isthmus() {
+        assert!(times(Some(10), Some(20), Some(30)).resolve().is_err());
+        assert!(times(Some(10), Some(20), Some(10)).resolve().is_err());
+        assert!(times(Some(10), None, Some(20)).resolve().is_err());
+    }
+
+    #[test]
+    fn isthmus_follows_holocene() {
+        // Same block is fine, the forks are applied in order
+        assert!(times(Some(10), Some(10), None).resolve().is_ok());
+        assert!(times(Some(20), Some(10), None).resolve().is_err());
+        assert!(times(None, Some(10), None).resolve().is_err());
+    }
+
+    #[test]
+    fn overrides_replace_set_times() {
+        let genesis = times(Some(10), Some(20), Some(20));
+
+        assert_eq!(
+            genesis.with_overrides(times(None, Some(30), None)),
+            times(Some(10), Some(30), Some(20))
+        );
+        // The genesis Prague no longer matches the overridden Isthmus
+        assert!(
+            genesis
+                .with_overrides(times(None, Some(30), None))
+                .resolve()
+                .is_err()
+        );
+        assert_eq!(genesis.with_overrides(HardforkTimes::default()), genesis);
+    }
+}
@@ -2,11 +2,122 @@
 use reth_op::chainspec::OpChainSpec;
 // OpHardfork needs to be imported directly
 use reth_cli::chainspec::{ChainSpecParser, parse_genesis};
-use std::sync::Arc;
+use reth_optimism_forks::OP_SEPOLIA_HARDFORKS;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
 
-use crate::chainspec::FLASH_CHAIN;
+use crate::{
+    chainspec::{flash_chain_from_genesis, load_genesis},
+    hardforks::HardforkTimes,
+    presets::{FLASH, FLASH_DEV, FLASH_TESTNET, preset},
+    rollup::RollupConfig,
+};
 
 pub mod chainspec;
+pub mod genesis;
+pub mod hardforks;
+pub mod presets;
+pub mod rollup;
+
+/// Argument setting the genesis file of the named chains read from a file.
+pub const GENESIS_ARG: &str = "--flash.genesis";
+/// Argument setting the op-node rollup config the chain is checked against.
+pub const ROLLUP_CONFIG_ARG: &str = "--flash.rollup-config";
+/// Argument overriding the Holocene activation timestamp.
+pub const HOLOCENE_TIME_ARG: &str = "--flash.holocene-time";
+/// Argument overriding the Isthmus activation timestamp.
+pub const ISTHMUS_TIME_ARG: &str = "--flash.isthmus-time";
+/// Argument overriding the Prague activation timestamp.
+pub const PRAGUE_TIME_ARG: &str = "--flash.prague-time";
+
+/// Environment variable setting the genesis file of the named chains read from a file.
+pub const GENESIS_ENV: &str = "FLASH_GENESIS";
+
+/// Chain arguments taken out of the command line.
+#[derive(Debug, Clone, Default)]
+pub struct FlashChainArgs {
+    pub genesis: Option<PathBuf>,
+    pub rollup_config: Option<PathBuf>,
+    pub hardforks: HardforkTimes,
+}
+
+static CHAIN_ARGS: OnceLock<FlashChainArgs> = OnceLock::new();
+
+fn chain_args() -> FlashChainArgs {
+    CHAIN_ARGS.get().cloned().unwrap_or_default()
+}
+
+/// Loads the rollup config set with [`ROLLUP_CONFIG_ARG`], if any.
+fn rollup_config() -> eyre::Result<Option<RollupConfig>> {
+    chain_args()
+        .rollup_config
+        .map(|path| RollupConfig::load(&path))
+        .transpose()
+}
+
+/// Takes the `--flash.*` chain arguments out of the command line arguments, and sets them for
+/// the chains parsed afterwards.
+///
+/// The chain is parsed along the other arguments, so the genesis file and hardfork overrides
+/// have to be known before they are, and every command taking `--chain` accepts them.
+pub fn take_flash_args(args: impl IntoIterator<Item = String>) -> eyre::Result<Vec<String>> {
+    let (chain_args, remaining) = split_flash_args(args)?;
+
+    CHAIN_ARGS
+        .set(chain_args)
+        .map_err(|_| eyre::eyre!("Flash chain arguments are already set"))?;
+
+    Ok(remaining)
+}
+
+/// Splits the `--flash.*` chain arguments from the other command line arguments.
+fn split_flash_args(
+    args: impl IntoIterator<Item = String>,
+) -> eyre::Result<(FlashChainArgs, Vec<String>)> {
+    let mut args = args.into_iter();
+    let mut remaining = Vec::new();
+    let mut chain_args = FlashChainArgs::default();
+
+    while let Some(arg) = args.next() {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        if ![
+            GENESIS_ARG,
+            ROLLUP_CONFIG_ARG,
+            HOLOCENE_TIME_ARG,
+            ISTHMUS_TIME_ARG,
+            PRAGUE_TIME_ARG,
+        ]
+        .contains(&name.as_str())
+        {
+            remaining.push(arg);
+            continue;
+        }
+
+        let value = value
+            .or_else(|| args.next())
+            .ok_or_else(|| eyre::eyre!("{} requires a value", name))?;
+        let timestamp = || {
+            value
+                .parse::<u64>()
+                .map_err(|e| eyre::eyre!("Invalid {} '{}': {}", name, value, e))
+        };
+
+        match name.as_str() {
+            GENESIS_ARG => chain_args.genesis = Some(PathBuf::from(&value)),
+            ROLLUP_CONFIG_ARG => chain_args.rollup_config = Some(PathBuf::from(&value)),
+            HOLOCENE_TIME_ARG => chain_args.hardforks.holocene = Some(timestamp()?),
+            ISTHMUS_TIME_ARG => chain_args.hardforks.isthmus = Some(timestamp()?),
+            _ => chain_args.hardforks.prague = Some(timestamp()?),
+        }
+    }
+
+    Ok((chain_args, remaining))
+}
 
 /// Odyssey chain specification parser.
 #[derive(Debug, Clone, Default)]
@@ -15,15 +126,99 @@ pub struct FlashChainSpecParser;
 impl ChainSpecParser for FlashChainSpecParser {
     type ChainSpec = OpChainSpec;
 
-    const SUPPORTED_CHAINS: &'static [&'static str] = &["flash"];
+    const SUPPORTED_CHAINS: &'static [&'static str] = &[FLASH, FLASH_DEV, FLASH_TESTNET];
 
+    /// Parses a named chain, `flash:<path>` to a genesis file with the flash hardforks, or any
+    /// genesis file or JSON.
     fn parse(s: &str) -> eyre::Result<Arc<Self::ChainSpec>> {
-        Ok(match s {
-            "flash" => FLASH_CHAIN.clone(),
-            s => {
+        if let Some(preset) = preset(s) {
+            return Ok(Arc::new(preset.chainspec(&chain_args())?));
+        }
+
+        Ok(match s.strip_prefix("flash:") {
+            Some(path) => Arc::new(flash_chain_from_genesis(
+                load_genesis(Path::new(path))?,
+                OP_SEPOLIA_HARDFORKS.clone(),
+                chain_args().hardforks,
+                rollup_config()?.as_ref(),
+            )?),
+            None => {
                 let chainspec = OpChainSpec::from(parse_genesis(s)?);
                 Arc::new(chainspec)
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_flash_args() {
+        let (chain_args, remaining) = split_flash_args(args(&[
+            "flash-node",
+            "node",
+            "--chain",
+            "flash",
+            "--flash.genesis=genesis.json",
+            "--flash.rollup-config",
+            "rollup.json",
+            "--flash.holocene-time",
+            "10",
+            "--flash.isthmus-time=20",
+            "--flash.prague-time",
+            "20",
+            "--http",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            remaining,
+            args(&["flash-node", "node", "--chain", "flash", "--http"])
+        );
+        assert_eq!(chain_args.genesis, Some(PathBuf::from("genesis.json")));
+        assert_eq!(chain_args.rollup_config, Some(PathBuf::from("rollup.json")));
+        assert_eq!(
+            chain_args.hardforks,
+            HardforkTimes {
+                holocene: Some(10),
+                isthmus: Some(20),
+                prague: Some(20),
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_other_args() {
+        let command = args(&["flash-node", "node", "--flash.dev", "--chain=flash-dev"]);
+        let (chain_args, remaining) = split_flash_args(command.clone()).unwrap();
+
+        assert_eq!(remaining, command);
+        assert_eq!(chain_args.genesis, None);
+        assert_eq!(chain_args.hardforks, HardforkTimes::default());
+    }
+
+    #[test]
+    fn rejects_invalid_flash_args() {
+        let error = split_flash_args(args(&["node", "--flash.genesis"])).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("--flash.genesis requires a value")
+        );
+
+        let error = split_flash_args(args(&["node", "--flash.holocene-time", "soon"])).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("Invalid --flash.holocene-time 'soon'")
+        );
+
+        assert!(split_flash_args(args(&["node", "--flash.isthmus-time=-1"])).is_err());
+    }
+}
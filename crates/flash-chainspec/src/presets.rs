@@ -0,0 +1,438 @@
+//! Named chains, each with its genesis, hardfork schedule and rollup config.
+use alloy_genesis::{Genesis, GenesisAccount};
+use alloy_primitives::{Address, B256, U256, address, keccak256};
+use reth_chainspec::ChainHardforks;
+use reth_op::chainspec::OpChainSpec;
+use reth_optimism_forks::{DEV_HARDFORKS, OP_SEPOLIA_HARDFORKS};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    FlashChainArgs, GENESIS_ENV, ROLLUP_CONFIG_ARG,
+    chainspec::{FLASH_GENESIS_HASH, flash_chain_from_genesis, load_genesis},
+    genesis::{L1Deployment, op_genesis, op_rollup_config},
+    hardforks::HardforkTimes,
+    rollup::{ChainOpConfig, RollupConfig},
+};
+
+/// The FLASH CHAIN.
+pub const FLASH: &str = "flash";
+/// Local devnet, with a prefunded dev account and every hardfork active from genesis.
+pub const FLASH_DEV: &str = "flash-dev";
+/// The FLASH CHAIN testnet.
+pub const FLASH_TESTNET: &str = "flash-testnet";
+
+/// Chain id of [`FLASH_DEV`].
+pub const FLASH_DEV_CHAIN_ID: u64 = 1337;
+/// L1 chain id of [`FLASH_DEV`], the mock L1 of dev mode.
+pub const FLASH_DEV_L1_CHAIN_ID: u64 = 900;
+
+/// Account prefunded on [`FLASH_DEV`], the first account of the `test test ... junk` mnemonic.
+///
+/// Its private key is public, never use it outside a devnet.
+pub const DEV_ACCOUNT: Address = address!("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+
+/// Balance of [`DEV_ACCOUNT`] at genesis in wei, a million ether.
+const DEV_ACCOUNT_BALANCE: u128 = 1_000_000 * 10u128.pow(18);
+
+/// Gas limit of the genesis blocks built in code.
+const GAS_LIMIT: u64 = 60_000_000;
+
+/// Where the genesis of a chain comes from.
+#[derive(Debug, Clone, Copy)]
+pub enum GenesisSource {
+    /// A genesis file, unless set with [`crate::GENESIS_ARG`] or [`GENESIS_ENV`].
+    File(&'static str),
+    /// A genesis built in code, so no file has to be checked in.
+    Built(fn() -> eyre::Result<Genesis>),
+}
+
+/// Where the rollup config of a chain comes from.
+#[derive(Debug, Clone, Copy)]
+pub enum RollupConfigSource {
+    /// A rollup config file, unless set with [`ROLLUP_CONFIG_ARG`]. It may be missing when the
+    /// genesis hash is pinned, the genesis is then checked against the hash instead.
+    File(&'static str),
+    /// A rollup config built in code, from the genesis and the L1 deployment of the chain.
+    Built(fn() -> L1Deployment),
+}
+
+/// A named chain `--chain` accepts.
+#[derive(Debug, Clone, Copy)]
+pub struct ChainPreset {
+    pub name: &'static str,
+    pub genesis: GenesisSource,
+    /// Hash the genesis must have, when the chain is already running.
+    pub genesis_hash: Option<B256>,
+    /// Hardforks of the chain, before Holocene, Isthmus and Prague are scheduled.
+    pub hardforks: fn() -> ChainHardforks,
+    /// Holocene, Isthmus and Prague activations, over the genesis ones and under the CLI
+    /// overrides.
+    pub schedule: HardforkTimes,
+    /// Rollup config the chain is checked against.
+    pub rollup_config: RollupConfigSource,
+}
+
+/// The named chains, in the order of [`crate::FlashChainSpecParser`]'s supported chains.
+pub static PRESETS: [ChainPreset; 3] = [
+    ChainPreset {
+        name: FLASH,
+        genesis: GenesisSource::File("config/genesis.json"),
+        genesis_hash: Some(FLASH_GENESIS_HASH),
+        // Our genesis.json was generated by an old version of op-deployer, predating Holocene,
+        // Isthmus and Prague, so they only activate when scheduled
+        hardforks: op_sepolia_hardforks,
+        schedule: HardforkTimes {
+            holocene: None,
+            isthmus: None,
+            prague: None,
+        },
+        rollup_config: RollupConfigSource::File("config/rollup.json"),
+    },
+    ChainPreset {
+        name: FLASH_DEV,
+        genesis: GenesisSource::Built(dev_genesis),
+        genesis_hash: None,
+        hardforks: dev_hardforks,
+        schedule: DEV_SCHEDULE,
+        // Dev mode runs without an op-node, on the mock L1 this config describes
+        rollup_config: RollupConfigSource::Built(dev_l1_deployment),
+    },
+    ChainPreset {
+        name: FLASH_TESTNET,
+        // Generated with the predeploys and L1 addresses of the testnet deployment
+        genesis: GenesisSource::File("config/flash-testnet/genesis.json"),
+        // Checked against the rollup config instead, which is required
+        genesis_hash: None,
+        hardforks: op_sepolia_hardforks,
+        // Scheduled in the genesis
+        schedule: HardforkTimes {
+            holocene: None,
+            isthmus: None,
+            prague: None,
+        },
+        rollup_config: RollupConfigSource::File("config/flash-testnet/rollup.json"),
+    },
+];
+
+/// Every hardfork of [`FLASH_DEV`] is active from genesis.
+const DEV_SCHEDULE: HardforkTimes = HardforkTimes {
+    holocene: Some(0),
+    isthmus: Some(0),
+    prague: Some(0),
+};
+
+fn op_sepolia_hardforks() -> ChainHardforks {
+    OP_SEPOLIA_HARDFORKS.clone()
+}
+
+fn dev_hardforks() -> ChainHardforks {
+    DEV_HARDFORKS.clone()
+}
+
+/// The [`FLASH_DEV`] genesis, prefunding [`DEV_ACCOUNT`].
+fn dev_genesis() -> eyre::Result<Genesis> {
+    let alloc = BTreeMap::from([(
+        DEV_ACCOUNT,
+        GenesisAccount {
+            balance: U256::from(DEV_ACCOUNT_BALANCE),
+            ..Default::default()
+        },
+    )]);
+
+    op_genesis(
+        FLASH_DEV_CHAIN_ID,
+        0,
+        GAS_LIMIT,
+        ChainOpConfig::OP_SEPOLIA,
+        DEV_SCHEDULE,
+        alloc,
+    )
+}
+
+/// The mock L1 of dev mode: it starts at block 0, and has no contracts. The batcher and fee
+/// scalars are zero, as in the L1 info deposits of dev mode.
+fn dev_l1_deployment() -> L1Deployment {
+    L1Deployment {
+        l1_chain_id: FLASH_DEV_L1_CHAIN_ID,
+        l1_genesis_hash: keccak256(0u64.to_be_bytes()),
+        l1_genesis_number: 0,
+        batch_inbox: Address::ZERO,
+        system_config: Address::ZERO,
+        deposit_contract: Address::ZERO,
+        protocol_versions: Address::ZERO,
+        batcher: Address::ZERO,
+        base_fee_scalar: 0,
+        blob_base_fee_scalar: 0,
+        block_time: 2,
+    }
+}
+
+/// Returns the named chain `name`.
+pub fn preset(name: &str) -> Option<&'static ChainPreset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+impl ChainPreset {
+    /// The spec of this chain, with the genesis, rollup config and hardfork overrides of `args`.
+    pub fn chainspec(&self, args: &FlashChainArgs) -> eyre::Result<OpChainSpec> {
+        let (genesis, path) = self.genesis(args)?;
+        let schedule = self.schedule.with_overrides(args.hardforks);
+        let rollup = match (&args.rollup_config, self.rollup_config) {
+            (Some(path), _) => Some(RollupConfig::load(path)?),
+            (None, RollupConfigSource::File(path)) if Path::new(path).exists() => {
+                Some(RollupConfig::load(Path::new(path))?)
+            }
+            (None, RollupConfigSource::File(path)) if self.genesis_hash.is_none() => {
+                return Err(eyre::eyre!(
+                    "The {} genesis is checked against its rollup config, but {} doesn't exist. Set it with {}",
+                    self.name,
+                    path,
+                    ROLLUP_CONFIG_ARG
+                ));
+            }
+            (None, RollupConfigSource::File(_)) => None,
+            (None, RollupConfigSource::Built(_)) => Some(
+                serde_json::from_value(self.built_rollup_config(&genesis, schedule)?)
+                    .map_err(|e| eyre::eyre!("Invalid {} rollup config: {}", self.name, e))?,
+            ),
+        };
+
+        let chainspec =
+            flash_chain_from_genesis(genesis, (self.hardforks)(), schedule, rollup.as_ref())?;
+
+        let hash = chainspec.inner.genesis_header.hash();
+        let mismatch = self.genesis_hash.filter(|expected| *expected != hash);
+        if let (Some(expected), Some(path)) = (mismatch, path) {
+            return Err(eyre::eyre!(
+                "Genesis file {} hashes to {}, but the {} genesis is {}. Use `--chain flash:{}` to run a different chain from it",
+                path.display(),
+                hash,
+                self.name,
+                expected,
+                path.display()
+            ));
+        }
+
+        Ok(chainspec)
+    }
+
+    /// The rollup config built for this chain from `genesis`. Fails if it's read from a file.
+    fn built_rollup_config(
+        &self,
+        genesis: &Genesis,
+        schedule: HardforkTimes,
+    ) -> eyre::Result<serde_json::Value> {
+        let RollupConfigSource::Built(l1_deployment) = self.rollup_config else {
+            return Err(eyre::eyre!(
+                "The {} rollup config is read from a file",
+                self.name
+            ));
+        };
+
+        let chainspec =
+            flash_chain_from_genesis(genesis.clone(), (self.hardforks)(), schedule, None)?;
+        let hardforks = HardforkTimes::from_genesis(genesis)?
+            .with_overrides(schedule)
+            .resolve()?;
+        op_rollup_config(
+            genesis,
+            chainspec.inner.genesis_header.hash(),
+            hardforks,
+            &l1_deployment(),
+        )
+    }
+
+    /// Returns the genesis of this chain, and the file it was read from.
+    fn genesis(&self, args: &FlashChainArgs) -> eyre::Result<(Genesis, Option<PathBuf>)> {
+        match self.genesis {
+            GenesisSource::File(default) => {
+                let path = args
+                    .genesis
+                    .clone()
+                    .or_else(|| std::env::var_os(GENESIS_ENV).map(PathBuf::from))
+                    .unwrap_or_else(|| PathBuf::from(default));
+                Ok((load_genesis(&path)?, Some(path)))
+            }
+            GenesisSource::Built(genesis) => Ok((
+                genesis()
+                    .map_err(|e| eyre::eyre!("Failed to build the {} genesis: {}", self.name, e))?,
+                None,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardforks::validate_hardforks;
+    use reth_chainspec::{EthereumHardfork, ForkCondition, Hardforks};
+    use reth_optimism_forks::OpHardfork;
+    use tempfile::NamedTempFile;
+
+    /// A genesis the OP Sepolia hardforks accept, with every fork active from it.
+    fn post_granite_genesis() -> Genesis {
+        let timestamp = 1_750_000_000;
+        op_genesis(
+            4210,
+            timestamp,
+            GAS_LIMIT,
+            ChainOpConfig::OP_SEPOLIA,
+            HardforkTimes {
+                holocene: Some(timestamp),
+                isthmus: Some(timestamp),
+                prague: Some(timestamp),
+            },
+            BTreeMap::new(),
+        )
+        .unwrap()
+    }
+
+    fn genesis_hash(genesis: &Genesis) -> B256 {
+        flash_chain_from_genesis(
+            genesis.clone(),
+            op_sepolia_hardforks(),
+            HardforkTimes::default(),
+            None,
+        )
+        .unwrap()
+        .inner
+        .genesis_header
+        .hash()
+    }
+
+    fn json_file(value: &impl serde::Serialize) -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), serde_json::to_string(value).unwrap()).unwrap();
+        file
+    }
+
+    #[test]
+    fn dev_preset_passes_validation() {
+        let chainspec = preset(FLASH_DEV)
+            .unwrap()
+            .chainspec(&FlashChainArgs::default())
+            .unwrap();
+        validate_hardforks(&chainspec).unwrap();
+
+        let genesis_time = chainspec.inner.genesis.timestamp;
+        let active = |fork: ForkCondition| fork.active_at_timestamp(genesis_time);
+        assert!(active(chainspec.fork(OpHardfork::Granite)));
+        assert!(active(chainspec.fork(OpHardfork::Holocene)));
+        assert!(active(chainspec.fork(OpHardfork::Isthmus)));
+        assert!(active(chainspec.fork(EthereumHardfork::Prague)));
+    }
+
+    #[test]
+    fn dev_account_is_prefunded() {
+        let chainspec = preset(FLASH_DEV)
+            .unwrap()
+            .chainspec(&FlashChainArgs::default())
+            .unwrap();
+
+        assert_eq!(chainspec.inner.genesis.config.chain_id, FLASH_DEV_CHAIN_ID);
+        assert_eq!(
+            chainspec.inner.genesis.alloc[&DEV_ACCOUNT].balance,
+            U256::from(DEV_ACCOUNT_BALANCE)
+        );
+    }
+
+    #[test]
+    fn dev_rollup_config_matches_the_genesis() {
+        let dev = preset(FLASH_DEV).unwrap();
+        let chainspec = dev.chainspec(&FlashChainArgs::default()).unwrap();
+
+        let rollup: RollupConfig = serde_json::from_value(
+            dev.built_rollup_config(&chainspec.inner.genesis, dev.schedule)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            rollup.genesis.l2.hash,
+            chainspec.inner.genesis_header.hash()
+        );
+        assert_eq!(rollup.genesis.l2_time, chainspec.inner.genesis.timestamp);
+        assert_eq!(rollup.l2_chain_id, FLASH_DEV_CHAIN_ID);
+        assert_eq!(rollup.chain_op_config, Some(ChainOpConfig::OP_SEPOLIA));
+    }
+
+    #[test]
+    fn testnet_requires_its_rollup_config() {
+        let genesis = post_granite_genesis();
+        let genesis_file = json_file(&genesis);
+        let mut args = FlashChainArgs {
+            genesis: Some(genesis_file.path().to_path_buf()),
+            ..Default::default()
+        };
+        let testnet = preset(FLASH_TESTNET).unwrap();
+
+        let error = testnet.chainspec(&args).unwrap_err().to_string();
+        assert!(
+            error.contains("config/flash-testnet/rollup.json doesn't exist"),
+            "{error}"
+        );
+
+        let hardforks = HardforkTimes::from_genesis(&genesis)
+            .unwrap()
+            .resolve()
+            .unwrap();
+        let rollup = |hash| {
+            json_file(&op_rollup_config(&genesis, hash, hardforks, &dev_l1_deployment()).unwrap())
+        };
+
+        let hash = genesis_hash(&genesis);
+        let rollup_file = rollup(hash);
+        args.rollup_config = Some(rollup_file.path().to_path_buf());
+        assert_eq!(
+            testnet
+                .chainspec(&args)
+                .unwrap()
+                .inner
+                .genesis_header
+                .hash(),
+            hash
+        );
+
+        // The rollup config of another chain
+        let rollup_file = rollup(B256::ZERO);
+        args.rollup_config = Some(rollup_file.path().to_path_buf());
+        assert!(testnet.chainspec(&args).is_err());
+    }
+
+    #[test]
+    fn rejects_genesis_files_of_other_chains() {
+        let genesis = post_granite_genesis();
+        let file = json_file(&genesis);
+        let args = FlashChainArgs {
+            genesis: Some(file.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let error = preset(FLASH).unwrap().chainspec(&args).unwrap_err();
+        let error = error.to_string();
+        assert!(error.contains(&FLASH_GENESIS_HASH.to_string()), "{error}");
+        assert!(error.contains("Use `--chain flash:"), "{error}");
+
+        // The same file is accepted by a chain expecting its hash
+        let hash = genesis_hash(&genesis);
+        let chain = ChainPreset {
+            genesis_hash: Some(hash),
+            ..*preset(FLASH).unwrap()
+        };
+        assert_eq!(
+            chain.chainspec(&args).unwrap().inner.genesis_header.hash(),
+            hash
+        );
+    }
+
+    #[test]
+    fn every_preset_is_supported() {
+        use reth_cli::chainspec::ChainSpecParser;
+
+        let names: Vec<_> = PRESETS.iter().map(|preset| preset.name).collect();
+        assert_eq!(names, crate::FlashChainSpecParser::SUPPORTED_CHAINS);
+    }
+}
@@ -0,0 +1,309 @@
+//! The parts of the op-node rollup config the execution client must agree with.
+use alloy_genesis::Genesis;
+use alloy_primitives::{B256, Bytes};
+use reth_op::chainspec::{BaseFeeParams, BaseFeeParamsKind, EthereumHardfork, Hardfork};
+use reth_optimism_forks::OpHardfork;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Genesis `config` field of the EIP-1559 parameters, as op-geth reads them.
+pub const GENESIS_OPTIMISM_FIELD: &str = "optimism";
+
+/// Version byte of the Holocene `extraData`.
+pub const HOLOCENE_EXTRA_DATA_VERSION: u8 = 0;
+
+/// EIP-1559 parameters of an OP chain, the rollup config `chain_op_config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainOpConfig {
+    pub eip1559_elasticity: u64,
+    pub eip1559_denominator: u64,
+    pub eip1559_denominator_canyon: u64,
+}
+
+impl ChainOpConfig {
+    /// The OP Sepolia parameters, used when neither the genesis nor the rollup config set any.
+    pub const OP_SEPOLIA: Self = Self {
+        eip1559_elasticity: 6,
+        eip1559_denominator: 50,
+        eip1559_denominator_canyon: 250,
+    };
+
+    /// Reads the parameters from the genesis `config.optimism`, if set.
+    pub fn from_genesis(genesis: &Genesis) -> eyre::Result<Option<Self>> {
+        genesis
+            .config
+            .extra_fields
+            .get_deserialized::<Self>(GENESIS_OPTIMISM_FIELD)
+            .transpose()
+            .map_err(|e| eyre::eyre!("Invalid genesis config {}: {}", GENESIS_OPTIMISM_FIELD, e))
+    }
+
+    /// Base fee parameters before and since Canyon. Since Holocene, blocks carry their own in
+    /// `extraData`, and these are only the default the payload builder falls back to.
+    pub fn base_fee_params(&self) -> BaseFeeParamsKind {
+        let elasticity = self.eip1559_elasticity as u128;
+
+        BaseFeeParamsKind::Variable(
+            vec![
+                (
+                    EthereumHardfork::London.boxed(),
+                    BaseFeeParams::new(self.eip1559_denominator as u128, elasticity),
+                ),
+                (
+                    OpHardfork::Canyon.boxed(),
+                    BaseFeeParams::new(self.eip1559_denominator_canyon as u128, elasticity),
+                ),
+            ]
+            .into(),
+        )
+    }
+}
+
+/// Genesis block the rollup config starts from.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RollupGenesis {
+    pub l2: RollupBlockId,
+    pub l2_time: u64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RollupBlockId {
+    pub hash: B256,
+    pub number: u64,
+}
+
+/// op-node rollup config, `rollup.json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RollupConfig {
+    pub genesis: RollupGenesis,
+    pub l2_chain_id: u64,
+    pub chain_op_config: Option<ChainOpConfig>,
+}
+
+impl RollupConfig {
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| eyre::eyre!("Failed to read rollup config {}: {}", path.display(), e))?;
+
+        serde_json::from_str(&config)
+            .map_err(|e| eyre::eyre!("Failed to parse rollup config {}: {}", path.display(), e))
+    }
+}
+
+/// Returns the EIP-1559 parameters of the chain, failing if the genesis and the rollup config
+/// disagree on them.
+pub fn chain_op_config(
+    genesis: &Genesis,
+    rollup: Option<&RollupConfig>,
+) -> eyre::Result<ChainOpConfig> {
+    let from_genesis = ChainOpConfig::from_genesis(genesis)?;
+    let from_rollup = rollup.and_then(|rollup| rollup.chain_op_config);
+
+    let config = match (from_genesis, from_rollup) {
+        (Some(genesis), Some(rollup)) if genesis != rollup => {
+            return Err(eyre::eyre!(
+                "Genesis EIP-1559 parameters {:?} differ from the rollup config ones {:?}",
+                genesis,
+                rollup
+            ));
+        }
+        (Some(config), _) | (None, Some(config)) => config,
+        (None, None) => ChainOpConfig::OP_SEPOLIA,
+    };
+
+    if config.eip1559_elasticity == 0
+        || config.eip1559_denominator == 0
+        || config.eip1559_denominator_canyon == 0
+    {
+        return Err(eyre::eyre!(
+            "EIP-1559 parameters {:?} must all be set",
+            config
+        ));
+    }
+
+    Ok(config)
+}
+
+/// Encodes Holocene EIP-1559 parameters in `extraData`: the version byte, then the denominator
+/// and elasticity as big endian `u32`s.
+pub fn encode_holocene_extra_data(params: BaseFeeParams) -> eyre::Result<Bytes> {
+    let denominator = u32::try_from(params.max_change_denominator).map_err(|_| {
+        eyre::eyre!(
+            "EIP-1559 denominator {} exceeds u32",
+            params.max_change_denominator
+        )
+    })?;
+    let elasticity = u32::try_from(params.elasticity_multiplier).map_err(|_| {
+        eyre::eyre!(
+            "EIP-1559 elasticity {} exceeds u32",
+            params.elasticity_multiplier
+        )
+    })?;
+
+    let mut extra_data = vec![HOLOCENE_EXTRA_DATA_VERSION];
+    extra_data.extend_from_slice(&denominator.to_be_bytes());
+    extra_data.extend_from_slice(&elasticity.to_be_bytes());
+    Ok(extra_data.into())
+}
+
+/// Decodes the EIP-1559 parameters of a Holocene block `extraData`.
+///
+/// Returns `None` when both are zero, meaning the Canyon parameters of the chain apply, as
+/// op-node encodes when the SystemConfig doesn't set any. A zero denominator is only valid with a
+/// zero elasticity.
+pub fn decode_holocene_extra_data(extra_data: &[u8]) -> eyre::Result<Option<BaseFeeParams>> {
+    let [HOLOCENE_EXTRA_DATA_VERSION, params @ ..] = extra_data else {
+        return Err(eyre::eyre!(
+            "Holocene extraData must start with version {}",
+            HOLOCENE_EXTRA_DATA_VERSION
+        ));
+    };
+    let [d0, d1, d2, d3, e0, e1, e2, e3] = *params else {
+        return Err(eyre::eyre!(
+            "Holocene extraData must be 9 bytes, not {}",
+            extra_data.len()
+        ));
+    };
+
+    let denominator = u32::from_be_bytes([d0, d1, d2, d3]);
+    let elasticity = u32::from_be_bytes([e0, e1, e2, e3]);
+    match (denominator, elasticity) {
+        (0, 0) => Ok(None),
+        (0, _) => Err(eyre::eyre!(
+            "Holocene extraData EIP-1559 denominator must be set with elasticity {}",
+            elasticity
+        )),
+        _ => Ok(Some(BaseFeeParams::new(
+            denominator as u128,
+            elasticity as u128,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{genesis::op_genesis, hardforks::HardforkTimes};
+
+    const CONFIG: ChainOpConfig = ChainOpConfig {
+        eip1559_elasticity: 2,
+        eip1559_denominator: 8,
+        eip1559_denominator_canyon: 50,
+    };
+
+    fn genesis(config: ChainOpConfig) -> Genesis {
+        op_genesis(
+            1,
+            0,
+            30_000_000,
+            config,
+            HardforkTimes::default(),
+            Default::default(),
+        )
+        .unwrap()
+    }
+
+    fn rollup(config: Option<ChainOpConfig>) -> RollupConfig {
+        RollupConfig {
+            genesis: RollupGenesis {
+                l2: RollupBlockId {
+                    hash: B256::ZERO,
+                    number: 0,
+                },
+                l2_time: 0,
+            },
+            l2_chain_id: 1,
+            chain_op_config: config,
+        }
+    }
+
+    #[test]
+    fn genesis_and_rollup_config_must_agree() {
+        let genesis = genesis(CONFIG);
+
+        assert_eq!(chain_op_config(&genesis, None).unwrap(), CONFIG);
+        assert_eq!(
+            chain_op_config(&genesis, Some(&rollup(Some(CONFIG)))).unwrap(),
+            CONFIG
+        );
+        assert_eq!(
+            chain_op_config(&genesis, Some(&rollup(None))).unwrap(),
+            CONFIG
+        );
+
+        let error =
+            chain_op_config(&genesis, Some(&rollup(Some(ChainOpConfig::OP_SEPOLIA)))).unwrap_err();
+        assert!(error.to_string().contains("differ from the rollup config"));
+    }
+
+    #[test]
+    fn defaults_to_op_sepolia_parameters() {
+        let mut genesis = genesis(CONFIG);
+        genesis.config.extra_fields.remove(GENESIS_OPTIMISM_FIELD);
+
+        assert_eq!(ChainOpConfig::from_genesis(&genesis).unwrap(), None);
+        assert_eq!(
+            chain_op_config(&genesis, None).unwrap(),
+            ChainOpConfig::OP_SEPOLIA
+        );
+        assert_eq!(
+            chain_op_config(&genesis, Some(&rollup(Some(CONFIG)))).unwrap(),
+            CONFIG
+        );
+    }
+
+    #[test]
+    fn rejects_unset_parameters() {
+        let config = ChainOpConfig {
+            eip1559_denominator_canyon: 0,
+            ..CONFIG
+        };
+
+        assert!(chain_op_config(&genesis(config), None).is_err());
+    }
+
+    #[test]
+    fn holocene_extra_data_round_trips() {
+        let params = BaseFeeParams::new(250, 6);
+        let extra_data = encode_holocene_extra_data(params).unwrap();
+
+        assert_eq!(
+            extra_data.as_ref(),
+            [0, 0, 0, 0, 250, 0, 0, 0, 6].as_slice()
+        );
+        assert_eq!(
+            decode_holocene_extra_data(&extra_data).unwrap(),
+            Some(params)
+        );
+
+        // The largest parameters the encoding holds
+        let params = BaseFeeParams::new(u32::MAX as u128, u32::MAX as u128);
+        let extra_data = encode_holocene_extra_data(params).unwrap();
+        assert_eq!(
+            decode_holocene_extra_data(&extra_data).unwrap(),
+            Some(params)
+        );
+    }
+
+    #[test]
+    fn holocene_extra_data_edge_cases() {
+        // Zero parameters select the Canyon ones
+        assert_eq!(decode_holocene_extra_data(&[0; 9]).unwrap(), None);
+        assert!(decode_holocene_extra_data(&[0, 0, 0, 0, 0, 0, 0, 0, 6]).is_err());
+        // A zero elasticity is kept when the denominator is set
+        assert_eq!(
+            decode_holocene_extra_data(&[0, 0, 0, 0, 8, 0, 0, 0, 0]).unwrap(),
+            Some(BaseFeeParams::new(8, 0))
+        );
+
+        // Only 9 bytes starting with version 0 are valid
+        assert!(decode_holocene_extra_data(&[]).is_err());
+        assert!(decode_holocene_extra_data(&[0; 8]).is_err());
+        assert!(decode_holocene_extra_data(&[0; 10]).is_err());
+        assert!(decode_holocene_extra_data(&[1, 0, 0, 0, 8, 0, 0, 0, 6]).is_err());
+
+        assert!(encode_holocene_extra_data(BaseFeeParams::new(u32::MAX as u128 + 1, 6)).is_err());
+        assert!(encode_holocene_extra_data(BaseFeeParams::new(8, u32::MAX as u128 + 1)).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use alloy_primitives::{B256, Bytes, hex, keccak256};
+use std::fmt;
+
+/// Derivation version byte of L1 transactions carrying an alt-DA commitment.
+pub const ALT_DA_TX_DATA_VERSION: u8 = 0x01;
+
+/// Commitment type byte of keccak commitments.
+pub const KECCAK_COMMITMENT_TYPE: u8 = 0x00;
+/// Commitment type byte of generic commitments.
+pub const GENERIC_COMMITMENT_TYPE: u8 = 0x01;
+
+/// DA layer byte of generic commitments to the file store.
+pub const FILE_DA_LAYER: u8 = 0xfd;
+
+/// Reference to data stored on an alt-DA server, following the OP alt-DA specs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Commitment {
+    /// Keccak256 hash of the data, computed by the client.
+    Keccak(B256),
+    /// Opaque commitment returned by the DA server.
+    Generic { da_layer: u8, payload: Bytes },
+}
+
+impl Commitment {
+    /// Keccak commitment to `data`.
+    pub fn keccak(data: &[u8]) -> Self {
+        Self::Keccak(keccak256(data))
+    }
+
+    /// Generic commitment of the file store to `data`, content-addressed like keccak ones.
+    pub fn file(data: &[u8]) -> Self {
+        Self::Generic {
+            da_layer: FILE_DA_LAYER,
+            payload: keccak256(data).into(),
+        }
+    }
+
+    /// Encodes the commitment, prefixed with its type byte.
+    pub fn encode(&self) -> Bytes {
+        let mut encoded = Vec::new();
+        match self {
+            Self::Keccak(hash) => {
+                encoded.push(KECCAK_COMMITMENT_TYPE);
+                encoded.extend_from_slice(hash.as_slice());
+            }
+            Self::Generic { da_layer, payload } => {
+                encoded.push(GENERIC_COMMITMENT_TYPE);
+                encoded.push(*da_layer);
+                encoded.extend_from_slice(payload);
+            }
+        }
+        encoded.into()
+    }
+
+    pub fn decode(data: &[u8]) -> eyre::Result<Self> {
+        match data {
+            [KECCAK_COMMITMENT_TYPE, hash @ ..] if hash.len() == 32 => {
+                Ok(Self::Keccak(B256::from_slice(hash)))
+            }
+            [KECCAK_COMMITMENT_TYPE, hash @ ..] => Err(eyre::eyre!(
+                "Invalid keccak commitment length: {} bytes",
+                hash.len()
+            )),
+            [GENERIC_COMMITMENT_TYPE, da_layer, payload @ ..] => Ok(Self::Generic {
+                da_layer: *da_layer,
+                payload: Bytes::copy_from_slice(payload),
+            }),
+            [commitment_type, ..] => Err(eyre::eyre!(
+                "Unknown commitment type 0x{:02x}",
+                commitment_type
+            )),
+            [] => Err(eyre::eyre!("Empty commitment")),
+        }
+    }
+
+    /// Data of the L1 transaction posting the commitment.
+    pub fn tx_data(&self) -> Bytes {
+        [&[ALT_DA_TX_DATA_VERSION], self.encode().as_ref()]
+            .concat()
+            .into()
+    }
+
+    /// Whether the commitment matches `data`. Generic commitments are opaque, and always match.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::Keccak(hash) => keccak256(data) == *hash,
+            Self::Generic { .. } => true,
+        }
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode_prefixed(self.encode()))
+    }
+}
+
+impl std::str::FromStr for Commitment {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = hex::decode(s).map_err(|e| eyre::eyre!("Invalid commitment '{}': {}", s, e))?;
+        Self::decode(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::hex;
+
+    #[test]
+    fn keccak_commitments_round_trip() {
+        let commitment = Commitment::keccak(b"channel");
+        let encoded = commitment.encode();
+        assert_eq!(encoded.len(), 33);
+        assert_eq!(encoded[0], KECCAK_COMMITMENT_TYPE);
+        assert_eq!(&encoded[1..], keccak256(b"channel").as_slice());
+
+        assert_eq!(Commitment::decode(&encoded).unwrap(), commitment);
+        assert_eq!(
+            commitment.to_string().parse::<Commitment>().unwrap(),
+            commitment
+        );
+        assert!(commitment.to_string().starts_with("0x00"));
+    }
+
+    #[test]
+    fn generic_commitments_round_trip() {
+        let commitment = Commitment::file(b"channel");
+        let encoded = commitment.encode();
+        assert_eq!(&encoded[..2], &[GENERIC_COMMITMENT_TYPE, FILE_DA_LAYER]);
+
+        assert_eq!(Commitment::decode(&encoded).unwrap(), commitment);
+        assert_eq!(
+            commitment.to_string().parse::<Commitment>().unwrap(),
+            commitment
+        );
+
+        // Payloads are opaque, even empty
+        let empty = Commitment::Generic {
+            da_layer: 0x0c,
+            payload: Bytes::new(),
+        };
+        assert_eq!(Commitment::decode(&empty.encode()).unwrap(), empty);
+    }
+
+    #[test]
+    fn tx_data_is_versioned() {
+        let commitment = Commitment::keccak(b"channel");
+        let tx_data = commitment.tx_data();
+        assert_eq!(tx_data[0], ALT_DA_TX_DATA_VERSION);
+        assert_eq!(&tx_data[1..], commitment.encode().as_ref());
+    }
+
+    #[test]
+    fn matches_keccak_data() {
+        assert!(Commitment::keccak(b"channel").matches(b"channel"));
+        assert!(!Commitment::keccak(b"channel").matches(b"other channel"));
+        assert!(Commitment::file(b"channel").matches(b"other channel"));
+    }
+
+    #[test]
+    fn rejects_invalid_commitments() {
+        assert!(Commitment::decode(&[]).is_err());
+        assert!(Commitment::decode(&[KECCAK_COMMITMENT_TYPE; 32]).is_err());
+        assert!(Commitment::decode(&[0x02, 0xaa]).is_err());
+        // A generic commitment needs its DA layer byte
+        assert!(Commitment::decode(&[GENERIC_COMMITMENT_TYPE]).is_err());
+
+        assert!("0x00zz".parse::<Commitment>().is_err());
+        assert!(
+            format!("0x00{}", hex::encode([0u8; 31]))
+                .parse::<Commitment>()
+                .is_err()
+        );
+    }
+}
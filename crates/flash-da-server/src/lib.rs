@@ -0,0 +1,357 @@
+//! File-backed alt-DA server, implementing the OP alt-DA HTTP API.
+//!
+//! - `PUT /put/<commitment>`: stores the body under a keccak commitment computed by the client
+//! - `PUT /put`: stores the body, and returns its generic commitment
+//! - `GET /get/<commitment>`: returns the data stored under the commitment
+//!
+//! Commitments are hex encoded with their type byte, e.g. `0x00<keccak256>` for keccak ones.
+//! Bodies over [`MAX_BODY_SIZE`] are rejected.
+
+use clap::Args;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use std::{
+    convert::Infallible,
+    fs,
+    io::{self, ErrorKind},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::net::TcpListener;
+use tracing::{debug, error, info, warn};
+
+pub mod commitment;
+
+use commitment::Commitment;
+
+/// Default address of the DA server.
+pub const DEFAULT_ADDR: &str = "127.0.0.1:3100";
+/// Default directory of the file store.
+pub const DEFAULT_DATA_DIR: &str = "da-data";
+/// Maximum size of the data put, in bytes.
+pub const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+/// Stores data in a directory, one file per commitment.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, commitment: &Commitment) -> PathBuf {
+        self.dir.join(commitment.to_string())
+    }
+
+    pub fn put(&self, commitment: &Commitment, data: &[u8]) -> io::Result<()> {
+        let path = self.path(commitment);
+
+        // Write then rename, so a commitment never points to partial data
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)
+    }
+
+    pub fn get(&self, commitment: &Commitment) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(commitment)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Serves the store on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, store: FileStore) -> eyre::Result<()> {
+    let store = Arc::new(store);
+    info!("DA server listening on {}", listener.local_addr()?);
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let store = store.clone();
+
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(store.clone(), req));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    store: Arc<FileStore>,
+    req: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    debug!("{} {}", method, path);
+
+    let response = match (method, path.as_str()) {
+        (Method::GET, path) if path.starts_with("/get/") => get(&store, &path["/get/".len()..]),
+        (Method::PUT | Method::POST, "/put" | "/put/") => match read_body(req).await {
+            Ok(data) => put_generic(&store, &data),
+            Err(response) => response,
+        },
+        (Method::PUT | Method::POST, path) if path.starts_with("/put/") => {
+            let commitment = path["/put/".len()..].to_string();
+            match read_body(req).await {
+                Ok(data) => put_keccak(&store, &commitment, &data),
+                Err(response) => response,
+            }
+        }
+        _ => respond(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    Ok(response)
+}
+
+fn get(store: &FileStore, commitment: &str) -> Response<Full<Bytes>> {
+    let commitment = match commitment.parse::<Commitment>() {
+        Ok(commitment) => commitment,
+        Err(e) => return respond(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    match store.get(&commitment) {
+        Ok(Some(data)) => respond(StatusCode::OK, data),
+        Ok(None) => respond(StatusCode::NOT_FOUND, "Not found"),
+        Err(e) => {
+            error!("Failed to read {}: {}", commitment, e);
+            respond(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read data")
+        }
+    }
+}
+
+fn put_generic(store: &FileStore, data: &[u8]) -> Response<Full<Bytes>> {
+    let commitment = Commitment::file(data);
+    match store.put(&commitment, data) {
+        Ok(()) => {
+            info!("Stored {} bytes under {}", data.len(), commitment);
+            respond(StatusCode::OK, commitment.encode())
+        }
+        Err(e) => {
+            error!("Failed to store {}: {}", commitment, e);
+            respond(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store data")
+        }
+    }
+}
+
+fn put_keccak(store: &FileStore, commitment: &str, data: &[u8]) -> Response<Full<Bytes>> {
+    let commitment = match commitment.parse::<Commitment>() {
+        Ok(commitment @ Commitment::Keccak(_)) => commitment,
+        Ok(_) => {
+            return respond(
+                StatusCode::BAD_REQUEST,
+                "Only keccak commitments can be put by the client",
+            );
+        }
+        Err(e) => return respond(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    if !commitment.matches(data) {
+        warn!("Rejected data not matching {}", commitment);
+        return respond(
+            StatusCode::BAD_REQUEST,
+            "Commitment does not match the data",
+        );
+    }
+
+    match store.put(&commitment, data) {
+        Ok(()) => {
+            info!("Stored {} bytes under {}", data.len(), commitment);
+            respond(StatusCode::OK, Bytes::new())
+        }
+        Err(e) => {
+            error!("Failed to store {}: {}", commitment, e);
+            respond(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store data")
+        }
+    }
+}
+
+async fn read_body(req: Request<Incoming>) -> Result<Bytes, Response<Full<Bytes>>> {
+    Limited::new(req.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map(|body| body.to_bytes())
+        .map_err(|e| {
+            if e.is::<LengthLimitError>() {
+                respond(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Body exceeds {MAX_BODY_SIZE} bytes"),
+                )
+            } else {
+                respond(StatusCode::BAD_REQUEST, e.to_string())
+            }
+        })
+}
+
+fn respond(status: StatusCode, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Run a file-backed alt-DA server.
+#[derive(Debug, Args)]
+pub struct DaServerCommand {
+    /// Address to listen on.
+    #[arg(long, default_value = DEFAULT_ADDR)]
+    pub addr: SocketAddr,
+
+    /// Directory the data is stored in.
+    #[arg(long, value_name = "PATH", default_value = DEFAULT_DATA_DIR)]
+    pub dir: PathBuf,
+}
+
+impl DaServerCommand {
+    pub fn execute(self) -> eyre::Result<()> {
+        let store = FileStore::new(&self.dir)
+            .map_err(|e| eyre::eyre!("Failed to open {}: {}", self.dir.display(), e))?;
+
+        tokio::runtime::Runtime::new()?.block_on(async move {
+            let listener = TcpListener::bind(self.addr).await?;
+            serve(listener, store).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper_util::client::legacy::Client;
+
+    /// Serves a store in a temporary directory, returning its URL.
+    async fn server() -> (String, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, store));
+        (url, dir)
+    }
+
+    async fn request(method: Method, uri: String, body: Vec<u8>) -> (StatusCode, Bytes) {
+        let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build_http();
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Full::new(Bytes::from(body)))
+            .unwrap();
+        let response = client.request(request).await.unwrap();
+        let status = response.status();
+        (
+            status,
+            response.into_body().collect().await.unwrap().to_bytes(),
+        )
+    }
+
+    #[tokio::test]
+    async fn puts_and_gets_keccak_commitments() {
+        let (url, _dir) = server().await;
+        let commitment = Commitment::keccak(b"channel");
+
+        let (status, _) = request(
+            Method::PUT,
+            format!("{url}/put/{commitment}"),
+            b"channel".to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) =
+            request(Method::GET, format!("{url}/get/{commitment}"), Vec::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_ref(), b"channel");
+    }
+
+    #[tokio::test]
+    async fn rejects_data_not_matching_the_keccak_commitment() {
+        let (url, _dir) = server().await;
+        let commitment = Commitment::keccak(b"channel");
+
+        let (status, body) = request(
+            Method::PUT,
+            format!("{url}/put/{commitment}"),
+            b"other channel".to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.as_ref(), b"Commitment does not match the data");
+
+        // Nothing was stored
+        let (status, _) = request(Method::GET, format!("{url}/get/{commitment}"), Vec::new()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn puts_generic_commitments() {
+        let (url, _dir) = server().await;
+
+        let (status, body) = request(Method::PUT, format!("{url}/put"), b"channel".to_vec()).await;
+        assert_eq!(status, StatusCode::OK);
+        let commitment = Commitment::decode(&body).unwrap();
+        assert_eq!(commitment, Commitment::file(b"channel"));
+
+        let (status, body) =
+            request(Method::GET, format!("{url}/get/{commitment}"), Vec::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_ref(), b"channel");
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_over_the_max_size() {
+        let (url, _dir) = server().await;
+
+        let (status, _) = request(
+            Method::PUT,
+            format!("{url}/put"),
+            vec![1; MAX_BODY_SIZE + 1],
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_commitments() {
+        let (url, _dir) = server().await;
+
+        let (status, _) = request(Method::GET, format!("{url}/get/0x02aa"), Vec::new()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Generic commitments are computed by the server
+        let generic = Commitment::file(b"channel");
+        let (status, _) = request(
+            Method::PUT,
+            format!("{url}/put/{generic}"),
+            b"channel".to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn store_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+        let commitment = Commitment::keccak(b"channel");
+
+        assert_eq!(store.get(&commitment).unwrap(), None);
+        store.put(&commitment, b"channel").unwrap();
+        assert_eq!(store.get(&commitment).unwrap(), Some(b"channel".to_vec()));
+    }
+}